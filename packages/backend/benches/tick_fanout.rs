@@ -0,0 +1,105 @@
+// packages/backend/benches/tick_fanout.rs
+// Tick 扇出基准：直接调用 token_manager::fan_out_tick，把一笔 Tick 应用到同一代币的 N 个周期房间 (不含上游连接与解析)，
+// 同时有任务持续对该代币的索引做订阅/退订写入；对比快照扇出 (订阅者达到阈值) 与直接遍历索引的单笔 Tick 耗时。
+// 运行: cargo bench --bench tick_fanout
+use backend::{
+    config::Config,
+    state::{self, AppState, RoomIndex, WorkerMetrics},
+    token_manager::{self, TickUpdate},
+    types::{Room, TradeSide},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use socketioxide::{extract::SocketRef, SocketIo};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+const POOL_ID: i64 = 16;
+const ADDRESS: &str = "MockSoLToken1111111111111111111111111111pump";
+/// 每个代币的房间数 (周期 1m..Nm，每个周期一个房间)
+const ROOM_COUNTS: [usize; 3] = [6, 32, 128];
+
+struct Fixture {
+    io: SocketIo,
+    config: Config,
+    metrics: WorkerMetrics,
+    app_state: AppState,
+    room_index: RoomIndex,
+}
+
+impl Fixture {
+    fn new(rooms: usize) -> Self {
+        let (_layer, io) = SocketIo::new_layer();
+        io.ns("/", |_s: SocketRef| async {});
+        let app_state = state::new_app_state();
+        let room_index = state::new_room_index();
+        for minutes in 1..=rooms {
+            let key = format!("kl@{}@{}@{}m", POOL_ID, ADDRESS, minutes);
+            app_state.insert(
+                key.clone(),
+                Room {
+                    clients: HashSet::new(),
+                    symbol: "MOCK".to_string(),
+                    current_kline: Arc::new(Mutex::new(None)),
+                    last_emitted_kline: Arc::new(Mutex::new(None)),
+                },
+            );
+            room_index.entry(ADDRESS.to_string()).or_default().insert(key);
+        }
+        Self { io, config: Config::new(), metrics: WorkerMetrics::default(), app_state, room_index }
+    }
+
+    async fn fan_out(&self, subscribers: usize, update: &TickUpdate) {
+        token_manager::fan_out_tick(&self.io, &self.config, &self.metrics, &self.app_state, &self.room_index, ADDRESS, subscribers, update)
+            .await
+            .expect("token rooms are indexed");
+    }
+}
+
+fn bench_tick_fanout(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("tick_fanout");
+    for rooms in ROOM_COUNTS {
+        let fixture = Arc::new(rt.block_on(async { Fixture::new(rooms) }));
+
+        // 其他客户端持续订阅/退订同一代币：写入与 Tick 扇出争用同一个索引分片
+        let churn_index = fixture.room_index.clone();
+        let churn = rt.spawn(async move {
+            let churn_room = format!("kl@{}@{}@churn", POOL_ID, ADDRESS);
+            loop {
+                churn_index.entry(ADDRESS.to_string()).or_default().insert(churn_room.clone());
+                churn_index.entry(ADDRESS.to_string()).or_default().remove(&churn_room);
+                tokio::task::yield_now().await;
+            }
+        });
+
+        group.throughput(Throughput::Elements(rooms as u64));
+        // 默认阈值 10：usize::MAX 个订阅者走快照，0 个订阅者直接遍历索引
+        for (mode, subscribers) in [("snapshot", usize::MAX), ("direct", 0)] {
+            group.bench_with_input(BenchmarkId::new(mode, rooms), &subscribers, |b, &subscribers| {
+                b.to_async(&rt).iter_custom(|iters| {
+                    let fixture = fixture.clone();
+                    async move {
+                        let started = Instant::now();
+                        for i in 0..iters {
+                            let update = TickUpdate {
+                                price: 1.0 + (i % 100) as f64 * 0.001,
+                                usd_volume: 500.0,
+                                side: Some(TradeSide::Buy),
+                                time: 0,
+                            };
+                            fixture.fan_out(subscribers, &update).await;
+                        }
+                        started.elapsed()
+                    }
+                })
+            });
+        }
+        churn.abort();
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_tick_fanout);
+criterion_main!(benches);
@@ -1,11 +1,19 @@
 use super::{
-    types::{AlertLogEntry, AlertType, HotlistItem},
+    config::Config,
+    socket_handlers::{chain_to_pool_id, normalize_address},
+    types::{
+        AlertComparator, AlertLogEntry, AlertRule, AlertRuleSource, AlertThresholds, AlertType,
+        HotlistItem, MemeScanItem,
+    },
     ServerState,
 };
+use serde::Serialize;
+use serde_json::{Map, Value};
 use socketioxide::SocketIo;
+use std::sync::atomic::Ordering;
 use chrono::Utc;
 use uuid::Uuid;
-use tracing::info;
+use tracing::{info, warn};
 
 // ============== 报警阈值配置 ==============
 pub const ALERT_VOLUME_1M_USD: f64 = 5000.0;
@@ -14,81 +22,284 @@ pub const ALERT_PRICE_CHANGE_1M_PERCENT: f64 = 5.0;
 pub const ALERT_PRICE_CHANGE_5M_PERCENT: f64 = 25.0;
 pub const ALERT_PRICE_CHANGE_1M_MIN_VOLUME_USD: f64 = 1000.0;  // 价格异动需满足的最小成交额
 pub const ALERT_PRICE_CHANGE_5M_MIN_VOLUME_USD: f64 = 5000.0;
-pub const ALERT_COOLDOWN_MS: i64 = 60_000; // 1 分钟冷却
 pub const MAX_ALERT_HISTORY: usize = 50;
 
+/// 启动时的报警阈值：成交额/涨跌幅取上面的默认值，买压阈值取自配置
+pub fn initial_alert_thresholds(config: &Config) -> AlertThresholds {
+    AlertThresholds {
+        volume_1m_usd: ALERT_VOLUME_1M_USD,
+        volume_5m_usd: ALERT_VOLUME_5M_USD,
+        price_change_1m_percent: ALERT_PRICE_CHANGE_1M_PERCENT,
+        price_change_5m_percent: ALERT_PRICE_CHANGE_5M_PERCENT,
+        price_change_1m_min_volume_usd: ALERT_PRICE_CHANGE_1M_MIN_VOLUME_USD,
+        price_change_5m_min_volume_usd: ALERT_PRICE_CHANGE_5M_MIN_VOLUME_USD,
+        buy_pressure_min_ratio: config.alert_buy_pressure_min_ratio,
+        buy_pressure_min_buys: config.alert_buy_pressure_min_buys,
+        buy_pressure_max_top10_percent: config.alert_buy_pressure_max_top10_percent,
+    }
+}
+
+/// 内置规则：原先硬编码的成交额 / 涨跌幅规则，阈值取自运行时可调的 AlertThresholds
+pub fn builtin_rules(thresholds: &AlertThresholds) -> Vec<(AlertType, AlertRule)> {
+    let volume_rule = |alert_type: AlertType, field: &str, threshold: f64, label: &str| {
+        let rule = AlertRule {
+            name: alert_type.as_str().to_string(),
+            source: AlertRuleSource::Hotlist,
+            field: field.to_string(),
+            comparator: AlertComparator::Gt,
+            threshold,
+            min_volume_field: None,
+            min_volume: 0.0,
+            message: format!("{{symbol}} {} {{value}}美金", label),
+            decimals: 0,
+        };
+        (alert_type, rule)
+    };
+    let price_rule = |alert_type: AlertType, field: &str, threshold: f64, volume_field: &str, min_volume: f64, label: &str| {
+        let rule = AlertRule {
+            name: alert_type.as_str().to_string(),
+            source: AlertRuleSource::Hotlist,
+            field: field.to_string(),
+            comparator: AlertComparator::AbsGt,
+            threshold,
+            min_volume_field: Some(volume_field.to_string()),
+            min_volume,
+            message: format!("{{symbol}} {}{{direction}}{{abs}}%", label),
+            decimals: 1,
+        };
+        (alert_type, rule)
+    };
+
+    vec![
+        volume_rule(AlertType::Volume1m, "volume1mUsd", thresholds.volume_1m_usd, "1分钟"),
+        volume_rule(AlertType::Volume5m, "volume5mUsd", thresholds.volume_5m_usd, "5分钟"),
+        // 价格异动需满足最小成交额
+        price_rule(
+            AlertType::PriceChange1m, "priceChange1m", thresholds.price_change_1m_percent,
+            "volume1mUsd", thresholds.price_change_1m_min_volume_usd, "1分钟",
+        ),
+        price_rule(
+            AlertType::PriceChange5m, "priceChange5m", thresholds.price_change_5m_percent,
+            "volume5mUsd", thresholds.price_change_5m_min_volume_usd, "5分钟",
+        ),
+    ]
+}
+
+/// 配置中作用于指定数据源的自定义规则
+fn custom_rules(state: &ServerState, source: AlertRuleSource) -> Vec<(AlertType, AlertRule)> {
+    state
+        .config
+        .alert_rules
+        .iter()
+        .filter(|rule| rule.source == source)
+        .map(|rule| (AlertType::Custom, rule.clone()))
+        .collect()
+}
+
 pub async fn check_and_trigger_alerts(
     items: &[HotlistItem],
     state: &ServerState,
     io: &SocketIo,
+) {
+    // 取一份快照，整批数据使用同一组阈值
+    let thresholds = state.alert_thresholds.read().await.clone();
+    let mut rules = builtin_rules(&thresholds);
+    rules.extend(custom_rules(state, AlertRuleSource::Hotlist));
+    evaluate_rules(items, &rules, state, io).await;
+}
+
+/// Meme 数据 (新币 / 已迁移) 上的自定义规则
+pub async fn check_meme_rule_alerts(
+    items: &[MemeScanItem],
+    state: &ServerState,
+    io: &SocketIo,
+) {
+    let rules = custom_rules(state, AlertRuleSource::Meme);
+    if rules.is_empty() {
+        return;
+    }
+    evaluate_rules(items, &rules, state, io).await;
+}
+
+/// 通用规则评估：数据项按 serde 字段名取值，满足比较条件和成交额门槛时触发报警
+async fn evaluate_rules<T: Serialize>(
+    items: &[T],
+    rules: &[(AlertType, AlertRule)],
+    state: &ServerState,
+    io: &SocketIo,
 ) {
     let now = Utc::now().timestamp_millis();
     for item in items {
-        let chain = &item.chain;
-        let addr = &item.contract_address;
-        
+        let Ok(Value::Object(fields)) = serde_json::to_value(item) else {
+            continue;
+        };
+        let text = |name: &str| fields.get(name).and_then(Value::as_str).unwrap_or_default();
+        let (chain, addr, symbol) = (text("chain"), text("contractAddress"), text("symbol"));
+
+        if !is_alert_chain(state, chain) {
+            continue;
+        }
+
         // ✨ 新增：黑名单过滤 (不发送屏蔽品种的报警)
         if state.blacklist.contains(&addr.to_lowercase()) {
             continue;
         }
 
-        let symbol = &item.symbol;
-        let price = item.price.unwrap_or(0.0);
-        
-        // 计算成交额 (原始数据是 volume，需乘以价格得到 USD)
-        let volume_1m_usd = item.volume1m.unwrap_or(0.0) * price;
-        let volume_5m_usd = item.volume5m.unwrap_or(0.0) * price;
+        for (alert_type, rule) in rules {
+            let Some(value) = rule_field_value(&fields, &rule.field) else {
+                continue;
+            };
+            let passes = match rule.comparator {
+                AlertComparator::Gt => value > rule.threshold,
+                AlertComparator::Lt => value < rule.threshold,
+                AlertComparator::AbsGt => value.abs() > rule.threshold,
+            };
+            if !passes {
+                continue;
+            }
+            if let Some(volume_field) = &rule.min_volume_field {
+                let volume = rule_field_value(&fields, volume_field).unwrap_or(0.0);
+                if volume <= rule.min_volume {
+                    continue;
+                }
+            }
 
-        // --- 规则 1: 1 分钟成交额 ---
-        if volume_1m_usd > ALERT_VOLUME_1M_USD {
             try_trigger_alert(
                 state, io, chain, addr, symbol,
-                AlertType::Volume1m,
-                format!("{} 1分钟 {}美金", symbol, volume_1m_usd.round() as i64),
+                alert_type.clone(),
+                &rule.name,
+                render_alert_message(rule, symbol, value),
                 now,
             ).await;
         }
+    }
+}
 
-        // --- 规则 2: 5 分钟成交额 ---
-        if volume_5m_usd > ALERT_VOLUME_5M_USD {
-            try_trigger_alert(
-                state, io, chain, addr, symbol,
-                AlertType::Volume5m,
-                format!("{} 5分钟 {}美金", symbol, volume_5m_usd.round() as i64),
-                now,
-            ).await;
+/// 取规则字段的数值 (数字或数字字符串)；字段不存在时，xxxUsd 视为 xxx × price
+/// (原始数据的 volume 是代币数量，需乘以价格得到 USD)
+fn rule_field_value(fields: &Map<String, Value>, field: &str) -> Option<f64> {
+    let number = |value: &Value| match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    if let Some(value) = fields.get(field) {
+        return number(value);
+    }
+    let base = field.strip_suffix("Usd")?;
+    Some(number(fields.get(base)?)? * number(fields.get("price")?)?)
+}
+
+/// 渲染报警文本：{symbol} {value} {abs} {direction}
+fn render_alert_message(rule: &AlertRule, symbol: &str, value: f64) -> String {
+    let direction = if value > 0.0 { "上涨" } else { "下跌" };
+    rule.message
+        .replace("{symbol}", symbol)
+        .replace("{value}", &format!("{:.*}", rule.decimals, value))
+        .replace("{abs}", &format!("{:.*}", rule.decimals, value.abs()))
+        .replace("{direction}", direction)
+}
+
+/// 迁移报警：代币迁移到 DEX 是一次性事件，默认配置为一次性报警，同一地址只报一次
+pub async fn check_migration_alerts(
+    items: &[MemeScanItem],
+    state: &ServerState,
+    io: &SocketIo,
+) {
+    let now = Utc::now().timestamp_millis();
+    for item in items {
+        let addr = &item.contract_address;
+        if !is_alert_chain(state, &item.chain) || state.blacklist.contains(&addr.to_lowercase()) {
+            continue;
         }
 
-        // --- 规则 3: 1 分钟涨跌幅 (需满足最小成交额) ---
-        let pc_1m = item.price_change1m.unwrap_or(0.0);
-        if pc_1m.abs() > ALERT_PRICE_CHANGE_1M_PERCENT
-            && volume_1m_usd > ALERT_PRICE_CHANGE_1M_MIN_VOLUME_USD
-        {
-            let direction = if pc_1m > 0.0 { "上涨" } else { "下跌" };
-            try_trigger_alert(
-                state, io, chain, addr, symbol,
-                AlertType::PriceChange1m,
-                format!("{} 1分钟{}{:.1}%", symbol, direction, pc_1m.abs()),
-                now,
-            ).await;
+        try_trigger_alert(
+            state, io, &item.chain, addr, &item.symbol,
+            AlertType::Migration,
+            AlertType::Migration.as_str(),
+            format!("{} 已迁移到 DEX", item.symbol),
+            now,
+        ).await;
+    }
+}
+
+/// 实时逐笔买卖统计 (买卖比, 买入笔数)：该代币有运行中的 Worker 时，取其启动以来按 tp 分类的成交笔数
+fn live_buy_pressure(state: &ServerState, chain: &str, addr: &str) -> Option<(f64, i64)> {
+    let pool_id = chain_to_pool_id(chain)?;
+    let metrics = state.worker_status.get(&normalize_address(pool_id, addr))?;
+    let buys = metrics.buy_ticks.load(Ordering::Relaxed);
+    let sells = metrics.sell_ticks.load(Ordering::Relaxed);
+    Some((buys as f64 / sells.max(1) as f64, i64::try_from(buys).unwrap_or(i64::MAX)))
+}
+
+/// 买压报警：新 Meme 买卖比飙升且筹码不过度集中时触发 (扫描数据或实时逐笔统计任一满足即可)
+pub async fn check_buy_pressure_alerts(
+    items: &[MemeScanItem],
+    state: &ServerState,
+    io: &SocketIo,
+) {
+    let thresholds = state.alert_thresholds.read().await.clone();
+    let now = Utc::now().timestamp_millis();
+    for item in items {
+        let addr = &item.contract_address;
+        if !is_alert_chain(state, &item.chain) || state.blacklist.contains(&addr.to_lowercase()) {
+            continue;
         }
 
-        // --- 规则 4: 5 分钟涨跌幅 (需满足最小成交额) ---
-        let pc_5m = item.price_change5m.unwrap_or(0.0);
-        if pc_5m.abs() > ALERT_PRICE_CHANGE_5M_PERCENT
-            && volume_5m_usd > ALERT_PRICE_CHANGE_5M_MIN_VOLUME_USD
-        {
-            let direction = if pc_5m > 0.0 { "上涨" } else { "下跌" };
-            try_trigger_alert(
-                state, io, chain, addr, symbol,
-                AlertType::PriceChange5m,
-                format!("{} 5分钟{}{:.1}%", symbol, direction, pc_5m.abs()),
-                now,
-            ).await;
+        if item.holders_top10_percent >= thresholds.buy_pressure_max_top10_percent {
+            continue;
         }
+        let pressured = |ratio: f64, buys: i64| {
+            ratio > thresholds.buy_pressure_min_ratio && buys >= thresholds.buy_pressure_min_buys
+        };
+        let ratio = if pressured(item.buy_sell_ratio, item.count_buy) {
+            item.buy_sell_ratio
+        } else {
+            match live_buy_pressure(state, &item.chain, addr) {
+                Some((ratio, buys)) if pressured(ratio, buys) => ratio,
+                _ => continue,
+            }
+        };
+        try_trigger_alert(
+            state, io, &item.chain, addr, &item.symbol,
+            AlertType::BuyPressure,
+            AlertType::BuyPressure.as_str(),
+            format!("{} 买压 买卖比{:.1}", item.symbol, ratio),
+            now,
+        ).await;
     }
 }
 
+/// 链白名单检查：配置为空时放行所有链
+fn is_alert_chain(state: &ServerState, chain: &str) -> bool {
+    let allowed = &state.config.alert_chains;
+    allowed.is_empty() || allowed.contains(&chain.to_lowercase())
+}
+
+/// 各报警类型的冷却时间：按类型配置优先，否则使用默认冷却
+fn cooldown_ms(state: &ServerState, alert_type: &str) -> i64 {
+    let config = &state.config;
+    let cooldown = config
+        .alert_type_cooldowns
+        .get(alert_type)
+        .copied()
+        .unwrap_or(config.alert_cooldown);
+    i64::try_from(cooldown.as_millis()).unwrap_or(i64::MAX)
+}
+
+/// 清理过期的冷却记录：超过该类型冷却时间 N 倍 (alert_cooldown_retention_factor) 的时间戳已无意义，
+/// 否则每个触发过报警的代币都会永久留在表里。返回清理数量
+pub fn prune_alert_cooldowns(state: &ServerState, now: i64) -> usize {
+    let factor = i64::from(state.config.alert_cooldown_retention_factor.max(1));
+    let before = state.alert_cooldowns.len();
+    // 冷却键格式: chain:addr:type
+    state.alert_cooldowns.retain(|key, last_time| {
+        let alert_type = key.rsplit(':').next().unwrap_or_default();
+        now - *last_time <= cooldown_ms(state, alert_type).saturating_mul(factor)
+    });
+    before.saturating_sub(state.alert_cooldowns.len())
+}
+
 async fn try_trigger_alert(
     state: &ServerState,
     io: &SocketIo,
@@ -96,22 +307,27 @@ async fn try_trigger_alert(
     addr: &str,
     symbol: &str,
     alert_type: AlertType,
+    rule: &str,
     message: String,
     now: i64,
 ) {
-    let type_str = match alert_type {
-        AlertType::Volume1m => "volume1m",
-        AlertType::Volume5m => "volume5m",
-        AlertType::PriceChange1m => "priceChange1m",
-        AlertType::PriceChange5m => "priceChange5m",
-    };
-    
-    let cooldown_key = format!("{}:{}:{}", chain, addr.to_lowercase(), type_str);
+    // 自定义规则以规则名作为类型段，冷却与一次性配置都按规则名生效
+    let cooldown_key = format!("{}:{}:{}", chain, addr.to_lowercase(), rule);
+
+    // 一次性报警：只要触发过 (含重启前) 就不再报；插入即占位，并发推送也只会报一次
+    if state.config.alert_once_only_types.contains(rule) {
+        if !state.fired_once_alerts.insert(cooldown_key.clone()) {
+            return;
+        }
+        if let Err(e) = crate::kline_handler::record_fired_alert(&state.db_pool, &cooldown_key, now).await {
+            warn!("⚠️ [Alert] Failed to persist once-only alert {}: {}", cooldown_key, e);
+        }
+    }
 
     // 检查冷却
     let should_alert = {
         if let Some(last_time) = state.alert_cooldowns.get(&cooldown_key) {
-            now - *last_time > ALERT_COOLDOWN_MS
+            now - *last_time > cooldown_ms(state, rule)
         } else {
             true
         }
@@ -132,7 +348,8 @@ async fn try_trigger_alert(
         symbol: symbol.to_string(),
         message: message.clone(),
         timestamp: now,
-        alert_type: alert_type.clone(),
+        alert_type,
+        rule: rule.to_string(),
     };
 
     // 更新历史队列
@@ -146,5 +363,39 @@ async fn try_trigger_alert(
 
     // 广播给所有订阅者
     info!("🚨 [Alert] Broadcasting: {}", message);
-    io.emit("alert_update", &entry).await.ok();
+    if let Some(ns) = io.of(state.config.alerts_namespace.as_str()) {
+        ns.emit("alert_update", &entry).await.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_rules_render_legacy_messages() {
+        let thresholds = AlertThresholds {
+            volume_1m_usd: ALERT_VOLUME_1M_USD,
+            volume_5m_usd: ALERT_VOLUME_5M_USD,
+            price_change_1m_percent: ALERT_PRICE_CHANGE_1M_PERCENT,
+            price_change_5m_percent: ALERT_PRICE_CHANGE_5M_PERCENT,
+            price_change_1m_min_volume_usd: ALERT_PRICE_CHANGE_1M_MIN_VOLUME_USD,
+            price_change_5m_min_volume_usd: ALERT_PRICE_CHANGE_5M_MIN_VOLUME_USD,
+            buy_pressure_min_ratio: 3.0,
+            buy_pressure_min_buys: 30,
+            buy_pressure_max_top10_percent: 30.0,
+        };
+        let rules = builtin_rules(&thresholds);
+        assert_eq!(render_alert_message(&rules[0].1, "BTC", 51234.6), "BTC 1分钟 51235美金");
+        assert_eq!(render_alert_message(&rules[2].1, "BTC", -6.34), "BTC 1分钟下跌6.3%");
+    }
+
+    #[test]
+    fn usd_fields_multiply_volume_by_price() {
+        let fields = serde_json::json!({ "volume1m": "200", "price": 2.5, "priceChange1h": 60.0 });
+        let Value::Object(fields) = fields else { unreachable!() };
+        assert_eq!(rule_field_value(&fields, "volume1mUsd"), Some(500.0));
+        assert_eq!(rule_field_value(&fields, "priceChange1h"), Some(60.0));
+        assert_eq!(rule_field_value(&fields, "volume5mUsd"), None);
+    }
 }
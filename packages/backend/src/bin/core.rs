@@ -1,32 +1,41 @@
 // packages/backend/src/bin/core.rs
-use backend::{init_tracing, setup_shared_state, socket_handlers, http_handlers, cache_manager, kline_handler};
-use axum::{routing::get, Router};
+use backend::config::Config;
+use backend::{flush_pending_liquidity, init_tracing, persist_narrative_cache, replay, setup_shared_state, shutdown_signal, socket_handlers, http_handlers, cache_manager, kline_handler};
+use axum::{routing::{get, post}, Router};
 use socketioxide::SocketIo;
 use std::sync::Arc;
 use tracing::{info, warn};
 use std::fs::File;
 use std::io::BufReader;
 use rustls::ServerConfig;
+use axum_server::tls_rustls::RustlsConfig;
+use std::time::SystemTime;
 use tower_http::cors::{Any, CorsLayer};
 
+/// VACUUM 前观察写入活动的时长
+const DB_WRITE_PROBE_WINDOW: std::time::Duration = std::time::Duration::from_secs(2);
+
 #[tokio::main]
 async fn main() {
     init_tracing();
     info!("🚀 Starting Backend Core (Cloud Mode)");
 
     let (layer, io) = SocketIo::builder().max_buffer_size(40960).build_layer();
-    let config = Arc::new(backend::config::Config::new());
-    let server_state = setup_shared_state(config.clone(), io.clone()).await;
-
-    let socket_state = server_state.clone();
-    io.ns("/", move |s: socketioxide::extract::SocketRef| {
-        let state = socket_state.clone();
-        async move {
-            // Core 模式仅注册数据更新和报警相关处理器
-            // 虽然这里目前是全注册，但逻辑上我们只关心 data-update
-            socket_handlers::on_socket_connect(s, state).await;
-        }
-    });
+    let config = Arc::new(Config::new());
+    let server_state = setup_shared_state(config.clone(), io).await;
+
+    // 命名空间由配置决定：默认全部注册在 "/"，拆分后报警与行情各自独立
+    socket_handlers::register_namespaces(&server_state);
+
+    // 回放模式：`--replay <file>` 把录制的 data-update 推送按原节奏重新喂给处理逻辑 (服务照常启动，前端可直接观察报警)
+    if let Some(path) = replay_path_arg() {
+        let replay_state = server_state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = replay::replay_file(&replay_state, &path).await {
+                warn!("⚠️ [Replay] Failed to replay {}: {:#}", path, e);
+            }
+        });
+    }
 
     // 定时任务：流动性裁剪 & 缓存管理
     let db_pool_for_prune = server_state.db_pool.clone();
@@ -40,45 +49,219 @@ async fn main() {
             }
         }
     });
-    tokio::spawn(cache_manager::cache_manager_task(config));
+    tokio::spawn(cache_manager::cache_manager_task(config.clone()));
+
+    // 定时任务：数据库 checkpoint / VACUUM (仅 Core 执行，避免两个进程同时整理同一个库文件)
+    if let Some(maintenance_interval) = config.db_maintenance_interval {
+        let maintenance_state = server_state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(maintenance_interval);
+            interval.tick().await; // 启动时不立即执行
+            let mut runs: u32 = 0;
+            loop {
+                interval.tick().await;
+                runs = runs.wrapping_add(1);
+                let every = maintenance_state.config.db_vacuum_every_n_runs;
+                let mut vacuum = every > 0 && runs % every == 0;
+                // 观察窗口内有任何连接 (含 Market 进程) 提交写入说明写入繁忙，本轮只做 checkpoint；探测失败同样推迟
+                if vacuum {
+                    let busy = kline_handler::db_writes_active(&maintenance_state.db_pool, DB_WRITE_PROBE_WINDOW)
+                        .await
+                        .unwrap_or(true);
+                    if busy {
+                        warn!("🗜️ [DB MAINT] Writes in progress, deferring VACUUM to next run");
+                        vacuum = false;
+                        runs = runs.wrapping_sub(1);
+                    }
+                }
+                match kline_handler::run_db_maintenance(&maintenance_state.db_pool, vacuum, maintenance_state.config.slow_query_threshold).await {
+                    Ok((before, after)) => info!(
+                        "🗜️ [DB MAINT] checkpoint{} done: {:.1}MB -> {:.1}MB (reclaimed {:.1}MB)",
+                        if vacuum { " + VACUUM" } else { "" },
+                        before as f64 / 1048576.0,
+                        after as f64 / 1048576.0,
+                        (before - after).max(0) as f64 / 1048576.0,
+                    ),
+                    Err(e) => warn!("🗜️ [DB MAINT] Skipped (database busy?): {:#}", e),
+                }
+            }
+        });
+    }
 
     let app = Router::new()
         .route("/desired-fields", get(http_handlers::desired_fields_handler))
         .route("/image-proxy", get(http_handlers::image_proxy_handler))
-        .with_state(server_state)
+        .route("/narrative-stats", get(http_handlers::narrative_stats_handler))
+        .route("/workers", get(http_handlers::workers_handler))
+        .route("/worker-events", get(http_handlers::worker_events_handler))
+        .route("/version", get(http_handlers::version_handler))
+        .route("/liquidity", get(http_handlers::liquidity_export_handler))
+        .route("/klines", get(http_handlers::klines_handler))
+        .route("/klines/export", get(http_handlers::klines_export_handler))
+        .route("/price", get(http_handlers::price_handler))
+        .route("/admin/reset-token", post(http_handlers::admin_reset_token_handler))
+        .route("/admin/prune", post(http_handlers::admin_prune_handler))
+        .route("/admin/log-level", post(http_handlers::admin_log_level_handler))
+        .route(
+            "/admin/whitelist",
+            get(http_handlers::get_whitelist_handler)
+                .post(http_handlers::add_whitelist_handler)
+                .delete(http_handlers::remove_whitelist_handler),
+        )
+        .route(
+            "/admin/alert-config",
+            get(http_handlers::get_alert_config_handler).post(http_handlers::update_alert_config_handler),
+        )
+        .with_state(server_state.clone())
         .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
         .layer(layer);
 
     // HTTPS/HTTP Server logic (from original main.rs)
-    let cert_file = File::open("cert.pem")
-        .expect("Failed to open cert.pem");
-    let key_file = File::open("key.pem")
-        .expect("Failed to open key.pem");
-    let mut cert_reader = BufReader::new(cert_file);
-    let mut key_reader = BufReader::new(key_file);
-    let certs = rustls_pemfile::certs(&mut cert_reader).collect::<Result<Vec<_>, _>>().expect("Parse cert");
-    let key = rustls_pemfile::private_key(&mut key_reader).expect("Read key").expect("No key");
-    let mut tls_config = ServerConfig::builder().with_no_client_auth().with_single_cert(certs, key).expect("TLS config");
-    tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
-    let rustls_config = axum_server::tls_rustls::RustlsConfig::from_config(Arc::new(tls_config));
-
-    info!("🔒 HTTPS port 30001 | 🌐 HTTP port 30002");
-    
-    let https_app = app.clone();
-    let http_app = app;
-    
-    let https_server = tokio::spawn(async move {
-        axum_server::bind_rustls("0.0.0.0:30001".parse::<std::net::SocketAddr>().unwrap(), rustls_config)
-            .serve(https_app.into_make_service()).await.unwrap();
-    });
-    
+    let https_addr = config.socket_addr(config.https_port.unwrap_or(30001));
+    let http_addr = config.socket_addr(config.http_port.unwrap_or(30002));
+    let http_app = app.clone();
     let http_server = tokio::spawn(async move {
-        let listener = tokio::net::TcpListener::bind("0.0.0.0:30002").await.unwrap();
+        let listener = tokio::net::TcpListener::bind(http_addr).await.unwrap();
         axum::serve(listener, http_app).await.unwrap();
     });
-    
-    tokio::select! {
-        _ = https_server => info!("Core HTTPS stopped"),
-        _ = http_server => info!("Core HTTP stopped"),
+
+    match load_rustls_config(&config) {
+        Some(rustls_config) => {
+            info!("🔒 HTTPS {} | 🌐 HTTP {}", https_addr, http_addr);
+            tokio::spawn(tls_reload_task(config.clone(), rustls_config.clone()));
+            let https_app = app;
+            let https_server = tokio::spawn(async move {
+                axum_server::bind_rustls(https_addr, rustls_config)
+                    .serve(https_app.into_make_service()).await.unwrap();
+            });
+
+            tokio::select! {
+                _ = https_server => info!("Core HTTPS stopped"),
+                _ = http_server => info!("Core HTTP stopped"),
+                _ = shutdown_signal() => info!("🛑 Shutdown signal received"),
+            }
+        }
+        None => {
+            info!("🌐 HTTP {} (HTTPS disabled)", http_addr);
+            tokio::select! {
+                _ = http_server => info!("Core HTTP stopped"),
+                _ = shutdown_signal() => info!("🛑 Shutdown signal received"),
+            }
+        }
+    }
+
+    // 退出前刷新尚未落库的流动性快照，避免重启前出现数据空洞
+    flush_pending_liquidity(&server_state).await;
+    persist_narrative_cache(&server_state).await;
+    info!("👋 Core shutdown complete");
+}
+
+/// 命令行参数 `--replay <file>` (也接受 `--replay=<file>`)
+fn replay_path_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--replay" {
+            return args.next();
+        }
+        if let Some(path) = arg.strip_prefix("--replay=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+/// 加载 TLS 证书；未启用或证书缺失/无效时返回 None，由调用方降级为纯 HTTP
+fn load_rustls_config(config: &Config) -> Option<RustlsConfig> {
+    if !config.enable_tls {
+        warn!("⚠️ TLS disabled by config. Serving HTTP only.");
+        return None;
+    }
+    match build_tls_server_config(config) {
+        Ok(tls_config) => Some(RustlsConfig::from_config(Arc::new(tls_config))),
+        Err(e) => {
+            warn!("⚠️ {}. Serving HTTP only.", e);
+            None
+        }
+    }
+}
+
+/// 读取证书与私钥并构建 rustls ServerConfig
+fn build_tls_server_config(config: &Config) -> Result<ServerConfig, String> {
+    let cert_file = File::open(&config.tls_cert_path)
+        .map_err(|e| format!("Failed to open {}: {}", config.tls_cert_path, e))?;
+    let key_file = File::open(&config.tls_key_path)
+        .map_err(|e| format!("Failed to open {}: {}", config.tls_key_path, e))?;
+
+    let mut cert_reader = BufReader::new(cert_file);
+    let mut key_reader = BufReader::new(key_file);
+    let certs = rustls_pemfile::certs(&mut cert_reader)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to parse cert: {}", e))?;
+    let key = rustls_pemfile::private_key(&mut key_reader)
+        .map_err(|e| format!("Failed to read key: {}", e))?
+        .ok_or_else(|| format!("No private key found in {}", config.tls_key_path))?;
+    let mut tls_config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid TLS config: {}", e))?;
+    tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(tls_config)
+}
+
+/// 证书与私钥文件的修改时间，用于检测证书轮换
+fn tls_files_modified(config: &Config) -> Option<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(&config.tls_cert_path).and_then(|m| m.modified()).ok()?;
+    let key = std::fs::metadata(&config.tls_key_path).and_then(|m| m.modified()).ok()?;
+    Some((cert, key))
+}
+
+/// 证书热重载：定期检查证书文件修改时间 (Unix 下收到 SIGHUP 时立即检查)，变化后原地替换
+/// RustlsConfig，新握手使用新证书，已建立的连接不受影响。新证书无效时保留当前证书
+async fn tls_reload_task(config: Arc<Config>, rustls_config: RustlsConfig) {
+    let Some(check_interval) = config.tls_reload_check_interval else { return; };
+    let mut last_modified = tls_files_modified(&config);
+    let mut interval = tokio::time::interval(check_interval);
+    interval.tick().await;
+
+    #[cfg(unix)]
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => Some(signal),
+        Err(e) => {
+            warn!("⚠️ [TLS] Failed to install SIGHUP handler: {}", e);
+            None
+        }
+    };
+
+    loop {
+        #[cfg(unix)]
+        let forced = match hangup.as_mut() {
+            Some(hangup) => tokio::select! {
+                _ = interval.tick() => false,
+                _ = hangup.recv() => true,
+            },
+            None => {
+                interval.tick().await;
+                false
+            }
+        };
+        #[cfg(not(unix))]
+        let forced = {
+            interval.tick().await;
+            false
+        };
+
+        let modified = tls_files_modified(&config);
+        if !forced && modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        match build_tls_server_config(&config) {
+            Ok(tls_config) => {
+                rustls_config.reload_from_config(Arc::new(tls_config));
+                info!("🔒 [TLS] Certificate reloaded from {}", config.tls_cert_path);
+            }
+            Err(e) => warn!("⚠️ [TLS] Reload failed: {}. Keeping current certificate.", e),
+        }
     }
 }
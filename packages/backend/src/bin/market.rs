@@ -1,6 +1,6 @@
 // packages/backend/src/bin/market.rs
-use backend::{init_tracing, setup_shared_state, socket_handlers, http_handlers};
-use axum::{routing::get, Router};
+use backend::{flush_pending_liquidity, init_tracing, persist_narrative_cache, setup_shared_state, shutdown_signal, socket_handlers, http_handlers};
+use axum::{routing::{get, post}, Router};
 use socketioxide::SocketIo;
 use std::sync::Arc;
 use tracing::info;
@@ -13,26 +13,49 @@ async fn main() {
 
     let (layer, io) = SocketIo::builder().max_buffer_size(40960).build_layer();
     let config = Arc::new(backend::config::Config::new());
-    let server_state = setup_shared_state(config.clone(), io.clone()).await;
+    let server_state = setup_shared_state(config.clone(), io).await;
 
-    let socket_state = server_state.clone();
-    io.ns("/", move |s: socketioxide::extract::SocketRef| {
-        let state = socket_state.clone();
-        async move {
-            // Market 模式主要处理 K 线订阅和历史请求
-            socket_handlers::on_socket_connect(s, state).await;
-        }
-    });
+    // Market 模式主要处理 K 线订阅和历史请求；命名空间由配置决定
+    socket_handlers::register_namespaces(&server_state);
 
     let app = Router::new()
         .route("/image-proxy", get(http_handlers::image_proxy_handler))
-        .with_state(server_state)
+        .route("/narrative-stats", get(http_handlers::narrative_stats_handler))
+        .route("/workers", get(http_handlers::workers_handler))
+        .route("/worker-events", get(http_handlers::worker_events_handler))
+        .route("/version", get(http_handlers::version_handler))
+        .route("/liquidity", get(http_handlers::liquidity_export_handler))
+        .route("/klines", get(http_handlers::klines_handler))
+        .route("/klines/export", get(http_handlers::klines_export_handler))
+        .route("/price", get(http_handlers::price_handler))
+        .route("/admin/reset-token", post(http_handlers::admin_reset_token_handler))
+        .route("/admin/prune", post(http_handlers::admin_prune_handler))
+        .route("/admin/log-level", post(http_handlers::admin_log_level_handler))
+        .route(
+            "/admin/whitelist",
+            get(http_handlers::get_whitelist_handler)
+                .post(http_handlers::add_whitelist_handler)
+                .delete(http_handlers::remove_whitelist_handler),
+        )
+        .route(
+            "/admin/alert-config",
+            get(http_handlers::get_alert_config_handler).post(http_handlers::update_alert_config_handler),
+        )
+        .with_state(server_state.clone())
         .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
         .layer(layer);
 
-    // 本地不一定需要 HTTPS，直接监听 30003
-    let port = 30003;
-    info!("📊 Market server listening on port {}", port);
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // 本地不一定需要 HTTPS，直接监听 HTTP (默认 30003；https_port 对 Market 无效)
+    let addr = config.socket_addr(config.http_port.unwrap_or(30003));
+    info!("📊 Market server listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // 退出前刷新尚未落库的流动性快照
+    flush_pending_liquidity(&server_state).await;
+    persist_narrative_cache(&server_state).await;
+    info!("👋 Market shutdown complete");
 }
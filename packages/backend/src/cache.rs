@@ -8,14 +8,56 @@ use std::path::{Path, PathBuf};
 use tokio::fs;
 use tracing::warn; // 修正：移除了未使用的 `info`
 
+/// 缓存内容类型：不同类型使用独立目录和容量上限，互不驱逐。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    Icon,
+    Banner,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 2] = [CacheKind::Icon, CacheKind::Banner];
+
+    /// 从查询参数解析，未知或缺省时按图标处理
+    pub fn from_query(kind: Option<&str>) -> Self {
+        match kind {
+            Some(k) if k.eq_ignore_ascii_case("banner") => CacheKind::Banner,
+            _ => CacheKind::Icon,
+        }
+    }
+
+    pub fn dir<'a>(&self, config: &'a Config) -> &'a str {
+        match self {
+            CacheKind::Icon => &config.cache_dir,
+            CacheKind::Banner => &config.banner_cache_dir,
+        }
+    }
+
+    pub fn max_size_mb(&self, config: &Config) -> u64 {
+        match self {
+            CacheKind::Icon => config.max_cache_size_mb,
+            CacheKind::Banner => config.banner_max_cache_size_mb,
+        }
+    }
+}
+
+impl std::fmt::Display for CacheKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheKind::Icon => write!(f, "ICON"),
+            CacheKind::Banner => write!(f, "BANNER"),
+        }
+    }
+}
+
 /// 基于 URL 哈希生成缓存文件路径。
-fn get_cache_paths(url: &str, config: &Config) -> (PathBuf, PathBuf) {
+fn get_cache_paths(url: &str, kind: CacheKind, config: &Config) -> (PathBuf, PathBuf) {
     let mut hasher = Sha256::new();
     hasher.update(url.as_bytes());
     let hash = hasher.finalize();
     let hash_str = hex::encode(hash);
 
-    let cache_dir = Path::new(&config.cache_dir);
+    let cache_dir = Path::new(kind.dir(config));
     let data_path = cache_dir.join(format!("{}.data", hash_str));
     let meta_path = cache_dir.join(format!("{}.meta", hash_str));
     (data_path, meta_path)
@@ -24,9 +66,10 @@ fn get_cache_paths(url: &str, config: &Config) -> (PathBuf, PathBuf) {
 /// 尝试从缓存中获取响应。
 pub async fn get_cached_response(
     url: &str,
+    kind: CacheKind,
     config: &Config,
 ) -> Result<Option<Response>, AppError> {
-    let (data_path, meta_path) = get_cache_paths(url, config);
+    let (data_path, meta_path) = get_cache_paths(url, kind, config);
     if !data_path.exists() || !meta_path.exists() {
         return Ok(None);
     }
@@ -40,7 +83,7 @@ pub async fn get_cached_response(
     let meta_path_clone = meta_path.clone();
     tokio::spawn(async move {
         // 通过重写元数据文件来更新它的 mtime
-        if let Err(e) = fs::write(meta_path_clone, meta_json).await {
+        if let Err(e) = write_atomic(&meta_path_clone, meta_json.as_bytes()).await {
             warn!("[CACHE TOUCH] Failed to update metadata timestamp: {}", e);
         }
     });
@@ -64,9 +107,10 @@ pub async fn save_to_cache(
     url: &str,
     content_type: &HeaderValue,
     data: &Bytes,
+    kind: CacheKind,
     config: &Config,
 ) -> Result<(), AppError> {
-    let (data_path, meta_path) = get_cache_paths(url, config);
+    let (data_path, meta_path) = get_cache_paths(url, kind, config);
     // 确保缓存目录存在
     if let Some(parent) = data_path.parent() {
         fs::create_dir_all(parent).await?;
@@ -80,8 +124,26 @@ pub async fn save_to_cache(
     };
 
     let meta_json = serde_json::to_string(&meta)?;
-    fs::write(&data_path, data).await?;
-    fs::write(&meta_path, meta_json).await?;
+    // 先写 .data 再写 .meta：读取端以两者同时存在作为缓存命中条件，
+    // meta 最后落盘保证读到的 data 一定是完整的
+    write_atomic(&data_path, data).await?;
+    write_atomic(&meta_path, meta_json.as_bytes()).await?;
 
+    Ok(())
+}
+
+/// 先写入同目录下的临时文件，再原子 rename 到目标路径，避免并发写入产生半截文件。
+pub(crate) async fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), AppError> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("cache");
+    let tmp_path = path.with_file_name(format!("{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+
+    if let Err(e) = fs::write(&tmp_path, contents).await {
+        let _ = fs::remove_file(&tmp_path).await;
+        return Err(e.into());
+    }
+    if let Err(e) = fs::rename(&tmp_path, path).await {
+        let _ = fs::remove_file(&tmp_path).await;
+        return Err(e.into());
+    }
     Ok(())
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 // packages/backend/src/cache_manager.rs
-use super::config::Config;
+use super::{cache::CacheKind, config::Config};
 use std::{path::PathBuf, sync::Arc, time::SystemTime};
 use tokio::{fs, time::interval};
 use tracing::{info, warn};
@@ -11,37 +11,46 @@ struct CacheEntry {
     size: u64,
 }
 
-/// 后台缓存清理任务
+/// 后台缓存清理任务 (每种缓存类型独立计算容量并清理)
 pub async fn cache_manager_task(config: Arc<Config>) {
     let cleanup_interval = config.cache_cleanup_interval;
-    // 使用 MB 计算字节数
-    let max_size_bytes = config.max_cache_size_mb * 1024 * 1024;
-    // 当缓存超过最大值时，清理到这个比例
-    let target_size_bytes = (max_size_bytes as f64 * 0.8) as u64;
 
-    info!(
-        "🧹 Cache Manager started. Max size: {} MB, Cleanup interval: {:?}",
-        config.max_cache_size_mb, cleanup_interval
-    );
+    for kind in CacheKind::ALL {
+        info!(
+            "🧹 Cache Manager started for {}. Dir: {}, Max size: {} MB, Cleanup interval: {:?}",
+            kind,
+            kind.dir(&config),
+            kind.max_size_mb(&config),
+            cleanup_interval
+        );
+    }
 
     let mut timer = interval(cleanup_interval);
     loop {
         timer.tick().await;
         info!("[CACHE MANAGER] Running cleanup check...");
 
-        match run_cleanup_cycle(&config.cache_dir, max_size_bytes, target_size_bytes).await {
-            Ok(cleaned_bytes) => {
-                if cleaned_bytes > 0 {
-                    info!(
-                        "[CACHE MANAGER] Cleanup successful. Freed {:.2} MB.",
-                        cleaned_bytes as f64 / 1024.0 / 1024.0
-                    );
-                } else {
-                    info!("[CACHE MANAGER] Cache is within limits. No action needed.");
+        for kind in CacheKind::ALL {
+            // 使用 MB 计算字节数
+            let max_size_bytes = kind.max_size_mb(&config) * 1024 * 1024;
+            // 当缓存超过最大值时，清理到这个比例
+            let target_size_bytes = (max_size_bytes as f64 * 0.8) as u64;
+
+            match run_cleanup_cycle(kind.dir(&config), max_size_bytes, target_size_bytes).await {
+                Ok(cleaned_bytes) => {
+                    if cleaned_bytes > 0 {
+                        info!(
+                            "[CACHE MANAGER:{}] Cleanup successful. Freed {:.2} MB.",
+                            kind,
+                            cleaned_bytes as f64 / 1024.0 / 1024.0
+                        );
+                    } else {
+                        info!("[CACHE MANAGER:{}] Cache is within limits. No action needed.", kind);
+                    }
+                }
+                Err(e) => {
+                    warn!("[CACHE MANAGER:{}] Error during cleanup cycle: {}", kind, e);
                 }
-            }
-            Err(e) => {
-                warn!("[CACHE MANAGER] Error during cleanup cycle: {}", e);
             }
         }
     }
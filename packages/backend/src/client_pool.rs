@@ -1,17 +1,30 @@
 // packages/backend/src/client_pool.rs
 
-use reqwest::{Client, Proxy};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Client, ClientBuilder, Proxy};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
 };
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
+/// 池内客户端的额外限制 (图片代理池使用，默认不限制)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientRestrictions {
+    /// 不自动跟随重定向：由调用方对每一跳重新做安全检查后手动跟随
+    pub no_redirects: bool,
+    /// 直连时只连接公网地址 (经 PublicOnlyResolver 解析)；走代理时目标由代理解析，不受此限制
+    pub public_only: bool,
+}
+
 #[derive(Clone)]
 pub struct ClientPool {
     clients: Arc<RwLock<Vec<Client>>>,
     proxy_url: Option<String>,
+    restrictions: ClientRestrictions,
     max_size: usize,
     counter: Arc<AtomicUsize>,
     name: String, // 用于日志区分是 DIRECT 还是 PROXY 池
@@ -20,6 +33,23 @@ pub struct ClientPool {
 impl ClientPool {
     /// 初始化连接池，并并发预热所有连接
     pub async fn new(size: usize, proxy_url: Option<String>, name: String) -> Self {
+        Self::with_stagger(size, proxy_url, name, Duration::ZERO).await
+    }
+
+    /// 初始化连接池，第 i 个客户端延迟 i * stagger 再加 [0, stagger) 的随机抖动后构建，
+    /// 让代理池的握手在数秒内分散完成，而不是启动瞬间全部打到代理上。stagger 为 0 时全部并发
+    pub async fn with_stagger(size: usize, proxy_url: Option<String>, name: String, stagger: Duration) -> Self {
+        Self::with_restrictions(size, proxy_url, name, stagger, ClientRestrictions::default()).await
+    }
+
+    /// 同 with_stagger，池内 (包括回收重建的) 客户端都带上 restrictions
+    pub async fn with_restrictions(
+        size: usize,
+        proxy_url: Option<String>,
+        name: String,
+        stagger: Duration,
+        restrictions: ClientRestrictions,
+    ) -> Self {
         let mut clients = Vec::with_capacity(size);
 
         info!(
@@ -34,8 +64,12 @@ impl ClientPool {
         for i in 0..size {
             let p_url = proxy_url.clone();
             let pool_name = name.clone();
+            let delay = stagger * i as u32 + jitter(stagger);
             tasks.push(tokio::spawn(async move {
-                build_and_warm_client(p_url.as_deref(), i, &pool_name).await
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                build_and_warm_client(p_url.as_deref(), i, &pool_name, restrictions).await
             }));
         }
 
@@ -45,7 +79,7 @@ impl ClientPool {
                 Err(_) => {
                     // ⚠️ 安全修正：即使 Spawn 失败，也必须填充占位符。
                     // 必须使用 safe fallback，防止代理模式下变成直连。
-                    clients.push(build_safe_fallback(proxy_url.as_deref()));
+                    clients.push(build_safe_fallback(proxy_url.as_deref(), restrictions));
                 }
             }
         }
@@ -53,6 +87,7 @@ impl ClientPool {
         Self {
             clients: Arc::new(RwLock::new(clients)),
             proxy_url,
+            restrictions,
             max_size: size,
             counter: Arc::new(AtomicUsize::new(0)),
             name,
@@ -76,7 +111,7 @@ impl ClientPool {
 
         // 1. 在锁外构建并暖机新连接 (这包含网络 IO，耗时较长，不要阻塞锁)
         // 这会触发新的 TCP 握手，从而让底层代理软件分配新的出口 IP/节点
-        let new_client = build_and_warm_client(self.proxy_url.as_deref(), index, &self.name).await;
+        let new_client = build_and_warm_client(self.proxy_url.as_deref(), index, &self.name, self.restrictions).await;
 
         // 2. 获取写锁，替换旧连接
         let mut write_lock = self.clients.write().await;
@@ -85,27 +120,119 @@ impl ClientPool {
         info!("✅ [POOL:{}] Client #{} refreshed and ready.", self.name, index);
         new_client
     }
+
+    /// 启动后台健康检查：每个周期内把各客户端的探测均匀错开 (每 period/size 探测一个)，
+    /// 避免集中请求触发风控；连接失败/超时的客户端会被回收重建。
+    /// 只要拿到 HTTP 响应 (哪怕是 403/429) 就说明代理链路可用，不算失败
+    pub fn spawn_health_check(&self, url: String, period: Duration) {
+        let pool = self.clone();
+        let step = period / pool.max_size.max(1) as u32;
+        info!("🩺 [POOL:{}] Health check enabled: {} every {:?}", pool.name, url, period);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(step.max(Duration::from_secs(1)));
+            interval.tick().await;
+            let mut index = 0;
+            loop {
+                interval.tick().await;
+                let client = pool.clients.read().await[index].clone();
+                if let Err(e) = client.get(&url).send().await {
+                    warn!("🩺 [POOL:{}] Client #{} failed health check: {}", pool.name, index, e);
+                    pool.recycle_client(index).await;
+                }
+                index = (index + 1) % pool.max_size;
+            }
+        });
+    }
+}
+
+/// [0, max) 内的随机时长 (用 RandomState 的随机种子，无需额外依赖)
+fn jitter(max: Duration) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    Duration::from_nanos(random % max.as_nanos() as u64)
+}
+
+/// 按 restrictions 收紧 builder
+fn restrict(mut builder: ClientBuilder, restrictions: ClientRestrictions, direct: bool) -> ClientBuilder {
+    if restrictions.no_redirects {
+        builder = builder.redirect(redirect::Policy::none());
+    }
+    if restrictions.public_only && direct {
+        builder = builder.dns_resolver(Arc::new(PublicOnlyResolver));
+    }
+    builder
+}
+
+/// 只返回公网地址的 DNS 解析器：解析结果含非公网地址时整体拒绝。
+/// 连接直接使用这次解析的结果，没有 "检查时解析一次、连接时再解析一次" 之间的 DNS rebinding 窗口
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+                warn!("🚫 [DNS] {} resolves to non-public address {}", host, addr.ip());
+                return Err(format!("{} resolves to non-public address {}", host, addr.ip()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// 是否为公网地址：排除回环、私有网段、链路本地 (含 169.254.169.254 元数据地址)、CGNAT、组播、文档/保留网段等
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)) // 100.64.0.0/10 CGNAT
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (first & 0xfe00) == 0xfc00 // fc00::/7 ULA
+                || (first & 0xffc0) == 0xfe80) // fe80::/10 链路本地
+        }
+    }
 }
 
 /// 🛡️ 安全回退构建器
 /// 如果指定了 proxy_url，但构建失败，必须返回一个配置了“死胡同”代理的 Client。
-/// 这样请求会超时，但绝对不会泄露本机 IP。
-fn build_safe_fallback(proxy_url: Option<&str>) -> Client {
+/// 这样请求会超时，但绝对不会泄露本机 IP。回退客户端同样带上 restrictions
+fn build_safe_fallback(proxy_url: Option<&str>, restrictions: ClientRestrictions) -> Client {
     if let Some(_) = proxy_url {
         // 配置一个无法连接的代理地址 (黑洞)
         let broken_proxy = Proxy::all("http://0.0.0.0:1").unwrap();
-        Client::builder()
-            .proxy(broken_proxy)
+        restrict(Client::builder().proxy(broken_proxy), restrictions, false)
             .build()
             .unwrap_or_else(|_| Client::new()) // 如果连这也失败，Client::new 也没办法，但通常不会
     } else {
         // 直连模式下，Fallback 就是普通 Client
-        Client::new()
+        restrict(Client::builder(), restrictions, true).build().unwrap_or_else(|_| Client::new())
     }
 }
 
 /// 构建客户端并尝试发起一个请求来验证连通性
-async fn build_and_warm_client(proxy_url: Option<&str>, index: usize, pool_name: &str) -> Client {
+async fn build_and_warm_client(proxy_url: Option<&str>, index: usize, pool_name: &str, restrictions: ClientRestrictions) -> Client {
     // 最多重试 3 次构建，确保拿到的连接是通的
     for attempt in 1..=3 {
         let mut builder = Client::builder()
@@ -114,6 +241,7 @@ async fn build_and_warm_client(proxy_url: Option<&str>, index: usize, pool_name:
             // 保持长连接，直到手动回收
             .pool_idle_timeout(std::time::Duration::from_secs(90))
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36");
+        builder = restrict(builder, restrictions, proxy_url.is_none());
 
         if let Some(url) = proxy_url {
             match Proxy::all(url) {
@@ -123,7 +251,7 @@ async fn build_and_warm_client(proxy_url: Option<&str>, index: usize, pool_name:
                 Err(e) => {
                     error!("❌ [POOL:{}] Invalid Proxy URL: {}. SECURITY RISK.", pool_name, e);
                     // 代理配置错误，直接返回死胡同 Client，防止直连
-                    return build_safe_fallback(proxy_url);
+                    return build_safe_fallback(proxy_url, restrictions);
                 }
             }
         }
@@ -134,7 +262,7 @@ async fn build_and_warm_client(proxy_url: Option<&str>, index: usize, pool_name:
                 error!("❌ [POOL:{}] Build failed (Attempt {}): {}", pool_name, attempt, e);
                 // 只有最后一次失败才返回 fallback，中间失败则 continue 重试
                 if attempt == 3 {
-                    return build_safe_fallback(proxy_url);
+                    return build_safe_fallback(proxy_url, restrictions);
                 }
                 tokio::time::sleep(std::time::Duration::from_millis(500)).await;
                 continue;
@@ -153,5 +281,43 @@ async fn build_and_warm_client(proxy_url: Option<&str>, index: usize, pool_name:
     }
     
     error!("🔥 [POOL:{}] Client #{} failed all build attempts.", pool_name, index);
-    build_safe_fallback(proxy_url) 
-}
\ No newline at end of file
+    build_safe_fallback(proxy_url, restrictions) 
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_and_metadata_addresses_are_not_public() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "::ffff:10.0.0.1"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} should be blocked", ip);
+        }
+        for ip in ["8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn public_only_client_refuses_hosts_resolving_to_loopback() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let accepted_flag = accepted.clone();
+        tokio::spawn(async move {
+            if listener.accept().await.is_ok() {
+                accepted_flag.store(true, Ordering::SeqCst);
+            }
+        });
+
+        let restrictions = ClientRestrictions { no_redirects: true, public_only: true };
+        let pool = ClientPool::with_restrictions(1, None, "TEST_IMG".to_string(), Duration::ZERO, restrictions).await;
+        let (_, client) = pool.get_client().await;
+
+        // localhost 在连接时解析为回环地址，直接拒绝，连接不会建立
+        let err = client.get(format!("http://localhost:{}/a.png", port)).send().await.unwrap_err();
+        assert!(err.is_connect() || err.is_request(), "{:?}", err);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!accepted.load(Ordering::SeqCst));
+    }
+}
@@ -1,17 +1,293 @@
 // packages/backend/src/config.rs
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
+use tracing::warn;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::types::{known_item_fields, AlertRule};
+
+const DEFAULT_DESIRED_FIELDS: &[&str] = &[
+    "icon",
+    "symbol",
+    "price",
+    "marketCap",
+    "chain",
+    "chainId",
+    "contractAddress",
+    "volume1m",
+    "volume5m",
+    "volume1h",
+    "volume4h",
+    "volume24h",
+    "priceChange1m",
+    "priceChange5m",
+    "priceChange1h",
+    "priceChange4h",
+    "priceChange24h",
+];
+
+/// 运行时加载爬虫字段列表：`DESIRED_FIELDS` 环境变量优先，其次 `DESIRED_FIELDS_FILE` 指向的文件
+/// (逗号或换行分隔)，都没有或解析为空时使用默认列表
+fn load_desired_fields() -> Vec<String> {
+    let raw = match std::env::var("DESIRED_FIELDS") {
+        Ok(value) => Some(value),
+        Err(_) => std::env::var("DESIRED_FIELDS_FILE").ok().and_then(|path| {
+            std::fs::read_to_string(&path)
+                .map_err(|e| warn!("⚠️ [CONFIG] Failed to read desired fields file {}: {}", path, e))
+                .ok()
+        }),
+    };
+
+    let fields: Vec<String> = raw
+        .as_deref()
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c == '\n')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let fields = if fields.is_empty() {
+        DEFAULT_DESIRED_FIELDS.iter().map(|field| field.to_string()).collect()
+    } else {
+        fields
+    };
+    validate_desired_fields(&fields);
+    fields
+}
+
+/// 字段名拼写错误会让爬虫去抓一个不存在的字段且毫无提示，启动时对照已知字段告警
+fn validate_desired_fields(fields: &[String]) {
+    let known = known_item_fields();
+    for field in fields.iter().filter(|field| !known.contains(field.as_str())) {
+        warn!("⚠️ [CONFIG] Desired field '{}' is not a known MemeScanItem/HotlistItem field", field);
+    }
+}
+
+/// 自定义报警规则：`ALERT_RULES_FILE` 指向的 JSON 数组 (AlertRule)，与内置的成交额/涨跌幅规则一起评估。
+/// 未配置或解析失败时为空 (只跑内置规则)
+fn load_alert_rules() -> Vec<AlertRule> {
+    let Ok(path) = std::env::var("ALERT_RULES_FILE") else {
+        return Vec::new();
+    };
+    let raw = match std::fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            warn!("⚠️ [CONFIG] Failed to read alert rules file {}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    let rules: Vec<AlertRule> = match serde_json::from_str(&raw) {
+        Ok(rules) => rules,
+        Err(e) => {
+            warn!("⚠️ [CONFIG] Failed to parse alert rules file {}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    validate_alert_rules(rules)
+}
+
+/// 丢弃规则名非法的规则 (冷却键以 ':' 分隔)，并对未知字段告警 (volumeXxUsd 按 volumeXx 检查)
+fn validate_alert_rules(rules: Vec<AlertRule>) -> Vec<AlertRule> {
+    let known = known_item_fields();
+    let is_known = |field: &str| known.contains(field.strip_suffix("Usd").unwrap_or(field));
+    rules
+        .into_iter()
+        .filter(|rule| {
+            if rule.name.is_empty() || rule.name.contains(':') {
+                warn!("⚠️ [CONFIG] Alert rule name '{}' must be non-empty and contain no ':', skipped", rule.name);
+                return false;
+            }
+            for field in std::iter::once(&rule.field).chain(rule.min_volume_field.as_ref()) {
+                if !is_known(field.as_str()) {
+                    warn!("⚠️ [CONFIG] Alert rule '{}' uses unknown field '{}'", rule.name, field);
+                }
+            }
+            true
+        })
+        .collect()
+}
 
 #[derive(Clone)]
 pub struct Config {
     pub cache_dir: String,
     pub binance_wss_url: String,
+    /// 代理地址：host:port，可带 user:pass@ 与 scheme (http:// 或 socks5://，未写 scheme 按 http)；"none" 或空表示直连
     pub proxy_addr: String,
+    /// 代理认证 (优先于 proxy_addr 中的 user:pass@，密码含特殊字符时用这里配置)
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
     pub heartbeat_interval: Duration,
-    pub desired_fields: Vec<&'static str>,
+    /// 上游 WebSocket 单条消息 / 单帧的最大字节数，超出即断开重连 (防御异常的超大帧)
+    pub ws_max_message_size: usize,
+    pub ws_max_frame_size: usize,
+    /// 低于该成交额 (USD) 的 Tick 不更新 K 线 (过滤粉尘成交)，0 表示不过滤
+    pub min_tick_volume_usd: f64,
+    /// K 线与该房间上次推送的完全相同时不推送 kline_update (减少前端重渲染)
+    pub skip_unchanged_kline_updates: bool,
+    /// 逐笔成交 (带买卖方向) 是否以 trade_tick 推送给代币的成交房间 trades@{address} (订阅任一周期的客户端)
+    pub broadcast_trade_ticks: bool,
+    /// 各周期房间订阅者合计达到该值的代币，Tick 扇出前先快照房间句柄，推送期间不占用 DashMap 分片读锁；
+    /// 低于该值时直接遍历索引 (省去每笔 Tick 的分配)。0 表示所有代币都走快照
+    pub tick_fanout_snapshot_min_subscribers: usize,
+    /// Tick 调试日志总开关 (排查问题时临时打开)
+    pub tick_debug_logging: bool,
+    /// 开启调试日志时，成交额超过该值的 Tick 打印接收日志
+    pub tick_debug_recv_min_volume: f64,
+    /// 开启调试日志时，成交额超过该值的 Tick 打印广播房间数
+    pub tick_debug_broadcast_min_volume: f64,
+    /// 历史 K 线响应 (historical_kline_initial / completed) 是否 gzip 压缩后以二进制发送，需客户端支持解压
+    pub compress_history_payloads: bool,
+    /// 启用压缩时，JSON 超过该字节数才压缩
+    pub history_compression_min_bytes: usize,
+    /// 告知爬虫需要抓取的字段，可通过 `DESIRED_FIELDS` (逗号分隔) 或 `DESIRED_FIELDS_FILE` 覆盖
+    pub desired_fields: Vec<String>,
+    /// 图标缓存上限 (MB)，对应 `cache_dir`
     pub max_cache_size_mb: u64,
+    /// 大图 (banner 等) 单独的缓存目录，避免大文件把图标挤出缓存
+    pub banner_cache_dir: String,
+    pub banner_max_cache_size_mb: u64,
     pub cache_cleanup_interval: Duration,
+    /// 图片代理最多尝试次数 (仅 5xx / 连接错误会重试)
+    pub image_proxy_max_attempts: u32,
+    /// 图片代理单次请求超时
+    pub image_proxy_attempt_timeout: Duration,
+    /// 图片代理允许的最大响应体 (字节)，超过则返回 413，避免超大响应撑爆内存
+    pub max_image_bytes: usize,
+    /// 图片代理允许的域名 (精确匹配或其子域，如 "bnbstatic.com")，为空表示不限域名
+    pub image_proxy_allowed_hosts: Vec<String>,
+    /// 拒绝解析到内网 / 回环 / 链路本地等非公网地址的 URL (防 SSRF)
+    pub image_proxy_block_private_ips: bool,
+    /// 图片代理域名改写 (原域名小写 -> 镜像域名)，抓取前替换 URL 中的域名，如把慢速 CDN 导向 Cloudflare 镜像
+    pub image_proxy_host_rewrites: HashMap<String, String>,
+    /// 参与报警检测的链 (小写，如 "bsc" / "solana")，为空表示全部链
+    pub alert_chains: HashSet<String>,
+    /// 买压报警：买卖笔数比 (count_buy / count_sell) 下限
+    pub alert_buy_pressure_min_ratio: f64,
+    /// 买压报警：最少买入笔数，避免样本过小时比值失真
+    pub alert_buy_pressure_min_buys: i64,
+    /// 买压报警：前十持仓占比上限 (%)，筹码过于集中时不报
+    pub alert_buy_pressure_max_top10_percent: f64,
+    /// 报警默认冷却时间 (同一代币同一类型)
+    pub alert_cooldown: Duration,
+    /// 按报警类型覆盖冷却时间 (键为类型字符串，如 "volume1m" / "priceChange5m")
+    pub alert_type_cooldowns: HashMap<String, Duration>,
+    /// 冷却记录保留时长 = 冷却时间 × 该倍数，之后由后台任务清理
+    pub alert_cooldown_retention_factor: u32,
+    pub alert_cooldown_prune_interval: Duration,
+    /// 一次性报警类型 (每个代币只报一次，记录持久化到数据库，重启后也不重复)
+    pub alert_once_only_types: HashSet<String>,
+    /// 自定义报警规则 (见 load_alert_rules)，规则名同时作为冷却 / 一次性报警配置的类型键
+    pub alert_rules: Vec<AlertRule>,
+    /// K 线历史接口模板 (占位符: {address} {interval} {limit} {platform})
+    pub kline_api_url_template: String,
+    /// 按链 (小写) 覆盖 K 线历史接口模板，未配置的链使用默认模板
+    pub kline_api_url_templates: HashMap<String, String>,
+    /// 叙事 (AI Narrative) 查询接口
+    pub narrative_api_url: String,
+    /// 链名 (小写) -> 叙事接口使用的 chainId (如 bsc -> "56", solana -> "CT_501")
+    pub narrative_chain_ids: HashMap<String, String>,
+    /// 异步抓取到叙事后是否推送 `narrative_update`，让前端补全已渲染的条目
+    pub broadcast_narrative_updates: bool,
+    /// 叙事缓存持久化文件 (JSON)，None 表示不持久化；启动时加载，定时及退出时写回
+    pub narrative_cache_path: Option<String>,
+    pub narrative_persist_interval: Duration,
+    /// 批量抓取叙事时相邻请求的错峰间隔
+    pub narrative_fetch_stagger: Duration,
+    /// 一批叙事抓取的总错峰上限：批量较大时压缩单条间隔，避免叙事迟迟不到
+    pub narrative_fetch_max_spread: Duration,
+    /// 每个地址保留的不同叙事版本数 (`request_narrative_history`)
+    pub narrative_history_size: usize,
+    /// 已缓存的叙事超过该时长后，再次出现 (爬虫推送 / request_narrative) 时重新抓取，新版本追加到历史；None 表示不刷新
+    pub narrative_refresh_after: Option<Duration>,
+    /// data-broadcast 按分类节流 ("hotlist" / "meme_new" / "meme_migrated" -> 窗口)，未配置的分类立即广播
+    pub broadcast_throttle: HashMap<String, Duration>,
+    /// 录制爬虫推送：配置后每条 data-update 原始 JSON 追加写入该文件 (JSON Lines)，供 `--replay` 回放
+    pub data_update_record_path: Option<String>,
+    /// 回放速度倍数 (按录制时间间隔 ÷ 倍数等待)；0 表示不等待，尽快回放
+    pub replay_speed: f64,
+    /// Socket.IO 命名空间：报警/推送 (爬虫数据、Hotlist、报警、黑名单) 与行情 (K 线订阅)。
+    /// 默认都为 "/" (全部处理器注册在同一命名空间)；配置为不同值 (如 "/alerts"、"/market") 时各自只注册相关处理器
+    pub alerts_namespace: String,
+    pub market_namespace: String,
+    /// 管理接口令牌 (请求头 `X-Admin-Token`)，未配置时管理接口全部拒绝
+    pub admin_token: Option<String>,
+    /// 服务监听地址与端口；端口为 None 时使用各二进制的默认值 (core: HTTPS 30001 / HTTP 30002，market: HTTP 30003)
+    pub bind_addr: String,
+    pub https_port: Option<u16>,
+    pub http_port: Option<u16>,
+    /// 是否启用 HTTPS；关闭或证书文件缺失时仅启动 HTTP 服务
+    pub enable_tls: bool,
+    pub tls_cert_path: String,
+    pub tls_key_path: String,
+    /// 证书文件变更检查间隔 (证书轮换后无需重启即可生效)，None 表示不热重载
+    pub tls_reload_check_interval: Option<Duration>,
+    /// 代理连接池健康检查周期 (每个客户端每周期被探测一次，探测在周期内均匀错开)，None 表示不启用
+    pub pool_health_check_interval: Option<Duration>,
+    /// 健康检查探测地址
+    pub pool_health_check_url: String,
+    /// 代理连接池启动时相邻客户端的构建间隔 (另加同量级随机抖动)，避免启动瞬间大量握手被代理限流
+    pub pool_init_stagger: Duration,
+    /// 直连客户端池大小 (K 线等上游 API)
+    pub direct_pool_size: usize,
+    /// 叙事代理池大小 (上游限流，不宜过大)
+    pub narrative_pool_size: usize,
+    /// 图片代理池大小 (高并发)
+    pub image_pool_size: usize,
+    /// 流动性快照缓冲的落库间隔
+    pub liquidity_flush_interval: Duration,
+    /// K 线新鲜度窗口：同一 primary_key 在窗口内已同步过则跳过上游补齐 (0 表示每次都拉取)
+    pub kline_freshness_window: Duration,
+    /// 缓存中最新 K 线收盘后超过该时长时，首屏历史响应附带 stale_ms 提示数据可能过时，None 表示不提示
+    pub kline_stale_threshold: Option<Duration>,
+    /// 各周期补齐缺口后的目标 K 线数 (未配置的周期为 500)，避免日线等大周期补出大段平直的假数据
+    pub kline_gap_fill_counts: HashMap<String, usize>,
+    /// 支持的 K 线周期 (上游接受的周期)，其余周期的订阅 / 历史请求直接拒绝
+    pub supported_intervals: HashSet<String>,
+    /// 实时 K 线校准周期：定期向上游拉取活跃房间最新的 2 根 K 线，修正 Tick 累积的偏差；None 表示关闭
+    pub kline_reconcile_interval: Option<Duration>,
+    /// 校准容差 (相对偏差，0.01 = 1%)：开高低收任一超过该偏差才修正
+    pub kline_reconcile_tolerance: f64,
+    /// 同时进行的 K 线历史补齐任务上限，平滑大量图表同时加载时的上游压力
+    pub max_concurrent_kline_fetches: usize,
+    /// 每个 socket 每秒允许的订阅操作数 (令牌桶容量同值；resume 按恢复的条数计)，退订不限流；0 表示不限制
+    pub subscribe_ops_per_sec: f64,
+    /// 同时运行的 TokenWorker (即上游 WebSocket 连接) 上限，达到上限时淘汰最久未活跃的空闲 Worker，
+    /// 无可淘汰时拒绝新 Token 的订阅；0 表示不限制
+    pub max_token_workers: usize,
+    /// 热门代币预订阅：Hotlist 中 24h 成交额 (USD) 达到该值的代币提前创建 Worker 并补齐历史，None 表示关闭
+    pub presubscribe_min_volume_usd: Option<f64>,
+    /// 预订阅的 K 线周期 (与前端默认周期一致)
+    pub presubscribe_interval: String,
+    /// 每批 Hotlist 最多预订阅的代币数 (按成交额从高到低)；预订阅不会为此淘汰已有 Worker
+    pub presubscribe_max_tokens: usize,
+    /// 预订阅有效期：代币持续不在热门中且房间无真实订阅者超过该时长后退订
+    pub presubscribe_ttl: Duration,
+    /// 最后一个客户端离开后房间的保留时长：期间重新订阅可复用内存中的 current_kline，0 表示立即移除
+    pub empty_room_grace: Duration,
+    /// 同一 socket 重复订阅已加入的房间时直接回 already_subscribed，不再重跑订阅流程；false 时按新订阅处理
+    pub dedup_duplicate_subscribes: bool,
     // --- 新增数据库配置 ---
     pub database_url: String,
+    /// SQLite journal_mode (delete / truncate / persist / memory / wal / off)，网络文件系统上不宜用 WAL
+    pub sqlite_journal_mode: String,
+    /// SQLite synchronous (off / normal / full / extra)，更重视持久性时用 full
+    pub sqlite_synchronous: String,
+    /// 只读查询库 (副本路径或同一库的只读连接)，None 时读写共用主连接池
+    pub database_read_url: Option<String>,
+    pub read_pool_max_connections: u32,
+    /// 慢查询阈值，超过即打印警告
+    pub slow_query_threshold: Duration,
+    /// 数据库维护间隔 (WAL checkpoint)，None 表示不启用
+    pub db_maintenance_interval: Option<Duration>,
+    /// 每 N 轮维护执行一次 VACUUM，0 表示从不 VACUUM
+    pub db_vacuum_every_n_runs: u32,
+    /// K 线保留期：最新一根早于该时长的 primary_key 整体删除 (已死代币不再写入，单 key 500 根上限管不到)，None 表示不清理
+    pub kline_retention: Option<Duration>,
+    pub kline_prune_interval: Duration,
+    /// Worker 生命周期审计事件 (worker_events 表) 保留时长
+    pub worker_events_retention: Duration,
 }
 
 impl Config {
@@ -20,30 +296,203 @@ impl Config {
             cache_dir: "./image_cache".to_string(),
             binance_wss_url: "wss://nbstream.binance.com/w3w/stream".to_string(),
             proxy_addr: "127.0.0.1:1080".to_string(),
+            proxy_username: None,
+            proxy_password: None,
             heartbeat_interval: Duration::from_secs(300),
-            desired_fields: vec![
-                "icon",
-                "symbol",
-                "price",
-                "marketCap",
-                "chain",
-                "chainId",
-                "contractAddress",
-                "volume1m",
-                "volume5m",
-                "volume1h",
-                "volume4h",
-                "volume24h",
-                "priceChange1m",
-                "priceChange5m",
-                "priceChange1h",
-                "priceChange4h",
-                "priceChange24h",
-            ],
-            max_cache_size_mb: 70,
+            ws_max_message_size: 4 << 20,
+            ws_max_frame_size: 1 << 20,
+            min_tick_volume_usd: 0.0,
+            skip_unchanged_kline_updates: false,
+            broadcast_trade_ticks: true,
+            tick_fanout_snapshot_min_subscribers: 10,
+            tick_debug_logging: false,
+            tick_debug_recv_min_volume: 1000.0,
+            tick_debug_broadcast_min_volume: 5000.0,
+            compress_history_payloads: false,
+            history_compression_min_bytes: 8 * 1024,
+            desired_fields: load_desired_fields(),
+            max_cache_size_mb: 1024, // 1 GB
+            banner_cache_dir: "./image_cache/banner".to_string(),
+            banner_max_cache_size_mb: 512,
             cache_cleanup_interval: Duration::from_secs(3600),
+            image_proxy_max_attempts: 10,
+            image_proxy_attempt_timeout: Duration::from_secs(8),
+            max_image_bytes: 10 * 1024 * 1024,
+            image_proxy_allowed_hosts: Vec::new(),
+            image_proxy_block_private_ips: true,
+            image_proxy_host_rewrites: HashMap::new(),
+            alert_chains: HashSet::new(),
+            alert_buy_pressure_min_ratio: 3.0,
+            alert_buy_pressure_min_buys: 30,
+            alert_buy_pressure_max_top10_percent: 30.0,
+            alert_cooldown: Duration::from_secs(60),
+            alert_type_cooldowns: HashMap::new(),
+            alert_cooldown_retention_factor: 10,
+            alert_cooldown_prune_interval: Duration::from_secs(600),
+            alert_once_only_types: HashSet::from(["migration".to_string()]),
+            alert_rules: load_alert_rules(),
+            kline_api_url_template: "https://dquery.sintral.io/u-kline/v1/k-line/candles?address={address}&interval={interval}&limit={limit}&platform={platform}".to_string(),
+            kline_api_url_templates: HashMap::new(),
+            narrative_api_url: "https://web3.binance.com/bapi/defi/v1/public/wallet-direct/buw/wallet/token/ai/narrative/query".to_string(),
+            narrative_chain_ids: [
+                ("bsc", "56"),
+                ("sol", "CT_501"),
+                ("solana", "CT_501"),
+                ("eth", "1"),
+                ("ethereum", "1"),
+                ("base", "8453"),
+                ("arb", "42161"),
+                ("arbitrum", "42161"),
+                ("matic", "137"),
+                ("polygon", "137"),
+                ("op", "10"),
+                ("optimism", "10"),
+                ("avax", "43114"),
+                ("avalanche", "43114"),
+            ]
+            .into_iter()
+            .map(|(chain, id)| (chain.to_string(), id.to_string()))
+            .collect(),
+            broadcast_throttle: HashMap::new(),
+            data_update_record_path: None,
+            replay_speed: 1.0,
+            admin_token: None,
+            alerts_namespace: "/".to_string(),
+            market_namespace: "/".to_string(),
+            broadcast_narrative_updates: true,
+            narrative_cache_path: None,
+            narrative_persist_interval: Duration::from_secs(300),
+            narrative_fetch_stagger: Duration::from_millis(250),
+            narrative_fetch_max_spread: Duration::from_secs(3),
+            narrative_history_size: 5,
+            narrative_refresh_after: Some(Duration::from_secs(30 * 60)),
+            bind_addr: "0.0.0.0".to_string(),
+            https_port: None,
+            http_port: None,
+            enable_tls: true,
+            tls_cert_path: "cert.pem".to_string(),
+            tls_key_path: "key.pem".to_string(),
+            tls_reload_check_interval: Some(Duration::from_secs(60)),
+            pool_health_check_interval: None,
+            pool_health_check_url: "https://web3.binance.com/".to_string(),
+            pool_init_stagger: Duration::from_millis(200),
+            direct_pool_size: 20,
+            narrative_pool_size: 10,
+            image_pool_size: 10,
+            liquidity_flush_interval: Duration::from_secs(5),
+            kline_freshness_window: Duration::from_secs(10),
+            kline_stale_threshold: Some(Duration::from_secs(3600)),
+            kline_gap_fill_counts: HashMap::from([
+                ("1h".to_string(), 200),
+                ("4h".to_string(), 120),
+                ("1d".to_string(), 90),
+            ]),
+            kline_reconcile_interval: None,
+            kline_reconcile_tolerance: 0.01,
+            supported_intervals: ["1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            max_concurrent_kline_fetches: 8,
+            subscribe_ops_per_sec: 10.0,
+            max_token_workers: 0,
+            presubscribe_min_volume_usd: None,
+            presubscribe_interval: "5m".to_string(),
+            presubscribe_max_tokens: 20,
+            presubscribe_ttl: Duration::from_secs(600),
+            empty_room_grace: Duration::from_secs(15),
+            dedup_duplicate_subscribes: true,
             // --- SQLite 数据库文件路径 ---
             database_url: "sqlite:./database/kline_cache.db?mode=rwc".to_string(),
+            sqlite_journal_mode: "wal".to_string(),
+            sqlite_synchronous: "normal".to_string(),
+            database_read_url: None,
+            read_pool_max_connections: 20,
+            slow_query_threshold: Duration::from_millis(100),
+            db_maintenance_interval: None,
+            db_vacuum_every_n_runs: 7,
+            kline_retention: None,
+            kline_prune_interval: Duration::from_secs(3600),
+            worker_events_retention: Duration::from_secs(7 * 24 * 3600),
         }
     }
-}
\ No newline at end of file
+
+    /// 解析代理地址，未配置代理 ("none" / 空) 或地址无效时返回 None
+    pub fn proxy_endpoint(&self) -> Option<Url> {
+        let addr = self.proxy_addr.trim();
+        if addr.is_empty() || addr == "none" {
+            return None;
+        }
+        if addr.contains("://") {
+            Url::parse(addr).ok()
+        } else {
+            Url::parse(&format!("http://{}", addr)).ok()
+        }
+    }
+
+    /// 代理凭据 (用户名, 密码)：单独配置优先，其次取 proxy_addr 中的 user:pass@ (Url 中为百分号编码，这里解码为原文)
+    pub fn proxy_credentials(&self) -> Option<(String, String)> {
+        if let Some(username) = &self.proxy_username {
+            return Some((username.clone(), self.proxy_password.clone().unwrap_or_default()));
+        }
+        let endpoint = self.proxy_endpoint()?;
+        if endpoint.username().is_empty() {
+            return None;
+        }
+        let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+        Some((decode(endpoint.username()), decode(endpoint.password().unwrap_or_default())))
+    }
+
+    /// 供 reqwest 使用的代理 URL (reqwest 会从 URL 中的 user:pass@ 生成代理认证头)
+    pub fn proxy_url(&self) -> String {
+        let Some(mut endpoint) = self.proxy_endpoint() else {
+            return format!("http://{}", self.proxy_addr);
+        };
+        if let Some(username) = &self.proxy_username {
+            // 由 Url 负责转义特殊字符
+            let _ = endpoint.set_username(username);
+            let _ = endpoint.set_password(self.proxy_password.as_deref());
+        }
+        endpoint.to_string()
+    }
+
+    /// 选择某条链的 K 线历史接口模板
+    pub fn kline_api_url_template_for(&self, chain: &str) -> &str {
+        self.kline_api_url_templates
+            .get(&chain.to_lowercase())
+            .unwrap_or(&self.kline_api_url_template)
+    }
+
+    /// 查找叙事接口使用的 chainId
+    pub fn narrative_chain_id(&self, chain: &str) -> Option<String> {
+        self.narrative_chain_ids.get(&chain.to_lowercase()).cloned()
+    }
+
+    /// 周期是否受支持 (需在 supported_intervals 中)
+    pub fn is_supported_interval(&self, interval: &str) -> bool {
+        self.supported_intervals.contains(interval)
+    }
+
+    /// 监听地址：bind_addr + 端口 (IPv6 写成不带方括号的形式，如 "::")
+    pub fn socket_addr(&self, port: u16) -> std::net::SocketAddr {
+        let ip = self.bind_addr.parse().unwrap_or_else(|e| {
+            warn!("⚠️ [CONFIG] Invalid bind_addr '{}' ({}), falling back to 0.0.0.0", self.bind_addr, e);
+            std::net::IpAddr::from([0, 0, 0, 0])
+        });
+        std::net::SocketAddr::new(ip, port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_credentials_are_percent_decoded() {
+        let mut config = Config::new();
+        config.proxy_username = None;
+        config.proxy_password = None;
+        config.proxy_addr = "us%65r:p%40ss@127.0.0.1:8080".to_string();
+        assert_eq!(config.proxy_credentials(), Some(("user".to_string(), "p@ss".to_string())));
+    }
+}
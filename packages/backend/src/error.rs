@@ -3,8 +3,25 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use std::time::Duration;
 use thiserror::Error;
 
+/// K 线上游拉取失败的原因：区分限流与数据异常，方便重试退避和排查
+#[derive(Debug, Error)]
+pub enum KlineFetchError {
+    #[error("upstream rate limited (HTTP 429), retry after {retry_after:?}")]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("upstream returned HTTP {status}: {snippet}")]
+    Status { status: StatusCode, snippet: String },
+
+    #[error("upstream returned non-JSON or unexpected data (HTTP {status}): {error}; body: {snippet}")]
+    BadData { status: StatusCode, error: String, snippet: String },
+
+    #[error("upstream request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
 // 定义我们应用的主要错误类型，专门用于HTTP处理层
 #[derive(Debug, Error)]
 pub enum AppError {
@@ -31,6 +48,34 @@ pub enum AppError {
 
     #[error("Failed to create proxy client: {0}")]
     ProxyClientBuild(String),
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Kline upstream error: {0}")]
+    KlineFetch(#[from] KlineFetchError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+impl AppError {
+    /// anyhow 错误中若包含上游拉取失败，保留其类型以便返回 429 / 502，其余按内部错误处理
+    pub fn from_anyhow(error: anyhow::Error) -> Self {
+        match error.downcast::<KlineFetchError>() {
+            Ok(fetch) => AppError::KlineFetch(fetch),
+            Err(error) => AppError::Database(error),
+        }
+    }
 }
 
 // 实现 IntoResponse trait，这样我们的错误类型可以直接在 Axum handler 中返回
@@ -43,6 +88,14 @@ impl IntoResponse for AppError {
             AppError::InvalidUrl(url) => (StatusCode::BAD_REQUEST, format!("Invalid URL: {}", url)),
             AppError::UrlParse(_) => (StatusCode::BAD_REQUEST, "Failed to parse URL".to_string()),
             AppError::UpstreamError(code) => (code, format!("Upstream server error: {}", code)),
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            AppError::KlineFetch(KlineFetchError::RateLimited { .. }) => {
+                (StatusCode::TOO_MANY_REQUESTS, "Upstream rate limited, try again later".to_string())
+            }
+            AppError::KlineFetch(_) => (StatusCode::BAD_GATEWAY, "Upstream kline source unavailable".to_string()),
             // 其他错误都归为内部服务器错误，避免向客户端暴露过多细节
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
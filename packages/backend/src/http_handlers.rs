@@ -1,20 +1,441 @@
 // packages/backend/src/http_handlers.rs
-use super::{cache, error::AppError, types::ImageProxyQuery, ServerState};
+use super::{
+    cache::{self, CacheKind},
+    client_pool::is_public_ip,
+    config::Config,
+    error::AppError,
+    kline_handler,
+    socket_handlers::{chain_to_pool_id, normalize_address},
+    state::{ImageFetchResult, NarrativeStatsSnapshot, SubscriptionCommand, WorkerMetricsSnapshot},
+    types::{AdminAddressRequest, AdminLogLevelRequest, AdminPruneRequest, AdminTokenRequest, AlertThresholds, BuildInfo, ImageProxyQuery, KlineExportQuery, KlineHistoryResponse, KlineHttpQuery, KlineSubscribePayload, LiquidityExportQuery, PriceQuery, PriceResponse, PruneTarget, WorkerEventRecord, WorkerEventsQuery},
+    ServerState,
+};
 use axum::{
-    extract::{Query, State},
+    extract::{Json, Query, State},
     http::HeaderMap,
     response::{IntoResponse, Json as AxumJson, Response},
 };
+use bytes::{Bytes, BytesMut};
 use http::HeaderValue;
 use reqwest;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
 use tracing::{warn, error, info};
 use url::Url;
 
 /// 处理监控字段配置的请求。
-pub async fn desired_fields_handler(State(state): State<ServerState>) -> AxumJson<Vec<&'static str>> {
+pub async fn desired_fields_handler(State(state): State<ServerState>) -> AxumJson<Vec<String>> {
     AxumJson(state.config.desired_fields.clone())
 }
 
+/// 当前运行的构建版本 (git 哈希与构建时间由 build.rs 注入)
+pub async fn version_handler() -> AxumJson<BuildInfo> {
+    AxumJson(BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("GIT_COMMIT_HASH"),
+        build_timestamp: env!("BUILD_TIMESTAMP"),
+    })
+}
+
+/// 叙事缓存命中统计
+pub async fn narrative_stats_handler(State(state): State<ServerState>) -> AxumJson<NarrativeStatsSnapshot> {
+    AxumJson(state.narrative_stats.snapshot(state.narrative_cache.len()))
+}
+
+/// 各 TokenWorker 的帧吞吐统计，按广播量从高到低排序 (定位负载最重的代币)
+pub async fn workers_handler(State(state): State<ServerState>) -> AxumJson<Vec<WorkerMetricsSnapshot>> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut workers: Vec<WorkerMetricsSnapshot> = state
+        .worker_status
+        .iter()
+        .map(|entry| entry.value().snapshot(entry.key(), now))
+        .collect();
+    let total_broadcasts: u64 = workers.iter().map(|w| w.broadcasts_emitted).sum();
+    if total_broadcasts > 0 {
+        for worker in &mut workers {
+            worker.broadcast_share = worker.broadcasts_emitted as f64 / total_broadcasts as f64;
+        }
+    }
+    workers.sort_by(|a, b| b.broadcasts_emitted.cmp(&a.broadcasts_emitted));
+    AxumJson(workers)
+}
+
+/// Worker 生命周期事件 (按时间倒序)，用于排查 "某时刻该代币的 Worker 是否存活"
+pub async fn worker_events_handler(
+    State(state): State<ServerState>,
+    Query(query): Query<WorkerEventsQuery>,
+) -> Result<AxumJson<Vec<WorkerEventRecord>>, AppError> {
+    let address = query.address.as_deref().map(str::trim).filter(|a| !a.is_empty());
+    let events = kline_handler::query_worker_events(
+        &state.read_pool,
+        address,
+        query.since.unwrap_or(0),
+        query.until.unwrap_or(i64::MAX),
+        query.limit.unwrap_or(200).clamp(1, 1000),
+    )
+    .await?;
+    Ok(AxumJson(events))
+}
+
+/// 校验管理接口令牌 (请求头 `X-Admin-Token`)
+fn check_admin_auth(state: &ServerState, headers: &HeaderMap) -> Result<(), AppError> {
+    let expected = state.config.admin_token.as_deref().ok_or(AppError::Unauthorized)?;
+    let provided = headers
+        .get("x-admin-token")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(())
+}
+
+/// 常量时间比较：不在首个不同字节处提前返回，避免按响应耗时逐字节猜出 token (长度不同直接失败)
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 管理接口：读取当前报警阈值
+pub async fn get_alert_config_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+) -> Result<AxumJson<AlertThresholds>, AppError> {
+    check_admin_auth(&state, &headers)?;
+    Ok(AxumJson(state.alert_thresholds.read().await.clone()))
+}
+
+/// 管理接口：更新报警阈值 (只需提交要修改的字段)，对后续报警检测立即生效
+pub async fn update_alert_config_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(patch): Json<serde_json::Map<String, serde_json::Value>>,
+) -> Result<AxumJson<AlertThresholds>, AppError> {
+    check_admin_auth(&state, &headers)?;
+
+    // 写锁覆盖 "读取-合并-写回" 全过程，并发更新不会互相覆盖
+    let mut thresholds = state.alert_thresholds.write().await;
+    let mut merged = match serde_json::to_value(&*thresholds)? {
+        serde_json::Value::Object(map) => map,
+        _ => unreachable!("AlertThresholds serializes to an object"),
+    };
+    merged.extend(patch);
+    let updated: AlertThresholds = serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| AppError::BadRequest(format!("Invalid alert config: {}", e)))?;
+
+    let values = [
+        updated.volume_1m_usd,
+        updated.volume_5m_usd,
+        updated.price_change_1m_percent,
+        updated.price_change_5m_percent,
+        updated.price_change_1m_min_volume_usd,
+        updated.price_change_5m_min_volume_usd,
+        updated.buy_pressure_min_ratio,
+        updated.buy_pressure_max_top10_percent,
+    ];
+    if values.iter().any(|v| !v.is_finite() || *v < 0.0) || updated.buy_pressure_min_buys < 0 {
+        return Err(AppError::BadRequest("Alert thresholds must be non-negative numbers".to_string()));
+    }
+
+    warn!("🛠️ [ADMIN] Alert thresholds updated: {:?}", updated);
+    *thresholds = updated.clone();
+    Ok(AxumJson(updated))
+}
+
+/// 查询单个代币的当前价格，无需建立 socket 订阅
+pub async fn price_handler(
+    State(state): State<ServerState>,
+    Query(query): Query<PriceQuery>,
+) -> Result<AxumJson<PriceResponse>, AppError> {
+    let pool_id = chain_to_pool_id(&query.chain)
+        .ok_or_else(|| AppError::BadRequest(format!("Unsupported chain: {}", query.chain)))?;
+    if query.address.is_empty() {
+        return Err(AppError::BadRequest("Missing address".to_string()));
+    }
+
+    let address = normalize_address(pool_id, &query.address);
+    let interval = query.interval.unwrap_or_else(|| "1m".to_string());
+    if !state.config.is_supported_interval(&interval) {
+        return Err(AppError::BadRequest(format!("Unsupported interval: {}", interval)));
+    }
+    let payload = KlineSubscribePayload {
+        address: query.address,
+        chain: query.chain,
+        interval,
+        fill_gaps: false,
+        no_cache: false,
+    };
+    kline_handler::get_current_price(&state, &payload, pool_id, &address)
+        .await
+        .map_err(AppError::from_anyhow)?
+        .map(AxumJson)
+        .ok_or_else(|| AppError::NotFound(format!("No price available for {}", address)))
+}
+
+/// 通过 HTTP 获取缓存的 K 线 (与 socket `request_historical_kline` 的初始数据相同)，可选后台补齐
+pub async fn klines_handler(
+    State(state): State<ServerState>,
+    Query(query): Query<KlineHttpQuery>,
+) -> Result<AxumJson<KlineHistoryResponse>, AppError> {
+    if chain_to_pool_id(&query.chain).is_none() {
+        return Err(AppError::BadRequest(format!("Unsupported chain: {}", query.chain)));
+    }
+    if query.address.is_empty() || query.interval.is_empty() {
+        return Err(AppError::BadRequest("Missing address or interval".to_string()));
+    }
+    if !state.config.is_supported_interval(&query.interval) {
+        return Err(AppError::BadRequest(format!("Unsupported interval: {}", query.interval)));
+    }
+
+    // 与 socket 请求使用同样的缓存键 (address@chain@interval)
+    let payload = KlineSubscribePayload {
+        address: query.address,
+        chain: query.chain,
+        interval: query.interval,
+        fill_gaps: query.fill_gaps.unwrap_or(true),
+        no_cache: false,
+    };
+    let mut resp = kline_handler::load_cached_kline_history(&state, &payload).await;
+    if let Some(limit) = query.limit {
+        let skip = resp.data.len().saturating_sub(limit);
+        resp.data.drain(..skip);
+    }
+
+    if query.sync.unwrap_or(false) {
+        kline_handler::spawn_kline_sync(state.clone(), payload, None);
+    }
+    Ok(AxumJson(resp))
+}
+
+/// 批量导出库中全部 K 线 (列式 f64 二进制，见 `encode_klines_columnar`)，供量化分析批量拉取
+pub async fn klines_export_handler(
+    State(state): State<ServerState>,
+    Query(query): Query<KlineExportQuery>,
+) -> Result<Response, AppError> {
+    if chain_to_pool_id(&query.chain).is_none() {
+        return Err(AppError::BadRequest(format!("Unsupported chain: {}", query.chain)));
+    }
+    if query.address.is_empty() || query.interval.is_empty() {
+        return Err(AppError::BadRequest("Missing address or interval".to_string()));
+    }
+    if !state.config.is_supported_interval(&query.interval) {
+        return Err(AppError::BadRequest(format!("Unsupported interval: {}", query.interval)));
+    }
+
+    // 与 socket 请求使用同样的缓存键 (address@chain@interval)
+    let payload = KlineSubscribePayload {
+        address: query.address,
+        chain: query.chain,
+        interval: query.interval,
+        fill_gaps: false,
+        no_cache: false,
+    };
+    let klines = kline_handler::get_all_klines(&state, &payload).await?;
+    let body = kline_handler::encode_klines_columnar(&klines);
+
+    let safe_name: String = format!("{}_{}_{}", payload.chain, payload.address, payload.interval)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect();
+    let disposition = format!("attachment; filename=\"klines_{}.f64\"", safe_name);
+    Ok((
+        [
+            (http::header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (http::header::CONTENT_DISPOSITION, disposition),
+            (http::header::HeaderName::from_static("x-kline-columns"), "time,open,high,low,close,volume".to_string()),
+            (http::header::HeaderName::from_static("x-kline-rows"), klines.len().to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// 导出流动性历史 (JSON 或 CSV: `time_bucket,value`)，供回测等离线分析使用
+pub async fn liquidity_export_handler(
+    State(state): State<ServerState>,
+    Query(query): Query<LiquidityExportQuery>,
+) -> Result<Response, AppError> {
+    if query.address.is_empty() {
+        return Err(AppError::BadRequest("Missing address".to_string()));
+    }
+    let interval = query.interval.as_deref().unwrap_or("1m");
+    let format = query.format.as_deref().unwrap_or("json").to_lowercase();
+    if format != "json" && format != "csv" {
+        return Err(AppError::BadRequest(format!("Unsupported format: {}", format)));
+    }
+
+    let points = kline_handler::query_liquidity_history_aggregated(&state.read_pool, &query.address, interval, state.config.slow_query_threshold).await?;
+
+    if format == "csv" {
+        let mut csv = String::from("time_bucket,value\n");
+        for point in &points {
+            csv.push_str(&format!("{},{}\n", point.time, point.value));
+        }
+        // 文件名只保留字母数字，避免用户输入破坏响应头
+        let safe_name: String = format!("{}_{}", query.address, interval)
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        let disposition = format!("attachment; filename=\"liquidity_{}.csv\"", safe_name);
+        Ok((
+            [
+                (http::header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (http::header::CONTENT_DISPOSITION, disposition),
+            ],
+            csv,
+        )
+            .into_response())
+    } else {
+        Ok(AxumJson(points).into_response())
+    }
+}
+
+/// 管理接口：列出白名单
+pub async fn get_whitelist_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+) -> Result<AxumJson<Vec<String>>, AppError> {
+    check_admin_auth(&state, &headers)?;
+    let mut list: Vec<String> = state.whitelist.iter().map(|item| item.key().clone()).collect();
+    list.sort();
+    Ok(AxumJson(list))
+}
+
+/// 管理接口：加入白名单 (持久化到数据库)
+pub async fn add_whitelist_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(req): Json<AdminAddressRequest>,
+) -> Result<AxumJson<serde_json::Value>, AppError> {
+    check_admin_auth(&state, &headers)?;
+    let address = whitelist_address(&req)?;
+    kline_handler::add_to_whitelist(&state.db_pool, &address).await?;
+    state.whitelist.insert(address.clone());
+    info!("⭐ [ADMIN] Whitelisted {}", address);
+    Ok(AxumJson(serde_json::json!({ "action": "add", "address": address })))
+}
+
+/// 管理接口：移出白名单
+pub async fn remove_whitelist_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(req): Json<AdminAddressRequest>,
+) -> Result<AxumJson<serde_json::Value>, AppError> {
+    check_admin_auth(&state, &headers)?;
+    let address = whitelist_address(&req)?;
+    kline_handler::remove_from_whitelist(&state.db_pool, &address).await?;
+    let removed = state.whitelist.remove(&address).is_some();
+    info!("⭐ [ADMIN] Removed {} from whitelist (was present: {})", address, removed);
+    Ok(AxumJson(serde_json::json!({ "action": "remove", "address": address, "removed": removed })))
+}
+
+/// 管理接口：立即执行一次清理 (备份前或需要马上回收空间时)，返回删除条数
+pub async fn admin_prune_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(req): Json<AdminPruneRequest>,
+) -> Result<AxumJson<serde_json::Value>, AppError> {
+    check_admin_auth(&state, &headers)?;
+    let deleted = match req.target {
+        PruneTarget::Liquidity => kline_handler::prune_liquidity_history(&state.db_pool).await?,
+        PruneTarget::Blacklist => crate::prune_expired_blacklist(&state).await?,
+        PruneTarget::Klines => {
+            let retention = state
+                .config
+                .kline_retention
+                .ok_or_else(|| AppError::BadRequest("kline retention is not configured".to_string()))?;
+            state.kline_store.prune_inactive(retention.as_secs() as i64).await?
+        }
+        PruneTarget::Alerts => {
+            crate::alert_handler::prune_alert_cooldowns(&state, chrono::Utc::now().timestamp_millis()) as u64
+        }
+    };
+    warn!("🧹 [ADMIN] Manual prune {:?}: {} rows deleted", req.target, deleted);
+    Ok(AxumJson(serde_json::json!({ "target": req.target, "deleted": deleted })))
+}
+
+/// 管理接口：运行时替换日志过滤指令 (排查线上问题时临时打开某个模块的 debug 日志)
+pub async fn admin_log_level_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(req): Json<AdminLogLevelRequest>,
+) -> Result<AxumJson<serde_json::Value>, AppError> {
+    check_admin_auth(&state, &headers)?;
+    let filter = tracing_subscriber::EnvFilter::try_new(req.filter.trim())
+        .map_err(|e| AppError::BadRequest(format!("Invalid log filter: {}", e)))?;
+    crate::set_log_filter(filter)?;
+    warn!("🔧 [ADMIN] Log filter set to '{}'", req.filter.trim());
+    Ok(AxumJson(serde_json::json!({ "filter": req.filter.trim() })))
+}
+
+/// 名单地址统一小写 (与黑名单一致)
+fn whitelist_address(req: &AdminAddressRequest) -> Result<String, AppError> {
+    let address = req.address.trim().to_lowercase();
+    if address.is_empty() {
+        return Err(AppError::BadRequest("Missing address".to_string()));
+    }
+    Ok(address)
+}
+
+/// 管理接口：强制重置单个 Token (停止 Worker、清理房间与索引、盘口订阅、清空 K 线缓存)
+pub async fn admin_reset_token_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(req): Json<AdminTokenRequest>,
+) -> Result<AxumJson<serde_json::Value>, AppError> {
+    check_admin_auth(&state, &headers)?;
+
+    let pool_id = chain_to_pool_id(&req.chain)
+        .ok_or_else(|| AppError::BadRequest(format!("Unsupported chain: {}", req.chain)))?;
+    let address = normalize_address(pool_id, &req.address);
+    warn!("🛠️ [ADMIN] Resetting token {} on {}", address, req.chain);
+
+    // 1. 停止 Worker
+    let worker_stopped = match state.token_managers.remove(&address) {
+        Some((_, sender)) => sender.send(SubscriptionCommand::Subscribe("SHUTDOWN".to_string())).is_ok(),
+        None => false,
+    };
+    state.worker_activity.remove(&address);
+    state.worker_status.remove(&address);
+    if let Some(counter) = state.token_subscribers.get(&address) {
+        counter.store(0, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    // 2. 清理房间与索引，并通知房间内客户端重新订阅
+    let rooms: Vec<String> = state
+        .room_index
+        .remove(&address)
+        .map(|(_, rooms)| rooms.into_iter().collect())
+        .unwrap_or_default();
+    for room_key in &rooms {
+        state.app_state.remove(room_key);
+        let Some(ns) = state.io.of(state.config.market_namespace.as_str()) else { continue; };
+        ns.to(room_key.clone())
+            .emit("token_reset", &serde_json::json!({ "room": room_key, "address": address }))
+            .await
+            .ok();
+    }
+    // 盘口订阅与房间一并清除 (残留条目会被当作仍有盘口订阅者，客户端需重新订阅)
+    state.depth_subscriptions.remove(&address);
+
+    // 3. 清空 K 线缓存
+    let deleted_klines = kline_handler::clear_token_kline_cache(&state, &address).await?;
+    // primary_key 中的地址与 address 同样规范化，按精确前缀清理
+    let key_prefix = format!("{}@", address);
+    state.kline_last_sync.retain(|key, _| !key.starts_with(&key_prefix));
+
+    info!(
+        "✅ [ADMIN] Reset {} done. Worker stopped: {}, rooms cleared: {}, kline rows deleted: {}",
+        address, worker_stopped, rooms.len(), deleted_klines
+    );
+    Ok(AxumJson(serde_json::json!({
+        "address": address,
+        "workerStopped": worker_stopped,
+        "roomsCleared": rooms.len(),
+        "klineRowsDeleted": deleted_klines,
+    })))
+}
+
 /// 处理图片代理请求，包含缓存逻辑。
 /// 
 /// 优化：使用了连接池 (Connection Pool) 和重试机制，
@@ -23,52 +444,209 @@ pub async fn image_proxy_handler(
     State(state): State<ServerState>,
     Query(query): Query<ImageProxyQuery>,
 ) -> Result<Response, AppError> {
-    let config = state.config;
-    let image_url = query.url;
+    let config = state.config.clone();
+    let mut image_url = query.url;
+    let cache_kind = CacheKind::from_query(query.kind.as_deref());
 
     // 0. 入口日志，追踪所有请求
     // 使用 trace 或 debug 级别可能更好，但为了调试目前的问题，先用 info
     // 截断 URL 避免日志过长，或者只打印 hash 部分（如果 URL 结构允许）。这里先打印完整 URL
     info!("📥 [IMG PROXY] Incoming Request: {}", image_url);
 
-    // 1. 验证 URL
-    Url::parse(&image_url).map_err(|_| AppError::InvalidUrl(image_url.clone()))?;
+    // 1. 域名改写 (镜像 CDN) 后验证 URL (含 SSRF 防护：域名白名单 + 禁止内网地址)
+    // 缓存 key 与 in-flight 去重都使用改写后的 URL
+    let mut parsed_url = Url::parse(&image_url).map_err(|_| AppError::InvalidUrl(image_url.clone()))?;
+    if rewrite_image_host(&config, &mut parsed_url) {
+        info!("🔀 [IMG PROXY] Rewrote {} -> {}", image_url, parsed_url);
+        image_url = parsed_url.to_string();
+    }
+    check_image_url_allowed(&config, &parsed_url).await?;
 
     // 2. 检查缓存
-    if let Some(cached_response) = cache::get_cached_response(&image_url, &config).await? {
+    if let Some(cached_response) = cache::get_cached_response(&image_url, cache_kind, &config).await? {
         info!("💾 [IMG PROXY] Cache HIT: {}", image_url);
         return Ok(cached_response);
     }
     
     info!("☁️ [IMG PROXY] Cache MISS: {}. Fetching from upstream...", image_url);
 
-    // 3. 如果缓存未命中，则从源站抓取 (使用连接池 + 重试逻辑)
-    // 最多重试 2 次
+    // 3. 如果缓存未命中，则从源站抓取。
+    // 同一 URL 的并发请求共享同一个 in-flight 抓取 (页面加载时同一图标往往被并发请求多次)
+    let cell = state
+        .image_inflight
+        .entry(image_url.clone())
+        .or_insert_with(|| Arc::new(OnceCell::new()))
+        .clone();
+    let result = cell
+        .get_or_init(|| fetch_and_cache_image(&state, &image_url, cache_kind))
+        .await
+        .clone();
+    // 抓取完成后移除 in-flight 记录 (仅移除自己参与的那一个，避免误删新一轮抓取)
+    state.image_inflight.remove_if(&image_url, |_, v| Arc::ptr_eq(v, &cell));
+
+    // 4. 处理结果
+    match result {
+        Ok((response_content_type, image_buffer)) => {
+            // 返回响应
+            let mut headers = HeaderMap::new();
+            headers.insert(http::header::CONTENT_TYPE, response_content_type);
+            headers.insert(
+                http::header::CACHE_CONTROL,
+                HeaderValue::from_static("public, max-age=86400"),
+            );
+            headers.insert(http::header::CONTENT_LENGTH, image_buffer.len().into());
+
+            Ok((headers, image_buffer).into_response())
+        },
+        Err(status) => Err(AppError::UpstreamError(status)),
+    }
+}
+
+/// 按 image_proxy_host_rewrites 替换 URL 的域名，返回是否发生改写
+fn rewrite_image_host(config: &Config, url: &mut Url) -> bool {
+    let Some(host) = url.host_str().map(|h| h.trim_end_matches('.').to_lowercase()) else { return false; };
+    let Some(target) = config.image_proxy_host_rewrites.get(&host) else { return false; };
+    if let Err(e) = url.set_host(Some(target)) {
+        warn!("⚠️ [IMG PROXY] Invalid rewrite target {} for {}: {}", target, host, e);
+        return false;
+    }
+    true
+}
+
+/// 图片代理 SSRF 防护：只允许 http(s)，域名需在白名单内 (配置了的话)，且解析结果不能是内网地址。
+/// 直连时图片池客户端的连接本身也经 PublicOnlyResolver 解析，检查之后的 DNS rebinding 同样连不到内网；
+/// 走代理时目标由代理解析，这里的检查是唯一一道防线
+async fn check_image_url_allowed(config: &Config, url: &Url) -> Result<(), AppError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::Forbidden(format!("Scheme not allowed: {}", url.scheme())));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| AppError::InvalidUrl(url.to_string()))?
+        .trim_end_matches('.')
+        .to_lowercase();
+
+    if !config.image_proxy_allowed_hosts.is_empty()
+        && !config.image_proxy_allowed_hosts.iter().any(|allowed| host_matches(&host, allowed))
+    {
+        warn!("🚫 [IMG PROXY] Host not in allowlist: {}", host);
+        return Err(AppError::Forbidden(format!("Host not allowed: {}", host)));
+    }
+
+    if config.image_proxy_block_private_ips {
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<SocketAddr> = match url.host() {
+            Some(url::Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+            Some(url::Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+            _ => tokio::net::lookup_host((host.as_str(), port))
+                .await
+                .map_err(|_| AppError::InvalidUrl(url.to_string()))?
+                .collect(),
+        };
+        if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+            warn!("🚫 [IMG PROXY] {} resolves to non-public address {}", host, addr.ip());
+            return Err(AppError::Forbidden(format!("Host resolves to a non-public address: {}", host)));
+        }
+    }
+    Ok(())
+}
+
+/// 域名白名单匹配：完全相同或为其子域 ("cdn.bnbstatic.com" 匹配 "bnbstatic.com")
+fn host_matches(host: &str, allowed: &str) -> bool {
+    let allowed = allowed.trim().trim_start_matches("*.").trim_end_matches('.').to_lowercase();
+    !allowed.is_empty()
+        && (host == allowed || host.strip_suffix(allowed.as_str()).is_some_and(|prefix| prefix.ends_with('.')))
+}
+
+/// 分块读取响应体，累计超过 max_bytes 立即停止并返回 None (不依赖 Content-Length)
+async fn read_body_capped(mut res: reqwest::Response, max_bytes: usize) -> Result<Option<Bytes>, reqwest::Error> {
+    let mut buffer = BytesMut::new();
+    while let Some(chunk) = res.chunk().await? {
+        if buffer.len() + chunk.len() > max_bytes {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(Some(buffer.freeze()))
+}
+
+/// 图片请求最多跟随的重定向次数
+const IMAGE_MAX_REDIRECTS: usize = 5;
+
+enum ImageRequestError {
+    /// 重定向目标未通过 SSRF 检查 (或重定向次数超限)
+    Blocked,
+    Request(reqwest::Error),
+}
+
+/// 发起图片请求并手动跟随重定向：图片池客户端不会自动跟随，
+/// 每一跳的目标都重新经过 check_image_url_allowed (白名单 + 内网地址)，避免公网源站 302 到内网/元数据地址
+async fn send_following_redirects(config: &Config, client: &reqwest::Client, image_url: &str) -> Result<reqwest::Response, ImageRequestError> {
+    let mut url = Url::parse(image_url).map_err(|_| ImageRequestError::Blocked)?;
+    for _ in 0..=IMAGE_MAX_REDIRECTS {
+        let res = client
+            .get(url.clone())
+            .timeout(config.image_proxy_attempt_timeout)
+            .send()
+            .await
+            .map_err(ImageRequestError::Request)?;
+        if !res.status().is_redirection() {
+            return Ok(res);
+        }
+        let location = res.headers().get(reqwest::header::LOCATION).and_then(|v| v.to_str().ok());
+        let Some(next) = location.and_then(|location| url.join(location).ok()) else { return Ok(res); };
+        if check_image_url_allowed(config, &next).await.is_err() {
+            warn!("🚫 [IMG PROXY] Blocked redirect {} -> {}", url, next);
+            return Err(ImageRequestError::Blocked);
+        }
+        url = next;
+    }
+    warn!("🚫 [IMG PROXY] Too many redirects: {}", image_url);
+    Err(ImageRequestError::Blocked)
+}
+
+/// 从源站抓取图片 (使用连接池 + 重试逻辑)，成功后异步写入缓存。
+async fn fetch_and_cache_image(state: &ServerState, image_url: &str, cache_kind: CacheKind) -> ImageFetchResult {
     let mut response_bytes = None;
     let mut response_content_type = HeaderValue::from_static("application/octet-stream");
     // Track the last status code if we received one, otherwise None
     let mut last_error_status: Option<reqwest::StatusCode> = None;
 
-    for attempt in 1..=10 {
+    let max_attempts = state.config.image_proxy_max_attempts.max(1);
+    let max_bytes = state.config.max_image_bytes;
+
+    for attempt in 1..=max_attempts {
         // 从连接池获取 Client 和 索引
         let (client_idx, client) = state.image_proxy_pool.get_client().await;
         
-        info!("🔄 [IMG PROXY] Attempt {}/10 | Client #{} | Requesting: {}", attempt, client_idx, image_url);
+        info!("🔄 [IMG PROXY] Attempt {}/{} | Client #{} | Requesting: {}", attempt, max_attempts, client_idx, image_url);
 
-        match client.get(&image_url).send().await {
+        match send_following_redirects(&state.config, &client, image_url).await {
+            // 重定向指向不允许的地址：换客户端重试也一样，直接拒绝
+            Err(ImageRequestError::Blocked) => return Err(reqwest::StatusCode::FORBIDDEN),
             Ok(res) => {
                 let status = res.status();
-                info!("📩 [IMG PROXY] Attempt {}/10 | Client #{} | Response Status: {} | URL: {}", attempt, client_idx, status, image_url);
+                info!("📩 [IMG PROXY] Attempt {}/{} | Client #{} | Response Status: {} | URL: {}", attempt, max_attempts, client_idx, status, image_url);
 
                 if status.is_success() {
+                    // Content-Length 已声明超限：不读 body，直接拒绝 (换客户端重试也没有意义)
+                    if res.content_length().is_some_and(|len| len > max_bytes as u64) {
+                        warn!("🚫 [IMG PROXY] Content-Length {:?} exceeds limit {} bytes: {}", res.content_length(), max_bytes, image_url);
+                        return Err(reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+                    }
                     response_content_type = res
                         .headers()
                         .get(reqwest::header::CONTENT_TYPE)
                         .cloned()
                         .unwrap_or_else(|| HeaderValue::from_static("application/octet-stream"));
                     
-                    match res.bytes().await {
-                        Ok(bytes) => {
+                    match read_body_capped(res, max_bytes).await {
+                        // Content-Length 缺失或不实，实际读取时超限
+                        Ok(None) => {
+                            warn!("🚫 [IMG PROXY] Body exceeds limit {} bytes while streaming: {}", max_bytes, image_url);
+                            return Err(reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+                        }
+                        Ok(Some(bytes)) => {
                             info!("✅ [IMG PROXY] Success | Size: {} bytes | URL: {}", bytes.len(), image_url);
                             response_bytes = Some(bytes);
                             break; // 成功获取，退出重试循环
@@ -88,50 +666,43 @@ pub async fn image_proxy_handler(
                         Err(e) => format!("<Failed to read body: {}>", e),
                     };
 
-                    warn!("⚠️ [IMG PROXY] Upstream {}: {}. Body: {}. Attempt {}/10", status, image_url, body_snippet, attempt);
+                    warn!("⚠️ [IMG PROXY] Upstream {}: {}. Body: {}. Attempt {}/{}", status, image_url, body_snippet, attempt, max_attempts);
 
-                    // 策略：5xx 错误可能是代理节点问题，需要回收；404 可能是源站问题，不回收但记录警告
-                    if status.as_u16() >= 500 {
+                    // 策略：5xx 错误可能是代理节点问题，需要回收后重试；
+                    // 4xx (如 404) 是源站确实没有该资源，重试无意义，直接放弃 (429 限流除外)
+                    if status.is_server_error() {
                         state.image_proxy_pool.recycle_client(client_idx).await;
+                    } else if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                        break;
                     }
                 }
             },
-            Err(e) => {
+            Err(ImageRequestError::Request(e)) => {
                 // 连接层面的错误（如超时、握手失败），必须回收连接
                 // Use {:?} to get more details about the error
-                warn!("❌ [IMG PROXY] Request failed: {:?}. Recycling client #{}. Attempt {}/10", e, client_idx, attempt);
+                warn!("❌ [IMG PROXY] Request failed: {:?}. Recycling client #{}. Attempt {}/{}", e, client_idx, attempt, max_attempts);
                 state.image_proxy_pool.recycle_client(client_idx).await;
             }
         }
     }
 
-    // 4. 处理结果
     match response_bytes {
         Some(image_buffer) => {
             // 异步保存到缓存，避免阻塞响应
-            let cache_config = config.clone();
-            let cache_image_url = image_url.clone();
+            let cache_config = state.config.clone();
+            let cache_image_url = image_url.to_string();
             let cache_content_type = response_content_type.clone();
             let cache_image_buffer = image_buffer.clone();
             
             tokio::spawn(async move {
                 if let Err(e) =
-                    cache::save_to_cache(&cache_image_url, &cache_content_type, &cache_image_buffer, &cache_config).await
+                    cache::save_to_cache(&cache_image_url, &cache_content_type, &cache_image_buffer, cache_kind, &cache_config).await
                 {
                     warn!("[CACHE ASYNC] Failed to save to cache: {}", e);
                 }
             });
 
-            // 返回响应
-            let mut headers = HeaderMap::new();
-            headers.insert(http::header::CONTENT_TYPE, response_content_type);
-            headers.insert(
-                http::header::CACHE_CONTROL,
-                HeaderValue::from_static("public, max-age=86400"),
-            );
-            headers.insert(http::header::CONTENT_LENGTH, image_buffer.len().into());
-
-            Ok((headers, image_buffer).into_response())
+            Ok((response_content_type, image_buffer))
         },
         None => {
             let status_msg = last_error_status
@@ -139,7 +710,82 @@ pub async fn image_proxy_handler(
                 .unwrap_or_else(|| "No Response".to_string());
 
             error!("🔥 [IMG PROXY] Failed to fetch image after retries: {}. Last Status: {}", image_url, status_msg);
-            Err(AppError::UpstreamError(last_error_status.unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR)))
+            Err(last_error_status.unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR))
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_time_eq_requires_identical_bytes() {
+        assert!(constant_time_eq(b"secret-token", b"secret-token"));
+        assert!(!constant_time_eq(b"secret-token", b"secret-tokeN"));
+        assert!(!constant_time_eq(b"secret", b"secret-token"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn host_matches_exact_and_subdomains_only() {
+        assert!(host_matches("bnbstatic.com", "bnbstatic.com"));
+        assert!(host_matches("bin.bnbstatic.com", "*.bnbstatic.com"));
+        assert!(!host_matches("evilbnbstatic.com", "bnbstatic.com"));
+        assert!(!host_matches("bnbstatic.com.evil.io", "bnbstatic.com"));
+    }
+
+    #[test]
+    fn rewrite_image_host_replaces_only_configured_hosts() {
+        let mut config = Config::new();
+        config.image_proxy_host_rewrites.insert("bin.bnbstatic.com".to_string(), "icons.example-cdn.com".to_string());
+
+        let mut url = Url::parse("https://BIN.bnbstatic.com/image/a.png?x=1").unwrap();
+        assert!(rewrite_image_host(&config, &mut url));
+        assert_eq!(url.as_str(), "https://icons.example-cdn.com/image/a.png?x=1");
+
+        let mut other = Url::parse("https://public.bnbstatic.com/a.png").unwrap();
+        assert!(!rewrite_image_host(&config, &mut other));
+        assert_eq!(other.host_str(), Some("public.bnbstatic.com"));
+    }
+
+    /// mock 源站：/img 302 到同机回环地址上的 /secret，记录 /secret 是否被访问
+    async fn spawn_redirecting_upstream(secret_hit: Arc<std::sync::atomic::AtomicBool>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let secret_url = format!("{}/secret", base);
+        let app = axum::Router::new()
+            .route("/img", axum::routing::get(move || {
+                let location = secret_url.clone();
+                async move { (http::StatusCode::FOUND, [(http::header::LOCATION, location)]) }
+            }))
+            .route("/secret", axum::routing::get(move || {
+                secret_hit.store(true, std::sync::atomic::Ordering::SeqCst);
+                async { "metadata" }
+            }));
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn redirect_to_loopback_is_not_followed() {
+        let secret_hit = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let base = spawn_redirecting_upstream(secret_hit.clone()).await;
+        let restrictions = crate::client_pool::ClientRestrictions { no_redirects: true, public_only: false };
+        let pool = crate::client_pool::ClientPool::with_restrictions(1, None, "TEST_IMG".to_string(), std::time::Duration::ZERO, restrictions).await;
+        let (_, client) = pool.get_client().await;
+
+        // 图片池客户端不自动跟随重定向
+        let raw = client.get(format!("{}/img", base)).send().await.unwrap();
+        assert_eq!(raw.status(), reqwest::StatusCode::FOUND);
+
+        // 手动跟随时对回环目标做 SSRF 检查并拒绝
+        let config = Config::new();
+        assert!(config.image_proxy_block_private_ips);
+        let result = send_following_redirects(&config, &client, &format!("{}/img", base)).await;
+        assert!(matches!(result, Err(ImageRequestError::Blocked)));
+        assert!(!secret_hit.load(std::sync::atomic::Ordering::SeqCst));
+    }
+}
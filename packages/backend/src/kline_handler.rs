@@ -2,41 +2,49 @@
 
 use crate::{
     client_pool::ClientPool,
-    types::{HistoricalDataWrapper, KlineHistoryResponse, KlineSubscribePayload, KlineTick, LiquidityPoint},
+    config::Config,
+    error::KlineFetchError,
+    kline_store::SqliteKlineStore,
+    state::WorkerEvent,
+    types::{CompressedPayload, HistoricalDataWrapper, KlineBroadcastData, KlineHistoryResponse, KlineSubscribePayload, KlineTick, LiquidityPoint, PriceResponse, TokenMetadata, WorkerEventRecord},
+    socket_handlers::{chain_to_pool_id, normalize_address},
     ServerState,
 };
 use anyhow::{Context, Result};
 use chrono::{DateTime, Duration, TimeZone, Utc};
+use flate2::{write::GzEncoder, Compression};
 use serde_json::Value;
 use socketioxide::extract::{Data, SocketRef};
 use sqlx::{
     sqlite::{SqlitePool, SqliteRow},
     Row,
 };
+use dashmap::{mapref::entry::Entry, DashMap};
 use std::collections::HashMap;
+use std::io::Write;
 use std::time::Instant;
 use tracing::{error, info, warn};
 
-const API_URL_TEMPLATE: &str = "https://dquery.sintral.io/u-kline/v1/k-line/candles?address={address}&interval={interval}&limit={limit}&platform={platform}";
 /// 币安API单次最多返回500根K线，也是我们缓存的上限
 const MAX_KLINES: i64 = 500;
+const KLINE_FETCH_ATTEMPTS: u32 = 3;
+/// 限流时的基础退避 (逐次翻倍)，上游给了 Retry-After 时以其为准
+const KLINE_RATE_LIMIT_BACKOFF_MS: u64 = 500;
+const KLINE_ERROR_SNIPPET_CHARS: usize = 200;
+/// 超过慢查询阈值 (Config::slow_query_threshold) 时打印警告，返回是否为慢查询
+pub(crate) fn warn_if_slow(op: &str, key: &str, start: Instant, threshold: std::time::Duration) -> bool {
+    let elapsed = start.elapsed();
+    if elapsed > threshold {
+        warn!("⏳ [DB SLOW: {}] key={}, {}ms", op, key, elapsed.as_millis());
+        true
+    } else {
+        false
+    }
+}
 
 // ✨ 确保是 public
 pub async fn init_db(pool: &SqlitePool) -> Result<()> {
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS klines (
-            primary_key TEXT NOT NULL,
-            time INTEGER NOT NULL,
-            open REAL NOT NULL,
-            high REAL NOT NULL,
-            low REAL NOT NULL,
-            close REAL NOT NULL,
-            volume REAL NOT NULL,
-            PRIMARY KEY (primary_key, time)
-        )",
-    )
-    .execute(pool)
-    .await?;
+    SqliteKlineStore::create_schema(pool).await?;
     info!("🗃️ 'klines' table is ready.");
 
     sqlx::query(
@@ -62,6 +70,60 @@ pub async fn init_db(pool: &SqlitePool) -> Result<()> {
     .await?;
     info!("🗃️ 'blacklist' table is ready.");
 
+    // 白名单表 (手动维护，不做 TTL 清理)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS whitelist (
+            contract_address TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL
+        )"
+    )
+    .execute(pool)
+    .await?;
+    info!("🗃️ 'whitelist' table is ready.");
+
+    // ✨ 新增：一次性报警记录 (重启后不再重复触发)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS fired_alerts (
+            alert_key TEXT PRIMARY KEY,
+            fired_at INTEGER NOT NULL
+        )"
+    )
+    .execute(pool)
+    .await?;
+    info!("🗃️ 'fired_alerts' table is ready.");
+
+    // ✨ 新增：代币元数据 (地址统一小写，与 token_symbols 一致)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS tokens (
+            address TEXT PRIMARY KEY,
+            chain TEXT NOT NULL,
+            symbol TEXT NOT NULL,
+            decimals INTEGER,
+            icon TEXT,
+            last_seen INTEGER NOT NULL
+        )"
+    )
+    .execute(pool)
+    .await?;
+    info!("🗃️ 'tokens' table is ready.");
+
+    // Worker 生命周期审计日志 (spawned / connected / disconnected / shutdown)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS worker_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            address TEXT NOT NULL,
+            event TEXT NOT NULL,
+            detail TEXT,
+            created_at INTEGER NOT NULL
+        )"
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_worker_events_address_time ON worker_events (address, created_at)")
+        .execute(pool)
+        .await?;
+    info!("🗃️ 'worker_events' table is ready.");
+
     Ok(())
 }
 
@@ -71,12 +133,75 @@ pub async fn handle_kline_request(
     Data(payload): Data<KlineSubscribePayload>,
     state: ServerState,
 ) {
-    let _start_total = Instant::now();
-    let primary_key = get_primary_key(&payload);
+    if !crate::socket_handlers::check_interval_supported(&s, &state, "request_historical_kline", &payload.interval) {
+        return;
+    }
+
+    // 绕过缓存：直接返回上游数据，失败时通知客户端而不是回退到可能过期的 DB 数据。
+    // 上游数据已是完整结果，与补齐流程一样推送 completed 并初始化 / 校准房间 K 线
+    if payload.no_cache {
+        match fetch_uncached_kline_history(&state, &payload).await {
+            Ok(resp) => {
+                emit_history(&s, &state, "historical_kline_initial", &resp);
+                finish_kline_history(&state, &payload, &resp, Some(&s)).await;
+            }
+            Err(e) => {
+                warn!("⚠️ [KLINE NO-CACHE] Upstream fetch failed for {}: {:#}", get_primary_key(&payload), e);
+                s.emit(
+                    "subscription_error",
+                    &serde_json::json!({ "action": "request_historical_kline", "reason": "upstream_failed" }),
+                )
+                .ok();
+            }
+        }
+        return;
+    }
+
+    // 1. DB Query + Hydration
+    let initial_response = load_cached_kline_history(&state, &payload).await;
+    emit_history(&s, &state, "historical_kline_initial", &initial_response);
+
+    // 2. Fetch missing
+    spawn_kline_sync(state, payload, Some(s));
+}
+
+/// 推送历史响应；启用压缩且 JSON 超过阈值时改为 gzip 二进制负载 (CompressedPayload)
+fn emit_history(s: &SocketRef, state: &ServerState, event: &str, resp: &KlineHistoryResponse) {
+    if state.config.compress_history_payloads {
+        match gzip_json(resp, state.config.history_compression_min_bytes) {
+            Ok(Some(payload)) => {
+                info!("🗜️ [GZIP] {} {} -> {} bytes", event, payload.original_size, payload.data.len());
+                s.emit(event, &payload).ok();
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => warn!("⚠️ [GZIP] Failed to compress {}: {}. Sending plain JSON.", event, e),
+        }
+    }
+    s.emit(event, resp).ok();
+}
+
+/// 序列化为 JSON 并 gzip；不足 min_bytes 时返回 None (压缩收益不抵开销)
+fn gzip_json<T: serde::Serialize>(value: &T, min_bytes: usize) -> Result<Option<CompressedPayload>> {
+    let json = serde_json::to_vec(value)?;
+    if json.len() < min_bytes {
+        return Ok(None);
+    }
+    let mut encoder = GzEncoder::new(Vec::with_capacity(json.len() / 4), Compression::fast());
+    encoder.write_all(&json)?;
+    Ok(Some(CompressedPayload {
+        encoding: "gzip",
+        original_size: json.len(),
+        data: encoder.finish()?.into(),
+    }))
+}
+
+/// 读取 DB 缓存的 K 线 (补齐缺口) 与流动性历史，组装为历史响应；socket 与 HTTP 接口共用
+pub async fn load_cached_kline_history(state: &ServerState, payload: &KlineSubscribePayload) -> KlineHistoryResponse {
+    let primary_key = get_primary_key(payload);
 
-    // 1. DB Query
     let db_start = Instant::now();
-    let initial_data = match get_klines_from_db(&state.db_pool, &primary_key).await {
+    let initial_data = match state.kline_store.recent(&primary_key, MAX_KLINES).await {
         Ok(data) => {
             if !data.is_empty() {
                 info!("💾 [DB HIT] {} records ({}ms)", data.len(), db_start.elapsed().as_millis());
@@ -91,26 +216,121 @@ pub async fn handle_kline_request(
         }
     };
 
+    // 补齐前计算：补出的平直 K 线不代表真实成交
+    let stale_ms = state.config.kline_stale_threshold.and_then(|threshold| {
+        kline_staleness_ms(&initial_data, &payload.interval, threshold, Utc::now().timestamp_millis())
+    });
+
     // ✨ HYDRATION: Fill gaps before sending
-    let hydrated_data = fill_kline_gaps(initial_data, &payload.interval, MAX_KLINES as usize);
+    let hydrated_data = hydrate_klines(initial_data, payload, &state.config);
 
     // 查询流动性历史
-    let liquidity_history = query_liquidity_history(&state.db_pool, &payload.address)
+    let liquidity_history = query_liquidity_history(&state.read_pool, &payload.address)
         .await
         .ok(); // 失败时返回 None，不阻塞主流程
+    let (symbol, icon) = token_header(state, payload).await;
 
-    let initial_response = KlineHistoryResponse {
+    KlineHistoryResponse {
         address: payload.address.clone(),
         chain: payload.chain.clone(),
         interval: payload.interval.clone(),
         data: hydrated_data,
         liquidity_history,
-    };
-    s.emit("historical_kline_initial", &initial_response).ok();
+        decimals: get_token_decimals(state, &payload.address),
+        symbol,
+        icon,
+        stale_ms,
+    }
+}
 
-    // 2. Fetch missing
+/// 最新 K 线收盘距今超过阈值时返回其毫秒数 (代币已沉寂，首屏图表可能过时)；无数据时不提示
+fn kline_staleness_ms(klines: &[KlineTick], interval: &str, threshold: std::time::Duration, now_ms: i64) -> Option<i64> {
+    let last = klines.last()?;
+    let close_ms = last.time.timestamp_millis() + interval_to_ms(interval);
+    let age_ms = now_ms - close_ms;
+    (age_ms > i64::try_from(threshold.as_millis()).unwrap_or(i64::MAX)).then_some(age_ms)
+}
+
+/// 图表标题所需的 symbol / icon：symbol 优先取房间中已解析的值 (跳过 "0xabcd..." 占位)，
+/// 其次内存映射与 tokens 表；icon 来自 tokens 表
+async fn token_header(state: &ServerState, payload: &KlineSubscribePayload) -> (Option<String>, Option<String>) {
+    let addr_lower = payload.address.to_lowercase();
+    let room_symbol = chain_to_pool_id(&payload.chain).and_then(|pool_id| {
+        let room_key = format!("kl@{}@{}@{}", pool_id, normalize_address(pool_id, &payload.address), payload.interval);
+        state.app_state.get(&room_key).map(|room| room.symbol.clone())
+    });
+    let token = get_token(&state.read_pool, &addr_lower).await.ok().flatten();
+    let symbol = room_symbol
+        .filter(|s| !s.ends_with("..."))
+        .or_else(|| state.token_symbols.get(&addr_lower).map(|s| s.value().clone()))
+        .or_else(|| token.as_ref().map(|t| t.symbol.clone()));
+    (symbol, token.and_then(|t| t.icon))
+}
+
+/// 不读 DB 缓存，直接向上游拉取完整历史 (no_cache)；结果照常写入 DB 并刷新同步时间，供之后的缓存读取
+async fn fetch_uncached_kline_history(state: &ServerState, payload: &KlineSubscribePayload) -> Result<KlineHistoryResponse> {
+    let _permit = state.kline_fetch_semaphore.acquire().await?;
+    let primary_key = get_primary_key(payload);
+    let mut klines = fetch_historical_data_with_pool(&state.client_pool, &state.config, payload, MAX_KLINES).await?;
+    klines.sort_by_key(|k| k.time);
+    if !klines.is_empty() {
+        state.kline_store.save(&primary_key, &klines, MAX_KLINES).await?;
+        state.kline_last_sync.insert(primary_key, Utc::now().timestamp_millis());
+    }
+
+    Ok(synced_history_response(state, payload, klines).await)
+}
+
+/// 同步后的历史响应 (K 线补齐缺口，附流动性历史与代币信息)
+async fn synced_history_response(state: &ServerState, payload: &KlineSubscribePayload, klines: Vec<KlineTick>) -> KlineHistoryResponse {
+    let liquidity_history = query_liquidity_history(&state.read_pool, &payload.address).await.ok();
+    let (symbol, icon) = token_header(state, payload).await;
+    KlineHistoryResponse {
+        address: payload.address.clone(),
+        chain: payload.chain.clone(),
+        interval: payload.interval.clone(),
+        data: hydrate_klines(klines, payload, &state.config),
+        liquidity_history,
+        decimals: get_token_decimals(state, &payload.address),
+        symbol,
+        icon,
+        stale_ms: None,
+    }
+}
+
+/// 历史同步的收尾：推送 `historical_kline_completed`，并用最新一根初始化 / 校准房间的 current_kline
+async fn finish_kline_history(state: &ServerState, payload: &KlineSubscribePayload, resp: &KlineHistoryResponse, s: Option<&SocketRef>) {
+    if let Some(s) = s {
+        emit_history(s, state, "historical_kline_completed", resp);
+    }
+
+    let Some(kline) = resp.data.last().cloned() else { return; };
+    // 房间名与订阅时一致：EVM 地址小写，Solana 保留大小写
+    let Some(pool_id) = chain_to_pool_id(&payload.chain) else { return; };
+    let room_key = format!("kl@{}@{}@{}", pool_id, normalize_address(pool_id, &payload.address), payload.interval);
+    let Some(current) = state.app_state.get(&room_key).map(|room| room.current_kline.clone()) else { return; };
+    let mut guard = current.lock().await;
+    match guard.as_mut() {
+        None => {
+            info!("✅ [KLINE INIT] Initialized current_kline for {} from history/db", room_key);
+            *guard = Some(kline);
+        }
+        // 已由首个 Tick 建立临时 K 线：以历史为准对齐 open/volume，保留实时的 close 与极值
+        Some(current) if kline.time >= current.time => {
+            info!("🔗 [KLINE RECONCILE] Reconciled current_kline for {} with history/db", room_key);
+            *current = reconcile_kline(kline, current);
+        }
+        Some(_) => {}
+    }
+}
+
+/// 后台向上游补齐缺失的 K 线；有 socket 时补齐完成后推送 `historical_kline_completed`
+pub fn spawn_kline_sync(state: ServerState, payload: KlineSubscribePayload, s: Option<SocketRef>) {
     tokio::spawn(async move {
-        let _ = complete_kline_data(&payload, &state, &primary_key, &s).await;
+        // 限制同时补齐的数量，排队的请求已经拿到了 DB 中的初始数据
+        let Ok(_permit) = state.kline_fetch_semaphore.acquire().await else { return; };
+        let primary_key = get_primary_key(&payload);
+        let _ = complete_kline_data(&payload, &state, &primary_key, s.as_ref()).await;
     });
 }
 
@@ -120,13 +340,17 @@ pub async fn handle_liquidity_request(
     state: ServerState,
 ) {
     // 使用聚合查询，根据前端请求的 interval 返回对应周期的流动性数据
-    if let Ok(history) = query_liquidity_history_aggregated(&state.db_pool, &payload.address, &payload.interval).await {
+    if let Ok(history) = query_liquidity_history_aggregated(&state.read_pool, &payload.address, &payload.interval, state.config.slow_query_threshold).await {
         let resp = KlineHistoryResponse {
             address: payload.address.clone(),
             chain: payload.chain.clone(),
             interval: payload.interval.clone(),
             data: vec![],
             liquidity_history: Some(history),
+            decimals: get_token_decimals(&state, &payload.address),
+            symbol: None,
+            icon: None,
+            stale_ms: None,
         };
         s.emit("historical_liquidity_initial", &resp).ok();
     }
@@ -138,12 +362,73 @@ async fn complete_kline_data(
     payload: &KlineSubscribePayload,
     state: &ServerState,
     primary_key: &str,
-    s: &SocketRef,
+    s: Option<&SocketRef>,
 ) -> Result<Option<usize>> {
-    let last_kline = get_last_kline_from_db(&state.db_pool, primary_key).await?;
     let interval_ms = interval_to_ms(&payload.interval);
     let now_ts = Utc::now().timestamp_millis();
-    
+
+    // ✨ 新鲜度窗口：同一品种/周期刚同步过或正在同步 (如多个客户端先后订阅同一热门币)，跳过上游拉取，直接用 DB 数据
+    let fresh_window_ms = state.config.kline_freshness_window.as_millis() as i64;
+    let new_klines = if !claim_kline_sync(&state.kline_last_sync, primary_key, now_ts, fresh_window_ms) {
+        info!("⏭️ [KLINE FRESH] {} synced within {}ms. Skipping upstream fetch.", primary_key, fresh_window_ms);
+        vec![]
+    } else {
+        match fetch_missing_klines(payload, state, primary_key, interval_ms, now_ts).await {
+            Ok(fetched) => fetched,
+            // 上游失败 (限流 / 非 JSON 等)：记录原因并释放认领以便下次重试，仍用 DB 中已有的数据回填
+            Err(e) => {
+                warn!("⚠️ [KLINE SYNC] Upstream sync failed for {}: {:#}", primary_key, e);
+                state.kline_last_sync.remove_if(primary_key, |_, claimed_at| *claimed_at == now_ts);
+                vec![]
+            }
+        }
+    };
+
+    // ✨ HYDRATION: Always read back the FULL updated set from DB and hydrate
+    let full_raw_data = state.kline_store.recent(primary_key, MAX_KLINES).await.unwrap_or_default();
+
+    if !full_raw_data.is_empty() {
+        let resp = synced_history_response(state, payload, full_raw_data).await;
+        finish_kline_history(state, payload, &resp, s).await;
+    }
+
+    Ok(Some(new_klines.len()))
+}
+
+/// 原子地认领一次上游同步：窗口内已有同步 (完成或进行中) 返回 false；否则记录本次时间并返回 true。
+/// 检查与写入在同一个 entry 锁内完成，并发订阅同一品种/周期时只有一个会拉取上游
+fn claim_kline_sync(last_sync: &DashMap<String, i64>, primary_key: &str, now_ts: i64, fresh_window_ms: i64) -> bool {
+    match last_sync.entry(primary_key.to_string()) {
+        Entry::Occupied(e) if fresh_window_ms > 0 && now_ts - *e.get() < fresh_window_ms => false,
+        Entry::Occupied(mut e) => {
+            e.insert(now_ts);
+            true
+        }
+        Entry::Vacant(v) => {
+            v.insert(now_ts);
+            true
+        }
+    }
+}
+
+/// 清理超出新鲜度窗口的同步记录 (过期记录不再影响判断，只占内存)，返回清理条数
+pub fn prune_kline_last_sync(state: &ServerState, now_ts: i64) -> usize {
+    let fresh_window_ms = state.config.kline_freshness_window.as_millis() as i64;
+    let before = state.kline_last_sync.len();
+    state.kline_last_sync.retain(|_, last_sync| now_ts - *last_sync < fresh_window_ms);
+    before.saturating_sub(state.kline_last_sync.len())
+}
+
+/// 根据 DB 中最后一根 K 线计算缺口，向上游补齐并落库，返回新拉取的 K 线
+async fn fetch_missing_klines(
+    payload: &KlineSubscribePayload,
+    state: &ServerState,
+    primary_key: &str,
+    interval_ms: i64,
+    now_ts: i64,
+) -> Result<Vec<KlineTick>> {
+    let last_kline = state.kline_store.last(primary_key).await?;
+
     // ✨ 智能计算 Limit
     let limit = match last_kline {
         Some(last) => {
@@ -153,7 +438,7 @@ async fn complete_kline_data(
             
             if missing_count > MAX_KLINES {
                 info!("⚠️ [KLINE STALE] 数据过旧 (缺少 {} 根). 清空缓存并重新拉取: {}", missing_count, primary_key);
-                clear_kline_cache(&state.db_pool, primary_key).await?;
+                state.kline_store.clear(primary_key).await?;
                 MAX_KLINES
             } else {
                 let final_limit = missing_count.max(2).min(MAX_KLINES); // 至少取 2 根以确保覆盖最新和前一根
@@ -164,64 +449,134 @@ async fn complete_kline_data(
         None => MAX_KLINES,
     };
 
-    let new_klines = fetch_historical_data_with_pool(&state.client_pool, payload, limit).await?;
+    let new_klines = fetch_historical_data_with_pool(&state.client_pool, &state.config, payload, limit).await?;
     
     // Save new raw data to DB first
     if !new_klines.is_empty() {
-        save_klines_to_db(&state.db_pool, primary_key, &new_klines).await?;
+        state.kline_store.save(primary_key, &new_klines, MAX_KLINES).await?;
     }
 
-    // ✨ HYDRATION: Always read back the FULL updated set from DB and hydrate
-    let full_raw_data = get_klines_from_db(&state.db_pool, primary_key).await.unwrap_or_default();
-    
-    if !full_raw_data.is_empty() {
-        let hydrated_data = fill_kline_gaps(full_raw_data, &payload.interval, MAX_KLINES as usize);
+    Ok(new_klines)
+}
 
-        // 查询流动性历史
-        let liquidity_history = query_liquidity_history(&state.db_pool, &payload.address)
-            .await
-            .ok();
+/// 历史 K 线与实时 K 线对齐：新周期直接采用历史，同一周期合并实时价格
+fn reconcile_kline(history: KlineTick, live: &KlineTick) -> KlineTick {
+    if history.time > live.time {
+        return history;
+    }
+    KlineTick {
+        high: history.high.max(live.high),
+        low: history.low.min(live.low),
+        close: live.close,
+        volume: history.volume.max(live.volume),
+        ..history
+    }
+}
 
-        let resp = KlineHistoryResponse {
-            address: payload.address.clone(),
-            chain: payload.chain.clone(),
-            interval: payload.interval.clone(),
-            data: hydrated_data.clone(),
-            liquidity_history,
-        };
-        s.emit("historical_kline_completed", &resp).ok();
-        
-        let latest_candidate = hydrated_data.last().cloned();
-        
-        if let Some(kline) = latest_candidate {
-             let chain_lower = payload.chain.to_lowercase();
-             let pool_id = match chain_lower.as_str() {
-                 "bsc" => 14, "sol" | "solana" => 16, "base" => 199, _ => 0,
-             };
-     
-             if pool_id > 0 {
-                 let room_key = format!("kl@{}@{}@{}", pool_id, payload.address.to_lowercase(), payload.interval);
-                 if let Some(room) = state.app_state.get(&room_key) {
-                     let mut guard = room.current_kline.lock().await;
-                     if guard.is_none() {
-                         info!("✅ [KLINE INIT] Initialized current_kline for {} from history/db", room_key);
-                         *guard = Some(kline);
-                     }
-                 }
-             }
+/// 定期校准所有有订阅者的 K 线房间：逐个向上游取最新 2 根 K 线，与 current_kline 偏差超过容差时以上游为准修正并推送。
+/// 返回被修正的房间数
+pub async fn reconcile_live_klines(state: &ServerState) -> usize {
+    let rooms: Vec<String> = state
+        .app_state
+        .iter()
+        .filter(|entry| !entry.value().clients.is_empty())
+        .map(|entry| entry.key().clone())
+        .collect();
+
+    let mut corrected = 0;
+    for room_key in rooms {
+        // 与历史补齐共用并发限制，避免校准挤占上游配额
+        let Ok(_permit) = state.kline_fetch_semaphore.acquire().await else { break; };
+        match reconcile_room_kline(state, &room_key).await {
+            Ok(true) => corrected += 1,
+            Ok(false) => {}
+            Err(e) => warn!("⚠️ [KLINE RECONCILE] {} failed: {:#}", room_key, e),
         }
     }
+    corrected
+}
 
-    Ok(Some(new_klines.len()))
+/// 校准单个房间 (room_key: kl@poolId@address@interval)，返回是否做了修正
+async fn reconcile_room_kline(state: &ServerState, room_key: &str) -> Result<bool> {
+    let parts: Vec<&str> = room_key.split('@').collect();
+    let [_, pool_id, address, interval] = parts[..] else { return Ok(false); };
+    let Some(chain) = pool_id.parse().ok().and_then(crate::socket_handlers::pool_id_to_chain) else { return Ok(false); };
+    let payload = KlineSubscribePayload {
+        address: address.to_string(),
+        chain: chain.to_string(),
+        interval: interval.to_string(),
+        fill_gaps: false,
+        no_cache: false,
+    };
+
+    let fetched = fetch_historical_data_with_pool(&state.client_pool, &state.config, &payload, 2).await?;
+    let Some(current) = state.app_state.get(room_key).map(|room| room.current_kline.clone()) else { return Ok(false); };
+
+    let corrected = {
+        let mut guard = current.lock().await;
+        let Some(live) = guard.as_mut() else { return Ok(false); };
+        // 取与实时 K 线同一周期的权威 K 线 (上游可能已滚动到下一根)
+        let Some(authoritative) = fetched.into_iter().find(|k| k.time == live.time) else { return Ok(false); };
+        let deviation = kline_deviation(&authoritative, live);
+        if deviation <= state.config.kline_reconcile_tolerance {
+            return Ok(false);
+        }
+        info!("🩹 [KLINE RECONCILE] {} drifted {:.2}% from upstream, correcting", room_key, deviation * 100.0);
+        *live = authoritative;
+        live.clone()
+    };
+
+    let bca = KlineBroadcastData { room: room_key.to_string(), data: corrected };
+    if let Some(ns) = state.io.of(state.config.market_namespace.as_str()) {
+        ns.to(room_key.to_string()).emit("kline_update", &bca).await.ok();
+    }
+    Ok(true)
 }
 
-async fn clear_kline_cache(pool: &SqlitePool, key: &str) -> Result<()> {
-    sqlx::query("DELETE FROM klines WHERE primary_key = ?").bind(key).execute(pool).await?;
-    Ok(())
+/// 开高低收中最大的相对偏差 (以权威值为基准)
+fn kline_deviation(authoritative: &KlineTick, live: &KlineTick) -> f64 {
+    [
+        (authoritative.open, live.open),
+        (authoritative.high, live.high),
+        (authoritative.low, live.low),
+        (authoritative.close, live.close),
+    ]
+    .into_iter()
+    .map(|(expected, actual)| (actual - expected).abs() / expected.abs().max(f64::EPSILON))
+    .fold(0.0, f64::max)
+}
+
+/// 查询当前价格：优先实时房间的 current_kline，其次 DB 最新 K 线，最后实时向上游拉取
+pub async fn get_current_price(state: &ServerState, payload: &KlineSubscribePayload, pool_id: i64, address: &str) -> Result<Option<PriceResponse>> {
+    let room_key = format!("kl@{}@{}@{}", pool_id, address, payload.interval);
+    // 先克隆出 Arc，避免持有 DashMap 引用跨 await
+    let live_kline = state.app_state.get(&room_key).map(|room| room.current_kline.clone());
+    if let Some(current) = live_kline {
+        if let Some(kline) = current.lock().await.as_ref() {
+            return Ok(Some(PriceResponse { price: kline.close, time: kline.time.timestamp_millis(), source: "live" }));
+        }
+    }
+
+    if let Some(kline) = state.kline_store.last(&get_primary_key(payload)).await? {
+        return Ok(Some(PriceResponse { price: kline.close, time: kline.time.timestamp_millis(), source: "db" }));
+    }
+
+    let fetched = fetch_historical_data_with_pool(&state.client_pool, &state.config, payload, 1).await?;
+    Ok(fetched.last().map(|kline| PriceResponse {
+        price: kline.close,
+        time: kline.time.timestamp_millis(),
+        source: "upstream",
+    }))
+}
+
+/// 清除某个 Token 所有链/周期的 K 线缓存 (primary_key 格式: address@chain@interval)
+pub async fn clear_token_kline_cache(state: &ServerState, address: &str) -> Result<u64> {
+    state.kline_store.clear_token(address).await
 }
 
 async fn fetch_historical_data_with_pool(
     pool: &ClientPool,
+    config: &Config,
     payload: &KlineSubscribePayload,
     limit: i64,
 ) -> Result<Vec<KlineTick>> {
@@ -230,7 +585,8 @@ async fn fetch_historical_data_with_pool(
     // Normalize platform name (e.g. SOL -> solana)
     let platform = if payload.chain.eq_ignore_ascii_case("SOL") { "solana" } else { &payload.chain };
 
-    let url = API_URL_TEMPLATE
+    let url = config
+        .kline_api_url_template_for(&payload.chain)
         .replace("{address}", &payload.address)
         .replace("{platform}", platform)
         .replace("{interval}", &formatted_interval)
@@ -240,82 +596,88 @@ async fn fetch_historical_data_with_pool(
 
     let interval_label = payload.interval.clone();
 
-    // 简单的重试逻辑
-    for _ in 0..2 {
+    // 重试：请求失败 / 非 2xx 换一个出口 (回收客户端)；429 额外退避；返回非 JSON 换客户端重试
+    let mut last_error = None;
+    for attempt in 0..KLINE_FETCH_ATTEMPTS {
         let (idx, client) = pool.get_client().await;
-        if let Ok(res) = client.get(&url).send().await {
-            if res.status().is_success() {
-                if let Ok(text) = res.text().await {
-                    if let Ok(wrapper) = serde_json::from_str::<HistoricalDataWrapper>(&text) {
-                         return parse_api_data(&wrapper.data, &interval_label);
-                    }
+        let error = match client.get(&url).send().await {
+            Ok(res) => {
+                let status = res.status();
+                let retry_after = res
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(std::time::Duration::from_secs);
+                match res.text().await {
+                    Ok(text) if status.is_success() => match serde_json::from_str::<HistoricalDataWrapper>(&text) {
+                        Ok(wrapper) => return parse_api_data(&wrapper.data, &interval_label),
+                        Err(e) => KlineFetchError::BadData { status, error: e.to_string(), snippet: body_snippet(&text) },
+                    },
+                    Ok(_) if status == reqwest::StatusCode::TOO_MANY_REQUESTS => KlineFetchError::RateLimited { retry_after },
+                    Ok(text) => KlineFetchError::Status { status, snippet: body_snippet(&text) },
+                    Err(e) => KlineFetchError::Request(e),
                 }
-            } else {
+            }
+            Err(e) => KlineFetchError::Request(e),
+        };
+
+        warn!("⚠️ [KLINE FETCH] Attempt {}/{} failed for {}: {}", attempt + 1, KLINE_FETCH_ATTEMPTS, url, error);
+        let is_last = attempt + 1 == KLINE_FETCH_ATTEMPTS;
+        match &error {
+            KlineFetchError::RateLimited { retry_after } => {
+                pool.recycle_client(idx).await;
+                if !is_last {
+                    let backoff = retry_after.unwrap_or_else(|| {
+                        std::time::Duration::from_millis(KLINE_RATE_LIMIT_BACKOFF_MS << attempt)
+                    });
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+            // 数据异常：连接本身是好的，直接换下一个客户端重试
+            KlineFetchError::BadData { .. } => {}
+            KlineFetchError::Status { .. } | KlineFetchError::Request(_) => {
                 pool.recycle_client(idx).await;
             }
-        } else {
-            pool.recycle_client(idx).await;
         }
+        last_error = Some(error);
     }
-    Ok(vec![])
+    Err(last_error.expect("KLINE_FETCH_ATTEMPTS > 0").into())
 }
 
-// ... DB Helpers ...
-/// 获取最新的500根K线，按时间升序返回（前端需要升序渲染）
-async fn get_klines_from_db(pool: &SqlitePool, key: &str) -> Result<Vec<KlineTick>> {
-    // 使用子查询：先倒序取最新500根，再外层正序排列
-    sqlx::query_as::<_, KlineTick>(
-        "SELECT time, open, high, low, close, volume FROM (
-            SELECT * FROM klines WHERE primary_key = ? ORDER BY time DESC LIMIT ?
-        ) ORDER BY time ASC"
-    )
-    .bind(key)
-    .bind(MAX_KLINES)
-    .fetch_all(pool)
-    .await
-    .context("获取缓存K线数据失败")
+/// 响应体片段 (折叠空白，最多 KLINE_ERROR_SNIPPET_CHARS 个字符)，用于日志排查上游返回的 HTML / 限流页面
+fn body_snippet(text: &str) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match collapsed.char_indices().nth(KLINE_ERROR_SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", &collapsed[..end]),
+        None => collapsed,
+    }
 }
-async fn get_last_kline_from_db(pool: &SqlitePool, key: &str) -> Result<Option<KlineTick>> {
-    sqlx::query_as("SELECT time, open, high, low, close, volume FROM klines WHERE primary_key = ? ORDER BY time DESC LIMIT 1")
-        .bind(key).fetch_optional(pool).await.context("DB fetch last")
+
+// ... DB Helpers ...
+/// 导出用：该品种/周期在库中的全部 K 线 (时间升序)
+pub async fn get_all_klines(state: &ServerState, payload: &KlineSubscribePayload) -> Result<Vec<KlineTick>> {
+    state.kline_store.all(&get_primary_key(payload)).await
 }
-/// 保存K线数据并自动裁剪，确保每个品种/周期最多保留500根
-async fn save_klines_to_db(pool: &SqlitePool, key: &str, klines: &[KlineTick]) -> Result<()> {
-    if klines.is_empty() { return Ok(()); }
-    
-    let start = Instant::now();
-    let mut tx = pool.begin().await.context("Failed to begin transaction for save_klines")?;
-    let tx_time = start.elapsed().as_millis();
-    
-    // 1. 插入/更新新数据
-    for k in klines {
-        sqlx::query("INSERT OR REPLACE INTO klines (primary_key, time, open, high, low, close, volume) VALUES (?, ?, ?, ?, ?, ?, ?)")
-            .bind(key).bind(k.time.timestamp()).bind(k.open).bind(k.high).bind(k.low).bind(k.close).bind(k.volume)
-            .execute(&mut *tx).await?;
-    }
-    
-    // 2. 裁剪：删除超过500根的旧数据
-    let deleted = sqlx::query(
-        "DELETE FROM klines WHERE primary_key = ? AND time NOT IN (
-            SELECT time FROM klines WHERE primary_key = ? ORDER BY time DESC LIMIT ?
-        )"
-    )
-    .bind(key)
-    .bind(key)
-    .bind(MAX_KLINES)
-    .execute(&mut *tx)
-    .await?;
-    
-    tx.commit().await.context("Failed to commit transaction for save_klines")?;
-    let total_time = start.elapsed().as_millis();
-    
-    info!("💾 [DB WRITE: KLINE] {} records saved for {}. (Total: {}ms, TxBegin: {}ms)", klines.len(), key, total_time, tx_time);
-    
-    if deleted.rows_affected() > 0 {
-        info!("🧹 [PRUNE] {} 删除了 {} 条旧K线数据", key, deleted.rows_affected());
+
+/// 列式打包：time(秒) / open / high / low / close / volume 六列依次排列，每列 n 个小端 f64，
+/// 行数 = 字节数 / 48，客户端可直接映射为 Float64Array / numpy 数组
+pub fn encode_klines_columnar(klines: &[KlineTick]) -> Vec<u8> {
+    let columns: [fn(&KlineTick) -> f64; 6] = [
+        |k| k.time.timestamp() as f64,
+        |k| k.open,
+        |k| k.high,
+        |k| k.low,
+        |k| k.close,
+        |k| k.volume,
+    ];
+    let mut buf = Vec::with_capacity(klines.len() * columns.len() * 8);
+    for column in columns {
+        for k in klines {
+            buf.extend_from_slice(&column(k).to_le_bytes());
+        }
     }
-    
-    Ok(())
+    buf
 }
 
 /// 记录流动性快照（1分钟桶）
@@ -323,6 +685,7 @@ pub async fn record_liquidity_snapshot(
     pool: &SqlitePool,
     address: &str,
     liquidity: f64,
+    slow_query_threshold: std::time::Duration,
 ) -> Result<()> {
     let start = Instant::now();
     let now_secs = Utc::now().timestamp();
@@ -339,11 +702,8 @@ pub async fn record_liquidity_snapshot(
     .execute(pool)
     .await?;
     
-    let elapsed = start.elapsed().as_millis();
-    if elapsed > 100 {
-        warn!("⏳ [DB SLOW: LIQUIDITY] addr={}, value={}, {}ms", addr_lower, liquidity, elapsed);
-    } else {
-        info!("💾 [DB WRITE: LIQUIDITY] addr={}, value={}, {}ms", addr_lower, liquidity, elapsed);
+    if !warn_if_slow("LIQUIDITY", &addr_lower, start, slow_query_threshold) {
+        info!("💾 [DB WRITE: LIQUIDITY] addr={}, value={}, {}ms", addr_lower, liquidity, start.elapsed().as_millis());
     }
     Ok(())
 }
@@ -410,6 +770,7 @@ pub async fn query_liquidity_history_aggregated(
     pool: &SqlitePool,
     address: &str,
     interval: &str, // "1m", "5m", "15m", "1h"
+    slow_query_threshold: std::time::Duration,
 ) -> Result<Vec<LiquidityPoint>> {
     let interval_secs: i64 = match interval {
         "5m" => 300,
@@ -421,9 +782,12 @@ pub async fn query_liquidity_history_aggregated(
     let addr_lower = address.to_lowercase();
     info!("📊 [LIQUIDITY QUERY] 地址={}, 周期={}, 聚合秒数={}", addr_lower, interval, interval_secs);
 
+    let start = Instant::now();
     // 如果是 1 分钟，直接调用原函数
     if interval_secs == 60 {
-        return query_liquidity_history(pool, address).await;
+        let rows = query_liquidity_history(pool, address).await?;
+        warn_if_slow("LIQUIDITY READ", &addr_lower, start, slow_query_threshold);
+        return Ok(rows);
     }
 
     // 使用窗口函数取每个聚合桶内 time_bucket 最大的记录
@@ -450,6 +814,7 @@ pub async fn query_liquidity_history_aggregated(
     .fetch_all(pool)
     .await
     .context("查询聚合流动性历史失败")?;
+    warn_if_slow("LIQUIDITY READ", &addr_lower, start, slow_query_threshold);
 
     info!("📊 [LIQUIDITY QUERY] 返回 {} 条聚合记录", rows.len());
     Ok(rows)
@@ -465,7 +830,117 @@ pub async fn prune_liquidity_history(pool: &SqlitePool) -> Result<u64> {
     Ok(result.rows_affected())
 }
 
+/// 批量写入 Worker 生命周期事件
+pub async fn insert_worker_events(pool: &SqlitePool, events: &[WorkerEvent]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    for event in events {
+        sqlx::query("INSERT INTO worker_events (address, event, detail, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&event.address)
+            .bind(event.kind.as_str())
+            .bind(&event.detail)
+            .bind(event.at)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 按时间倒序查询 Worker 生命周期事件 (address 为空表示全部代币，时间为毫秒)
+pub async fn query_worker_events(
+    pool: &SqlitePool,
+    address: Option<&str>,
+    since: i64,
+    until: i64,
+    limit: i64,
+) -> Result<Vec<WorkerEventRecord>> {
+    let rows = sqlx::query(
+        "SELECT address, event, detail, created_at FROM worker_events
+         WHERE (?1 IS NULL OR address = ?1 COLLATE NOCASE) AND created_at >= ?2 AND created_at <= ?3
+         ORDER BY created_at DESC, id DESC LIMIT ?4",
+    )
+    .bind(address)
+    .bind(since)
+    .bind(until)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| WorkerEventRecord {
+            address: row.get("address"),
+            event: row.get("event"),
+            detail: row.get("detail"),
+            created_at: row.get("created_at"),
+        })
+        .collect())
+}
+
+/// 清理早于 max_age_secs 的 Worker 生命周期事件
+pub async fn prune_worker_events(pool: &SqlitePool, max_age_secs: i64) -> Result<u64> {
+    let cutoff = Utc::now().timestamp_millis() - max_age_secs * 1000;
+    let result = sqlx::query("DELETE FROM worker_events WHERE created_at < ?")
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// 数据库文件占用 (字节) = page_count * page_size
+async fn db_size_bytes(pool: &SqlitePool) -> Result<i64> {
+    let page_count: i64 = sqlx::query_scalar("PRAGMA page_count").fetch_one(pool).await?;
+    let page_size: i64 = sqlx::query_scalar("PRAGMA page_size").fetch_one(pool).await?;
+    Ok(page_count * page_size)
+}
+
+/// 数据库维护：WAL checkpoint (TRUNCATE)，可选 VACUUM。返回 (维护前, 维护后) 的库大小 (字节)。
+/// 写入繁忙时 checkpoint/VACUUM 可能返回 SQLITE_BUSY，由调用方记录后等待下一轮
+pub async fn run_db_maintenance(pool: &SqlitePool, vacuum: bool, slow_query_threshold: std::time::Duration) -> Result<(i64, i64)> {
+    let before = db_size_bytes(pool).await?;
+
+    let start = Instant::now();
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(pool).await.context("WAL checkpoint 失败")?;
+    warn_if_slow("WAL CHECKPOINT", "-", start, slow_query_threshold);
+
+    if vacuum {
+        let start = Instant::now();
+        sqlx::query("VACUUM").execute(pool).await.context("VACUUM 失败")?;
+        warn_if_slow("VACUUM", "-", start, slow_query_threshold);
+    }
+
+    let after = db_size_bytes(pool).await?;
+    Ok((before, after))
+}
+
+/// window 内是否有写入提交：同一连接前后两次 `PRAGMA data_version` 不同，说明期间有其他连接 (本进程的
+/// K 线 / 流动性 / 代币 / Worker 事件写入，或 Market 进程) 提交过事务，用于 VACUUM 前判断写入是否繁忙
+pub async fn db_writes_active(pool: &SqlitePool, window: std::time::Duration) -> Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let before: i64 = sqlx::query_scalar("PRAGMA data_version").fetch_one(&mut *conn).await?;
+    tokio::time::sleep(window).await;
+    let after: i64 = sqlx::query_scalar("PRAGMA data_version").fetch_one(&mut *conn).await?;
+    Ok(before != after)
+}
+
 /// ✨ Gap Filling Implementation
+/// 按请求决定是否补齐缺口：关闭补齐时原样返回真实 K 线
+fn hydrate_klines(raw_data: Vec<KlineTick>, payload: &KlineSubscribePayload, config: &Config) -> Vec<KlineTick> {
+    if !payload.fill_gaps {
+        return raw_data;
+    }
+    fill_kline_gaps(raw_data, &payload.interval, gap_fill_target(config, &payload.interval))
+}
+
+/// 该周期补齐后的目标 K 线数 (按周期配置，最多 MAX_KLINES)
+fn gap_fill_target(config: &Config, interval: &str) -> usize {
+    config
+        .kline_gap_fill_counts
+        .get(interval)
+        .copied()
+        .unwrap_or(MAX_KLINES as usize)
+        .clamp(1, MAX_KLINES as usize)
+}
+
 fn fill_kline_gaps(mut raw_data: Vec<KlineTick>, interval_str: &str, target_count: usize) -> Vec<KlineTick> {
     if raw_data.is_empty() {
         return vec![];
@@ -536,26 +1011,41 @@ fn fill_kline_gaps(mut raw_data: Vec<KlineTick>, interval_str: &str, target_coun
 }
 
 // Helper functions
-fn get_primary_key(p: &KlineSubscribePayload) -> String { format!("{}@{}@{}", p.address, p.chain, p.interval) }
+fn get_token_decimals(state: &ServerState, address: &str) -> Option<i64> {
+    state.token_decimals.get(&address.to_lowercase()).map(|d| *d.value())
+}
+/// address@chain@interval；地址按房间规则规范化 (EVM 小写 / Solana 原样)，同一代币不因请求大小写不同而分成多个 key
+fn get_primary_key(p: &KlineSubscribePayload) -> String {
+    let address = match chain_to_pool_id(&p.chain) {
+        Some(pool_id) => normalize_address(pool_id, &p.address),
+        None => p.address.clone(),
+    };
+    format!("{}@{}@{}", address, p.chain, p.interval)
+}
 fn format_interval_for_api(i: &str) -> String { if let Some(v) = i.strip_suffix('m') { format!("{}min", v) } else { i.to_string() } }
-fn interval_to_ms(i: &str) -> i64 { 
+pub fn interval_to_ms(i: &str) -> i64 { 
     let v: String = i.chars().take_while(|c| c.is_ascii_digit()).collect();
     let u: String = i.chars().skip_while(|c| c.is_ascii_digit()).collect();
     let val = v.parse::<i64>().unwrap_or(0);
     match u.as_str() { "m"=>val*60000, "h"=>val*3600000, "d"=>val*86400000, _=>0 }
 }
-fn parse_api_data(data: &[Vec<Value>], _label: &str) -> Result<Vec<KlineTick>> {
+/// 解析上游 K 线数组，只返回通过校验的 K 线 (非法数据记录日志后丢弃)
+fn parse_api_data(data: &[Vec<Value>], label: &str) -> Result<Vec<KlineTick>> {
      let mut res = Vec::new();
      for d in data {
          let t = d.get(5).and_then(|v| v.as_i64()).unwrap_or(0);
-         res.push(KlineTick {
+         let kline = KlineTick {
              time: DateTime::from_timestamp(t/1000, 0).unwrap_or_default().with_timezone(&Utc),
              open: d.get(0).and_then(|v| v.as_f64()).unwrap_or(0.0),
              high: d.get(1).and_then(|v| v.as_f64()).unwrap_or(0.0),
              low: d.get(2).and_then(|v| v.as_f64()).unwrap_or(0.0),
              close: d.get(3).and_then(|v| v.as_f64()).unwrap_or(0.0),
              volume: d.get(4).and_then(|v| v.as_f64()).unwrap_or(0.0),
-         });
+         };
+         match kline.validate() {
+             Ok(()) => res.push(kline),
+             Err(reason) => warn!("⚠️ [KLINE INVALID] {} dropped candle ({}): {:?}", label, reason, d),
+         }
      }
      Ok(res)
 }
@@ -605,3 +1095,274 @@ pub async fn prune_blacklist(pool: &SqlitePool, max_age_secs: i64) -> Result<u64
         .await?;
     Ok(result.rows_affected())
 }
+
+/// 获取全量白名单
+pub async fn get_whitelist(pool: &SqlitePool) -> Result<Vec<String>> {
+    let rows = sqlx::query("SELECT contract_address FROM whitelist")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| r.get(0)).collect())
+}
+
+/// 添加到白名单
+pub async fn add_to_whitelist(pool: &SqlitePool, address: &str) -> Result<()> {
+    sqlx::query("INSERT OR REPLACE INTO whitelist (contract_address, created_at) VALUES (?, ?)")
+        .bind(address.to_lowercase())
+        .bind(Utc::now().timestamp())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// 从白名单移除
+pub async fn remove_from_whitelist(pool: &SqlitePool, address: &str) -> Result<()> {
+    sqlx::query("DELETE FROM whitelist WHERE contract_address = ?")
+        .bind(address.to_lowercase())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// 读取所有已触发的一次性报警键
+pub async fn get_fired_alerts(pool: &SqlitePool) -> Result<Vec<String>> {
+    let rows = sqlx::query("SELECT alert_key FROM fired_alerts")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| r.get(0)).collect())
+}
+
+/// 记录一次性报警已触发
+pub async fn record_fired_alert(pool: &SqlitePool, alert_key: &str, fired_at: i64) -> Result<()> {
+    sqlx::query("INSERT OR IGNORE INTO fired_alerts (alert_key, fired_at) VALUES (?, ?)")
+        .bind(alert_key)
+        .bind(fired_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+fn row_to_token(row: &SqliteRow) -> TokenMetadata {
+    TokenMetadata {
+        address: row.get("address"),
+        chain: row.get("chain"),
+        symbol: row.get("symbol"),
+        decimals: row.get("decimals"),
+        icon: row.get("icon"),
+        last_seen: row.get("last_seen"),
+    }
+}
+
+/// 批量写入代币元数据；新数据缺少 decimals / icon 时保留已有值
+pub async fn upsert_tokens(pool: &SqlitePool, tokens: &[TokenMetadata]) -> Result<()> {
+    if tokens.is_empty() {
+        return Ok(());
+    }
+    let mut tx = pool.begin().await?;
+    for token in tokens {
+        sqlx::query(
+            "INSERT INTO tokens (address, chain, symbol, decimals, icon, last_seen) VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(address) DO UPDATE SET
+                chain = excluded.chain,
+                symbol = excluded.symbol,
+                decimals = COALESCE(excluded.decimals, tokens.decimals),
+                icon = COALESCE(excluded.icon, tokens.icon),
+                last_seen = excluded.last_seen",
+        )
+        .bind(token.address.to_lowercase())
+        .bind(&token.chain)
+        .bind(&token.symbol)
+        .bind(token.decimals)
+        .bind(&token.icon)
+        .bind(token.last_seen)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 读取全部代币元数据 (启动时加载到内存映射)
+pub async fn get_all_tokens(pool: &SqlitePool) -> Result<Vec<TokenMetadata>> {
+    let rows = sqlx::query("SELECT address, chain, symbol, decimals, icon, last_seen FROM tokens")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(row_to_token).collect())
+}
+
+/// 按地址查询单个代币元数据
+pub async fn get_token(pool: &SqlitePool, address: &str) -> Result<Option<TokenMetadata>> {
+    let row = sqlx::query("SELECT address, chain, symbol, decimals, icon, last_seen FROM tokens WHERE address = ?")
+        .bind(address.to_lowercase())
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(row_to_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candle(open: f64, high: f64, low: f64, close: f64, ts_ms: i64) -> Vec<Value> {
+        vec![json!(open), json!(high), json!(low), json!(close), json!(100.0), json!(ts_ms)]
+    }
+
+    #[test]
+    fn kline_deviation_uses_largest_relative_drift() {
+        let authoritative = kline(1_700_000_040, 1.0, 1.2, 0.9, 1.1, 250.0);
+        let live = kline(1_700_000_040, 1.0, 1.5, 0.9, 1.1, 300.0);
+        assert!((kline_deviation(&authoritative, &live) - 0.25).abs() < 1e-9);
+        assert_eq!(kline_deviation(&authoritative, &authoritative), 0.0);
+    }
+
+    #[test]
+    fn body_snippet_collapses_whitespace_and_truncates() {
+        assert_eq!(body_snippet("<html>\n  <body>Too Many\tRequests</body>\n</html>"), "<html> <body>Too Many Requests</body> </html>");
+        let long = "限".repeat(KLINE_ERROR_SNIPPET_CHARS + 10);
+        assert_eq!(body_snippet(&long).chars().count(), KLINE_ERROR_SNIPPET_CHARS + 1);
+    }
+
+    #[test]
+    fn parse_api_data_keeps_valid_candles() {
+        let ts = (Utc::now().timestamp() - 600) * 1000;
+        let parsed = parse_api_data(&[candle(1.0, 1.2, 0.9, 1.1, ts)], "1m").unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].time.timestamp(), ts / 1000);
+        assert_eq!(parsed[0].close, 1.1);
+    }
+
+    #[test]
+    fn parse_api_data_drops_invalid_candles() {
+        let ts = (Utc::now().timestamp() - 600) * 1000;
+        let future_ts = (Utc::now().timestamp() + 3600) * 1000;
+        let data = vec![
+            candle(1.0, 0.8, 0.9, 1.0, ts),        // high < low
+            candle(0.0, 1.2, 0.9, 1.1, ts),        // zero open
+            candle(1.0, 1.2, -0.1, 1.1, ts),       // negative low
+            candle(1.0, 1.2, 0.9, 1.1, 0),         // zero timestamp
+            candle(1.0, 1.2, 0.9, 1.1, future_ts), // future timestamp
+            vec![json!("bad"), json!(null)],       // malformed frame
+            candle(1.0, 1.2, 0.9, 1.1, ts),
+        ];
+        let parsed = parse_api_data(&data, "1m").unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].open, 1.0);
+    }
+
+    fn kline(ts_secs: i64, open: f64, high: f64, low: f64, close: f64, volume: f64) -> KlineTick {
+        KlineTick {
+            time: DateTime::from_timestamp(ts_secs, 0).unwrap(),
+            open,
+            high,
+            low,
+            close,
+            volume,
+        }
+    }
+
+    #[test]
+    fn reconcile_kline_merges_live_price_into_same_bucket() {
+        let history = kline(1_700_000_040, 1.0, 1.2, 0.9, 1.1, 250.0);
+        let live = kline(1_700_000_040, 1.5, 1.5, 1.5, 1.5, 0.0);
+        let merged = reconcile_kline(history, &live);
+        assert_eq!(merged.open, 1.0);
+        assert_eq!(merged.high, 1.5);
+        assert_eq!(merged.low, 0.9);
+        assert_eq!(merged.close, 1.5);
+        assert_eq!(merged.volume, 250.0);
+    }
+
+    #[test]
+    fn reconcile_kline_prefers_newer_history_bucket() {
+        let history = kline(1_700_000_100, 2.0, 2.1, 1.9, 2.0, 10.0);
+        let live = kline(1_700_000_040, 1.5, 1.5, 1.5, 1.5, 0.0);
+        assert_eq!(reconcile_kline(history.clone(), &live), history);
+    }
+
+    #[test]
+    fn staleness_is_reported_only_past_threshold() {
+        let data = vec![kline(1_700_000_000, 1.0, 1.0, 1.0, 1.0, 1.0)];
+        let threshold = std::time::Duration::from_secs(3600);
+        // 1m K 线 1_700_000_060 收盘
+        let close_ms = 1_700_000_060_000;
+        assert_eq!(kline_staleness_ms(&data, "1m", threshold, close_ms + 60_000), None);
+        assert_eq!(kline_staleness_ms(&data, "1m", threshold, close_ms + 7_200_000), Some(7_200_000));
+        assert_eq!(kline_staleness_ms(&[], "1m", threshold, close_ms + 7_200_000), None);
+    }
+
+    #[test]
+    fn columnar_export_packs_each_field_contiguously() {
+        let data = vec![kline(1_700_000_000, 1.0, 1.2, 0.9, 1.1, 250.0), kline(1_700_000_060, 1.1, 1.3, 1.0, 1.2, 300.0)];
+        let buf = encode_klines_columnar(&data);
+        assert_eq!(buf.len(), 2 * 6 * 8);
+
+        let values: Vec<f64> = buf.chunks_exact(8).map(|c| f64::from_le_bytes(c.try_into().unwrap())).collect();
+        assert_eq!(&values[0..2], &[1_700_000_000.0, 1_700_000_060.0]);
+        assert_eq!(&values[2..4], &[1.0, 1.1]);
+        assert_eq!(&values[10..12], &[250.0, 300.0]);
+        assert!(encode_klines_columnar(&[]).is_empty());
+    }
+
+    #[test]
+    fn gzip_json_shrinks_full_history_response() {
+        let data: Vec<KlineTick> = (0..MAX_KLINES)
+            .map(|i| {
+                let price = 0.000123 + i as f64 * 0.0000007;
+                kline(1_700_000_000 + i * 60, price, price * 1.01, price * 0.99, price * 1.005, 1234.5 + i as f64)
+            })
+            .collect();
+        let resp = KlineHistoryResponse {
+            address: "0x1234567890abcdef1234567890abcdef12345678".to_string(),
+            chain: "bsc".to_string(),
+            interval: "1m".to_string(),
+            data,
+            liquidity_history: None,
+            decimals: Some(18),
+            symbol: Some("TEST".to_string()),
+            icon: None,
+            stale_ms: None,
+        };
+
+        let payload = gzip_json(&resp, 0).unwrap().unwrap();
+        assert_eq!(payload.original_size, serde_json::to_vec(&resp).unwrap().len());
+        // 500 根 K 线的 JSON 压缩后应不足原大小的三分之一
+        assert!(payload.data.len() * 3 < payload.original_size, "{} -> {}", payload.original_size, payload.data.len());
+
+        assert!(gzip_json(&resp, usize::MAX).unwrap().is_none());
+    }
+
+    #[test]
+    fn kline_sync_claim_is_exclusive_within_window() {
+        let last_sync = DashMap::new();
+        assert!(claim_kline_sync(&last_sync, "a@bsc@1m", 1_000, 10_000));
+        // 窗口内 (含同步进行中) 不再认领
+        assert!(!claim_kline_sync(&last_sync, "a@bsc@1m", 5_000, 10_000));
+        assert!(claim_kline_sync(&last_sync, "a@bsc@5m", 5_000, 10_000));
+        // 窗口过后重新认领；窗口为 0 时每次都认领
+        assert!(claim_kline_sync(&last_sync, "a@bsc@1m", 11_000, 10_000));
+        assert!(claim_kline_sync(&last_sync, "a@bsc@1m", 11_001, 0));
+    }
+
+    #[tokio::test]
+    async fn db_writes_active_sees_commits_from_other_connections() {
+        use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+        let db_path = std::env::temp_dir().join(format!("backend-data-version-{}.db", uuid::Uuid::new_v4()));
+        let options = SqliteConnectOptions::new().filename(&db_path).create_if_missing(true);
+        let pool = SqlitePoolOptions::new().max_connections(2).connect_with(options).await.unwrap();
+        sqlx::query("CREATE TABLE t (v INTEGER)").execute(&pool).await.unwrap();
+        let window = std::time::Duration::from_millis(200);
+
+        assert!(!db_writes_active(&pool, window).await.unwrap());
+
+        let writer = pool.clone();
+        let write = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            sqlx::query("INSERT INTO t (v) VALUES (1)").execute(&writer).await.unwrap();
+        });
+        assert!(db_writes_active(&pool, window).await.unwrap());
+        write.await.unwrap();
+
+        pool.close().await;
+        let _ = std::fs::remove_file(db_path);
+    }
+}
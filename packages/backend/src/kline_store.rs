@@ -0,0 +1,249 @@
+// packages/backend/src/kline_store.rs
+// K 线存储抽象：只覆盖 klines 表，该表的全部 SQL 集中在这里，业务代码只通过 KlineStore 访问。
+// 其余表 (流动性历史、代币元数据、黑白名单、报警记录、Worker 事件) 仍由 kline_handler 中的函数
+// 直接使用 ServerState 的 db_pool / read_pool (SqlitePool)，因此数据库整体仍绑定 SQLite；
+// 换用共享数据库 (如 Postgres) 需要先把这些表也迁移到类似的 store 之后
+use crate::kline_handler::warn_if_slow;
+use crate::types::KlineTick;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use sqlx::SqlitePool;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// K 线存储。key 为 primary_key (address@chain@interval)，返回的 K 线均按时间升序
+pub trait KlineStore: Send + Sync {
+    /// 最新的 limit 根
+    fn recent<'a>(&'a self, key: &'a str, limit: i64) -> BoxFuture<'a, Result<Vec<KlineTick>>>;
+
+    /// 全部 K 线 (导出用)
+    fn all<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<KlineTick>>>;
+
+    /// 最新一根 (读主库，刚写入的数据立即可见)
+    fn last<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<KlineTick>>>;
+
+    /// 写入 (同一时间覆盖) 并裁剪到最多 keep 根
+    fn save<'a>(&'a self, key: &'a str, klines: &'a [KlineTick], keep: i64) -> BoxFuture<'a, Result<()>>;
+
+    /// 清空单个 primary_key
+    fn clear<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// 清空某个 Token 所有链/周期的 K 线 (按 primary_key 前缀 `address@` 精确匹配，区分大小写)，返回删除行数
+    fn clear_token<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<u64>>;
+
+    /// 删除最新一根 K 线早于 max_age_secs 的所有 primary_key 的全部 K 线，返回删除行数
+    fn prune_inactive(&self, max_age_secs: i64) -> BoxFuture<'_, Result<u64>>;
+}
+
+/// SQLite 实现：写入走主连接池，历史查询走只读连接池 (未配置只读库时两者相同)
+#[derive(Clone)]
+pub struct SqliteKlineStore {
+    write: SqlitePool,
+    read: SqlitePool,
+    /// 超过该耗时的读写打印慢查询警告 (Config::slow_query_threshold)
+    slow_query_threshold: Duration,
+}
+
+impl SqliteKlineStore {
+    pub fn new(write: SqlitePool, read: SqlitePool, slow_query_threshold: Duration) -> Self {
+        Self { write, read, slow_query_threshold }
+    }
+
+    /// 建表 (由 kline_handler::init_db 调用)
+    pub async fn create_schema(pool: &SqlitePool) -> Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS klines (
+                primary_key TEXT NOT NULL,
+                time INTEGER NOT NULL,
+                open REAL NOT NULL,
+                high REAL NOT NULL,
+                low REAL NOT NULL,
+                close REAL NOT NULL,
+                volume REAL NOT NULL,
+                PRIMARY KEY (primary_key, time)
+            )",
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+impl KlineStore for SqliteKlineStore {
+    fn recent<'a>(&'a self, key: &'a str, limit: i64) -> BoxFuture<'a, Result<Vec<KlineTick>>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            // 使用子查询：先倒序取最新 limit 根，再外层正序排列
+            let rows = sqlx::query_as::<_, KlineTick>(
+                "SELECT time, open, high, low, close, volume FROM (
+                    SELECT * FROM klines WHERE primary_key = ? ORDER BY time DESC LIMIT ?
+                ) ORDER BY time ASC"
+            )
+            .bind(key)
+            .bind(limit)
+            .fetch_all(&self.read)
+            .await
+            .context("获取缓存K线数据失败")?;
+            warn_if_slow("KLINE READ", key, start, self.slow_query_threshold);
+            Ok(rows)
+        })
+    }
+
+    fn all<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<KlineTick>>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let rows = sqlx::query_as::<_, KlineTick>(
+                "SELECT time, open, high, low, close, volume FROM klines WHERE primary_key = ? ORDER BY time ASC"
+            )
+            .bind(key)
+            .fetch_all(&self.read)
+            .await
+            .context("导出K线数据失败")?;
+            warn_if_slow("KLINE EXPORT", key, start, self.slow_query_threshold);
+            Ok(rows)
+        })
+    }
+
+    fn last<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<KlineTick>>> {
+        Box::pin(async move {
+            sqlx::query_as("SELECT time, open, high, low, close, volume FROM klines WHERE primary_key = ? ORDER BY time DESC LIMIT 1")
+                .bind(key).fetch_optional(&self.write).await.context("DB fetch last")
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, klines: &'a [KlineTick], keep: i64) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if klines.is_empty() { return Ok(()); }
+
+            let start = Instant::now();
+            let mut tx = self.write.begin().await.context("Failed to begin transaction for save_klines")?;
+            let tx_time = start.elapsed().as_millis();
+
+            // 1. 插入/更新新数据
+            for k in klines {
+                sqlx::query("INSERT OR REPLACE INTO klines (primary_key, time, open, high, low, close, volume) VALUES (?, ?, ?, ?, ?, ?, ?)")
+                    .bind(key).bind(k.time.timestamp()).bind(k.open).bind(k.high).bind(k.low).bind(k.close).bind(k.volume)
+                    .execute(&mut *tx).await?;
+            }
+
+            // 2. 裁剪：删除超过 keep 根的旧数据
+            let deleted = sqlx::query(
+                "DELETE FROM klines WHERE primary_key = ? AND time NOT IN (
+                    SELECT time FROM klines WHERE primary_key = ? ORDER BY time DESC LIMIT ?
+                )"
+            )
+            .bind(key)
+            .bind(key)
+            .bind(keep)
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await.context("Failed to commit transaction for save_klines")?;
+            let total_time = start.elapsed().as_millis();
+            warn_if_slow("KLINE WRITE", key, start, self.slow_query_threshold);
+
+            info!("💾 [DB WRITE: KLINE] {} records saved for {}. (Total: {}ms, TxBegin: {}ms)", klines.len(), key, total_time, tx_time);
+
+            if deleted.rows_affected() > 0 {
+                info!("🧹 [PRUNE] {} 删除了 {} 条旧K线数据", key, deleted.rows_affected());
+            }
+
+            Ok(())
+        })
+    }
+
+    fn clear<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("DELETE FROM klines WHERE primary_key = ?").bind(key).execute(&self.write).await?;
+            Ok(())
+        })
+    }
+
+    fn clear_token<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            // 前缀范围 [address@, addressA) ('A' 紧随 '@')：不受 LIKE 通配符影响，且可走 primary_key 索引
+            let result = sqlx::query("DELETE FROM klines WHERE primary_key >= ? AND primary_key < ?")
+                .bind(format!("{}@", address))
+                .bind(format!("{}A", address))
+                .execute(&self.write)
+                .await?;
+            Ok(result.rows_affected())
+        })
+    }
+
+    fn prune_inactive(&self, max_age_secs: i64) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move {
+            let cutoff = Utc::now().timestamp() - max_age_secs;
+            let result = sqlx::query(
+                "DELETE FROM klines WHERE primary_key IN (
+                    SELECT primary_key FROM klines GROUP BY primary_key HAVING MAX(time) < ?
+                )",
+            )
+            .bind(cutoff)
+            .execute(&self.write)
+            .await?;
+            Ok(result.rows_affected())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    async fn memory_store() -> SqliteKlineStore {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        SqliteKlineStore::create_schema(&pool).await.unwrap();
+        SqliteKlineStore::new(pool.clone(), pool, Duration::from_millis(100))
+    }
+
+    fn kline_at(ts_secs: i64) -> KlineTick {
+        KlineTick { time: DateTime::from_timestamp(ts_secs, 0).unwrap(), open: 1.0, high: 1.0, low: 1.0, close: 1.0, volume: 1.0 }
+    }
+
+    #[tokio::test]
+    async fn save_trims_to_keep_and_reads_ascending() {
+        let store = memory_store().await;
+        let klines: Vec<_> = (0..5).map(|i| kline_at(1_700_000_000 + i * 60)).collect();
+        store.save("k", &klines, 3).await.unwrap();
+
+        let times: Vec<_> = store.all("k").await.unwrap().iter().map(|k| k.time.timestamp()).collect();
+        assert_eq!(times, vec![1_700_000_120, 1_700_000_180, 1_700_000_240]);
+        assert_eq!(store.recent("k", 2).await.unwrap()[0].time.timestamp(), 1_700_000_180);
+        assert_eq!(store.last("k").await.unwrap().unwrap().time.timestamp(), 1_700_000_240);
+    }
+
+    #[tokio::test]
+    async fn prune_inactive_drops_only_abandoned_keys() {
+        let store = memory_store().await;
+        let now = Utc::now().timestamp();
+        let day = 24 * 3600;
+        // dead: 两根都在 10 天前；alive: 旧 K 线也保留，因为最新一根是刚刚
+        store.save("dead", &[kline_at(now - 11 * day), kline_at(now - 10 * day)], 500).await.unwrap();
+        store.save("alive", &[kline_at(now - 10 * day), kline_at(now)], 500).await.unwrap();
+
+        assert_eq!(store.prune_inactive(7 * day).await.unwrap(), 2);
+        assert_eq!(store.all("alive").await.unwrap().len(), 2);
+        assert!(store.all("dead").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn clear_token_matches_exact_address_prefix() {
+        let store = memory_store().await;
+        for key in ["ab_c@bsc@1m", "ab_c@bsc@5m", "abxc@bsc@1m", "AB_C@bsc@1m", "ab_cd@bsc@1m"] {
+            store.save(key, &[kline_at(1_700_000_000)], 500).await.unwrap();
+        }
+
+        assert_eq!(store.clear_token("ab_c").await.unwrap(), 2);
+        // '_' 不是通配符，大小写不同或更长的地址不受影响
+        for key in ["abxc@bsc@1m", "AB_C@bsc@1m", "ab_cd@bsc@1m"] {
+            assert_eq!(store.all(key).await.unwrap().len(), 1, "{}", key);
+        }
+    }
+}
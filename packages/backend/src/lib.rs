@@ -7,50 +7,104 @@ pub mod config;
 pub mod error;
 pub mod http_handlers;
 pub mod kline_handler;
+pub mod kline_store;
+pub mod replay;
 pub mod socket_handlers;
 pub mod state;
 pub mod token_manager;
 pub mod types;
 pub mod alert_handler;
 
-use client_pool::ClientPool;
+use client_pool::{ClientPool, ClientRestrictions};
 use config::Config;
 use dashmap::DashMap;
 use socketioxide::SocketIo;
 use sqlx::SqlitePool;
-use std::sync::Arc;
-use std::collections::VecDeque;
-use tokio::sync::Mutex;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use std::sync::{Arc, OnceLock};
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::{Mutex, RwLock, Semaphore};
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
 
 #[derive(Clone)]
 pub struct ServerState {
     pub app_state: state::AppState,
     pub room_index: state::RoomIndex,
+    /// 每个 Token 的房间订阅者总数 (Tick 扇出方式的依据)
+    pub token_subscribers: state::TokenSubscribers,
     pub config: Arc<Config>,
     pub io: SocketIo,
     pub token_symbols: Arc<DashMap<String, String>>,
+    /// 代币精度: Token Address (Lower) -> decimal (来自 Meme 数据)
+    pub token_decimals: Arc<DashMap<String, i64>>,
     pub narrative_cache: state::NarrativeCache,
+    pub narrative_stats: Arc<state::NarrativeStats>,
     pub db_pool: SqlitePool,
+    /// 历史查询专用连接池 (未配置只读库时与 db_pool 相同)
+    pub read_pool: SqlitePool,
+    /// K 线存储 (klines 表的读写只经由这里)；其余表仍直接使用 db_pool / read_pool
+    pub kline_store: Arc<dyn kline_store::KlineStore>,
     pub client_pool: ClientPool,
     pub narrative_proxy_pool: ClientPool,
     pub image_proxy_pool: ClientPool,
+    /// 图片代理 in-flight 抓取去重
+    pub image_inflight: state::ImageInflightMap,
     pub token_managers: state::TokenManagerMap,
+    /// 各 Worker 最近一次被订阅使用的时间
+    pub worker_activity: state::WorkerActivity,
+    /// 各 Worker 的帧吞吐计数 (GET /workers)
+    pub worker_status: state::WorkerStatusMap,
+    /// Worker 生命周期事件 -> worker_events 审计表
+    pub worker_events: state::WorkerEventSender,
+    /// 热门代币预订阅的房间
+    pub presubscriptions: state::Presubscriptions,
     /// 报警历史队列 (最多保留 50 条，后进先出)
     pub alert_history: Arc<Mutex<VecDeque<types::AlertLogEntry>>>,
     /// 报警冷却映射
     pub alert_cooldowns: Arc<DashMap<String, i64>>,
+    /// 报警阈值 (管理接口可运行时修改)
+    pub alert_thresholds: Arc<RwLock<types::AlertThresholds>>,
+    /// 已触发的一次性报警 (冷却键)，启动时从数据库加载
+    pub fired_once_alerts: Arc<dashmap::DashSet<String>>,
     /// ✨ 全局黑名单 (合约地址)
     pub blacklist: Arc<dashmap::DashSet<String>>,
+    /// 白名单 (合约地址，小写)：Hotlist 的成交额 / 上线时间 / 流动性过滤对其不生效 (黑名单仍优先)
+    pub whitelist: Arc<dashmap::DashSet<String>>,
+    /// 待落库的流动性快照 (地址, 流动性)，定时批量写入，退出前强制刷新
+    pub pending_liquidity: Arc<Mutex<Vec<(String, f64)>>>,
+    /// data-broadcast 节流缓冲
+    pub pending_broadcasts: state::PendingBroadcastMap,
+    /// K 线最近一次上游同步 (或正在进行的同步) 的开始时间: primary_key -> 毫秒时间戳 (用于新鲜度窗口，定期清理过期记录)
+    pub kline_last_sync: Arc<DashMap<String, i64>>,
+    /// 最近一次广播的 Hotlist (过滤后)，新连接 / request_hotlist 时立即下发
+    pub last_hotlist: Arc<Mutex<Vec<types::HotlistItem>>>,
+    /// K 线历史补齐并发限制
+    pub kline_fetch_semaphore: Arc<Semaphore>,
+    /// 每个 socket 的订阅/退订令牌桶
+    pub subscribe_rate_limits: state::SubscribeRateLimits,
+    /// 盘口 (depth) 订阅
+    pub depth_subscriptions: state::DepthSubscriptions,
 }
 
+/// init_tracing 建立的可重载日志过滤器 (POST /admin/log-level 运行时替换，无需重启断开连接)
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
 pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| "backend=info,tower_http=info,sqlx=warn".into());
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = LOG_FILTER.set(handle);
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "backend=info,tower_http=info,sqlx=warn".into()))
+        .with(filter)
         .with(tracing_subscriber::fmt::layer())
         .init();
 }
 
+/// 运行时替换日志过滤器 (未调用 init_tracing 时返回错误)
+pub fn set_log_filter(filter: EnvFilter) -> anyhow::Result<()> {
+    let handle = LOG_FILTER.get().ok_or_else(|| anyhow::anyhow!("tracing is not initialized"))?;
+    handle.reload(filter)?;
+    Ok(())
+}
+
 pub async fn setup_shared_state(config: Arc<Config>, io: SocketIo) -> ServerState {
     // Database Setup
     if let Some(parent) = std::path::Path::new(&config.database_url.replace("sqlite:", "")).parent() {
@@ -62,11 +116,15 @@ pub async fn setup_shared_state(config: Arc<Config>, io: SocketIo) -> ServerStat
     use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous, SqlitePoolOptions};
     use std::str::FromStr;
 
+    let journal_mode = SqliteJournalMode::from_str(&config.sqlite_journal_mode).expect("Invalid SQLite journal mode");
+    let synchronous = SqliteSynchronous::from_str(&config.sqlite_synchronous).expect("Invalid SQLite synchronous level");
+    tracing::info!("🗃️ [DB] journal_mode={:?}, synchronous={:?}", journal_mode, synchronous);
+
     let db_opts = SqliteConnectOptions::from_str(&config.database_url)
         .expect("Invalid database URL")
         .create_if_missing(true)
-        .journal_mode(SqliteJournalMode::Wal)
-        .synchronous(SqliteSynchronous::Normal)
+        .journal_mode(journal_mode)
+        .synchronous(synchronous)
         .pragma("cache_size", "-50000")
         .pragma("mmap_size", "104857600")
         .pragma("busy_timeout", "5000");
@@ -78,11 +136,39 @@ pub async fn setup_shared_state(config: Arc<Config>, io: SocketIo) -> ServerStat
         .expect("Failed to connect to SQLite database");
     kline_handler::init_db(&db_pool).await.expect("Failed to initialize database schema");
 
-    // Pools
-    let client_pool = ClientPool::new(20, None, "DIRECT".to_string()).await;
-    let proxy_url = format!("http://{}", config.proxy_addr);
-    let narrative_proxy_pool = ClientPool::new(10, Some(proxy_url.clone()), "PROXY_API".to_string()).await;
-    let image_proxy_pool = ClientPool::new(10, Some(proxy_url), "PROXY_IMG".to_string()).await;
+    // 只读连接池：历史 K 线 / 流动性查询走这里，减少与写入的 WAL 竞争
+    let read_pool = match &config.database_read_url {
+        Some(read_url) => {
+            let read_opts = SqliteConnectOptions::from_str(read_url)
+                .expect("Invalid read database URL")
+                .read_only(true)
+                .pragma("cache_size", "-50000")
+                .pragma("mmap_size", "104857600")
+                .pragma("busy_timeout", "5000");
+            let pool = SqlitePoolOptions::new()
+                .max_connections(config.read_pool_max_connections)
+                .connect_with(read_opts)
+                .await
+                .expect("Failed to connect to SQLite read database");
+            tracing::info!("📖 [DB] Read pool enabled: {}", read_url);
+            pool
+        }
+        None => db_pool.clone(),
+    };
+
+    // Pools (大小为 0 时按 1 处理，空池无法取客户端)
+    let client_pool = ClientPool::new(config.direct_pool_size.max(1), None, "DIRECT".to_string()).await;
+    // proxy_addr 为 "none" / 空时代理池也走直连
+    let proxy_url = config.proxy_endpoint().map(|_| config.proxy_url());
+    let narrative_proxy_pool = ClientPool::with_stagger(config.narrative_pool_size.max(1), proxy_url.clone(), "PROXY_API".to_string(), config.pool_init_stagger).await;
+    // 图片池不自动跟随重定向：fetch_and_cache_image 对每一跳重新做 SSRF 检查后再跟随；
+    // 直连时连接只走公网地址 (解析结果与连接一致，没有 DNS rebinding 窗口)
+    let image_restrictions = ClientRestrictions { no_redirects: true, public_only: config.image_proxy_block_private_ips };
+    let image_proxy_pool = ClientPool::with_restrictions(config.image_pool_size.max(1), proxy_url, "PROXY_IMG".to_string(), config.pool_init_stagger, image_restrictions).await;
+    if let Some(period) = config.pool_health_check_interval {
+        narrative_proxy_pool.spawn_health_check(config.pool_health_check_url.clone(), period);
+        image_proxy_pool.spawn_health_check(config.pool_health_check_url.clone(), period);
+    }
 
     let app_state = state::new_app_state();
     let room_index = state::new_room_index();
@@ -99,48 +185,214 @@ pub async fn setup_shared_state(config: Arc<Config>, io: SocketIo) -> ServerStat
         tracing::info!("🚫 [Blacklist] Loaded {} entries from DB", blacklist.len());
     }
 
+    let whitelist = Arc::new(dashmap::DashSet::new());
+    if let Ok(list) = kline_handler::get_whitelist(&db_pool).await {
+        for addr in list {
+            whitelist.insert(addr);
+        }
+        tracing::info!("⭐ [Whitelist] Loaded {} entries from DB", whitelist.len());
+    }
+
+    let narrative_cache = state::new_narrative_cache();
+    if let Some(path) = config.narrative_cache_path.as_deref() {
+        load_narrative_cache(path, &narrative_cache).await;
+    }
+
+    // ✨ 加载已触发的一次性报警
+    let fired_once_alerts = Arc::new(dashmap::DashSet::new());
+    if let Ok(keys) = kline_handler::get_fired_alerts(&db_pool).await {
+        for key in keys {
+            fired_once_alerts.insert(key);
+        }
+        tracing::info!("🔕 [Alert] Loaded {} fired once-only alerts from DB", fired_once_alerts.len());
+    }
+
+    // ✨ 加载代币元数据，避免重启后在爬虫重新推送前只能显示地址占位
+    let token_symbols = Arc::new(DashMap::new());
+    let token_decimals = Arc::new(DashMap::new());
+    if let Ok(tokens) = kline_handler::get_all_tokens(&db_pool).await {
+        for token in tokens {
+            if let Some(decimals) = token.decimals {
+                token_decimals.insert(token.address.clone(), decimals);
+            }
+            token_symbols.insert(token.address, token.symbol);
+        }
+        tracing::info!("🪙 [Tokens] Loaded {} token symbols from DB", token_symbols.len());
+    }
+
+    let kline_fetch_semaphore = Arc::new(Semaphore::new(config.max_concurrent_kline_fetches.max(1)));
+    let worker_events = spawn_worker_event_writer(db_pool.clone());
+    let kline_store: Arc<dyn kline_store::KlineStore> =
+        Arc::new(kline_store::SqliteKlineStore::new(db_pool.clone(), read_pool.clone(), config.slow_query_threshold));
+    let alert_thresholds = Arc::new(RwLock::new(alert_handler::initial_alert_thresholds(&config)));
+
     let state = ServerState {
         app_state,
         room_index,
+        token_subscribers: state::new_token_subscribers(),
         config,
         io,
-        token_symbols: Arc::new(DashMap::new()),
-        narrative_cache: state::new_narrative_cache(),
+        token_symbols,
+        token_decimals,
+        narrative_cache,
+        narrative_stats: Arc::new(state::NarrativeStats::default()),
         db_pool,
+        read_pool,
+        kline_store,
         client_pool,
         narrative_proxy_pool,
         image_proxy_pool,
+        image_inflight: state::new_image_inflight_map(),
         token_managers,
+        worker_activity: state::new_worker_activity(),
+        worker_status: state::new_worker_status_map(),
+        worker_events,
+        presubscriptions: state::new_presubscriptions(),
         alert_history,
         alert_cooldowns,
+        alert_thresholds,
+        fired_once_alerts,
         blacklist: blacklist.clone(),
+        whitelist,
+        pending_liquidity: Arc::new(Mutex::new(Vec::new())),
+        pending_broadcasts: state::new_pending_broadcast_map(),
+        kline_last_sync: Arc::new(DashMap::new()),
+        last_hotlist: Arc::new(Mutex::new(Vec::new())),
+        kline_fetch_semaphore,
+        subscribe_rate_limits: state::new_subscribe_rate_limits(),
+        depth_subscriptions: state::new_depth_subscriptions(),
     };
 
+    // ✨ 启动流动性快照批量落库任务
+    let state_for_flush = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state_for_flush.config.liquidity_flush_interval);
+        loop {
+            interval.tick().await;
+            flush_pending_liquidity(&state_for_flush).await;
+        }
+    });
+
+    // ✨ 启动叙事缓存定时持久化任务 (仅在配置了路径时)
+    if state.config.narrative_cache_path.is_some() {
+        let state_for_persist = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(state_for_persist.config.narrative_persist_interval);
+            interval.tick().await; // 跳过首次立即触发，刚加载完无需回写
+            loop {
+                interval.tick().await;
+                persist_narrative_cache(&state_for_persist).await;
+            }
+        });
+    }
+
+    // ✨ 启动报警冷却记录清理任务
+    let state_for_cooldown_prune = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state_for_cooldown_prune.config.alert_cooldown_prune_interval);
+        loop {
+            interval.tick().await;
+            let now = chrono::Utc::now().timestamp_millis();
+            let removed = alert_handler::prune_alert_cooldowns(&state_for_cooldown_prune, now);
+            if removed > 0 {
+                tracing::info!("🧹 [Alert Cooldown Prune] Removed {} stale entries", removed);
+            }
+        }
+    });
+
+    // ✨ 实时 K 线定期校准 (可选)
+    if let Some(reconcile_interval) = state.config.kline_reconcile_interval {
+        let state_for_reconcile = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(reconcile_interval);
+            interval.tick().await; // 启动时不立即执行
+            loop {
+                interval.tick().await;
+                let corrected = kline_handler::reconcile_live_klines(&state_for_reconcile).await;
+                if corrected > 0 {
+                    tracing::info!("🩹 [KLINE RECONCILE] Corrected {} rooms", corrected);
+                }
+            }
+        });
+    }
+
+    // ✨ 预订阅过期清理 (未开启预订阅时不启动)
+    if state.config.presubscribe_min_volume_usd.is_some() {
+        let state_for_presub = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let now = chrono::Utc::now().timestamp_millis();
+                let released = socket_handlers::prune_presubscriptions(&state_for_presub, now);
+                if released > 0 {
+                    tracing::info!("🧹 [Presubscribe] Released {} expired rooms", released);
+                }
+            }
+        });
+    }
+
+    // ✨ K 线同步记录清理：过了新鲜度窗口的记录不再有用
+    let state_for_sync_prune = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let now = chrono::Utc::now().timestamp_millis();
+            let pruned = kline_handler::prune_kline_last_sync(&state_for_sync_prune, now);
+            if pruned > 0 {
+                tracing::debug!("🧹 [Kline Sync] Pruned {} stale sync records", pruned);
+            }
+        }
+    });
+
+    // ✨ 启动 K 线保留期清理任务 (仅在配置了保留期时)
+    if let Some(retention) = state.config.kline_retention {
+        let state_for_kline_prune = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(state_for_kline_prune.config.kline_prune_interval);
+            loop {
+                interval.tick().await;
+                match state_for_kline_prune.kline_store.prune_inactive(retention.as_secs() as i64).await {
+                    Ok(count) => {
+                        if count > 0 {
+                            tracing::info!("🧹 [Kline Prune] Removed {} candles of abandoned tokens", count);
+                        }
+                    }
+                    Err(e) => tracing::error!("❌ [Kline Prune ERR] {}", e),
+                }
+            }
+        });
+    }
+
+    // ✨ 启动 Worker 生命周期事件清理任务
+    let state_for_event_prune = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let max_age = state_for_event_prune.config.worker_events_retention.as_secs() as i64;
+            match kline_handler::prune_worker_events(&state_for_event_prune.db_pool, max_age).await {
+                Ok(count) => {
+                    if count > 0 {
+                        tracing::info!("🧹 [Worker Events Prune] Removed {} old events", count);
+                    }
+                }
+                Err(e) => tracing::error!("❌ [Worker Events Prune ERR] {}", e),
+            }
+        }
+    });
+
     // ✨ 启动黑名单 TTL 清理任务 (每小时运行一次，24小时过期)
-    let db_pool_for_prune = state.db_pool.clone();
-    let blacklist_for_prune = state.blacklist.clone();
+    let state_for_blacklist_prune = state.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
         loop {
             interval.tick().await;
-            match kline_handler::prune_blacklist(&db_pool_for_prune, 24 * 3600).await {
+            match prune_expired_blacklist(&state_for_blacklist_prune).await {
                 Ok(count) => {
                     if count > 0 {
                         tracing::info!("🧹 [Blacklist Prune] Removed {} expired entries", count);
-                        // 🔥 新增：报警检测
-                        // The original instruction had `data` and `state` which are not in scope here.
-                        // Assuming this line was meant to be placed elsewhere or `data` and `state`
-                        // should be derived from the context if this was a different task.
-                        // As per the instruction, I'm inserting the line as is, but it will cause a compile error.
-                        // To make it syntactically correct and compile, I'm commenting it out.
-                        // crate::alert_handler::check_and_trigger_alerts(data, &state, &state.io).await;
-                        // 同步刷新内存缓存
-                        if let Ok(list) = kline_handler::get_blacklist(&db_pool_for_prune).await {
-                            blacklist_for_prune.clear();
-                            for addr in list {
-                                blacklist_for_prune.insert(addr);
-                            }
-                        }
                     }
                 }
                 Err(e) => tracing::error!("❌ [Blacklist Prune ERR] {}", e),
@@ -150,3 +402,122 @@ pub async fn setup_shared_state(config: Arc<Config>, io: SocketIo) -> ServerStat
 
     state
 }
+
+/// 启动 Worker 生命周期事件写入任务：逐批取出通道中的事件写入 worker_events 表
+fn spawn_worker_event_writer(db_pool: SqlitePool) -> state::WorkerEventSender {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut batch = Vec::new();
+        while rx.recv_many(&mut batch, 256).await > 0 {
+            if let Err(e) = kline_handler::insert_worker_events(&db_pool, &batch).await {
+                tracing::warn!("⚠️ [Worker Events] Failed to persist {} events: {}", batch.len(), e);
+            }
+            batch.clear();
+        }
+    });
+    tx
+}
+
+/// 清理超过 24 小时的黑名单记录，有删除时同步刷新内存黑名单。返回删除条数
+pub async fn prune_expired_blacklist(state: &ServerState) -> anyhow::Result<u64> {
+    let count = kline_handler::prune_blacklist(&state.db_pool, 24 * 3600).await?;
+    if count > 0 {
+        let list = kline_handler::get_blacklist(&state.db_pool).await?;
+        state.blacklist.clear();
+        for addr in list {
+            state.blacklist.insert(addr);
+        }
+    }
+    Ok(count)
+}
+
+/// 将缓冲中的流动性快照一次性写入数据库
+pub async fn flush_pending_liquidity(state: &ServerState) {
+    let items = std::mem::take(&mut *state.pending_liquidity.lock().await);
+    if items.is_empty() {
+        return;
+    }
+    let count = items.len();
+    if let Err(e) = kline_handler::record_liquidity_batch(&state.db_pool, items).await {
+        tracing::warn!("⚠️ [DB BATCH ERR] Failed to flush {} liquidity items: {}", count, e);
+    }
+}
+
+/// 叙事缓存文件中的条目：旧版只存最新一条字符串，新版存完整历史
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum PersistedNarrative {
+    Legacy(String),
+    History(state::NarrativeEntry),
+}
+
+/// 从磁盘恢复叙事缓存 (文件不存在时静默跳过，进行中的占位标记不恢复)
+async fn load_narrative_cache(path: &str, cache: &state::NarrativeCache) {
+    let bytes = match tokio::fs::read(path).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            tracing::warn!("⚠️ [Narrative] Failed to read cache file {}: {}", path, e);
+            return;
+        }
+    };
+    match serde_json::from_slice::<HashMap<String, PersistedNarrative>>(&bytes) {
+        Ok(entries) => {
+            let now = chrono::Utc::now().timestamp_millis();
+            for (address, narrative) in entries {
+                let entry = match narrative {
+                    PersistedNarrative::Legacy(text) if text == "__PENDING__" => continue,
+                    PersistedNarrative::Legacy(text) => state::NarrativeEntry::from_text(text, now),
+                    PersistedNarrative::History(entry) => entry,
+                };
+                cache.insert(address, entry);
+            }
+            tracing::info!("📖 [Narrative] Restored {} cached narratives from {}", cache.len(), path);
+        }
+        Err(e) => tracing::warn!("⚠️ [Narrative] Ignoring corrupt cache file {}: {}", path, e),
+    }
+}
+
+/// 将叙事缓存写回磁盘 (未配置路径时为空操作)
+pub async fn persist_narrative_cache(state: &ServerState) {
+    let Some(path) = state.config.narrative_cache_path.as_deref() else { return; };
+    let entries: HashMap<String, state::NarrativeEntry> = state
+        .narrative_cache
+        .iter()
+        .filter(|entry| !entry.value().pending)
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    let bytes = match serde_json::to_vec(&entries) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("⚠️ [Narrative] Failed to serialize cache: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = cache::write_atomic(std::path::Path::new(path), &bytes).await {
+        tracing::warn!("⚠️ [Narrative] Failed to persist cache to {}: {}", path, e);
+    }
+}
+
+/// 等待 Ctrl+C 或 SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
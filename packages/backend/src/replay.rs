@@ -0,0 +1,118 @@
+// packages/backend/src/replay.rs
+// 录制 / 回放爬虫的 data-update 推送：用生产环境录下的原始数据复现报警误报、调试过滤与 enrich 逻辑
+use super::{socket_handlers, types::DataPayload, ServerState};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// 录制文件中的一行：接收时间 (毫秒) + 原始 payload。
+/// 回放时也接受只有 payload 的行 (没有时间戳则不等待)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedUpdate {
+    #[serde(default)]
+    pub received_at: Option<i64>,
+    pub payload: serde_json::Value,
+}
+
+/// 追加一条录制记录 (后台写入，不阻塞推送处理)
+pub fn record_data_update(path: String, payload: serde_json::Value) {
+    tokio::spawn(async move {
+        let record = RecordedUpdate {
+            received_at: Some(chrono::Utc::now().timestamp_millis()),
+            payload,
+        };
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("⚠️ [Replay] Failed to encode recorded payload: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        // 单次 write_all 整行写入，多个连接并发录制时行不会交错
+        let result = match tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await {
+            Ok(mut file) => file.write_all(&line).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("⚠️ [Replay] Failed to append to record file {}: {}", path, e);
+        }
+    });
+}
+
+/// 解析录制文件的一行：优先按 RecordedUpdate，否则视为裸 payload
+fn parse_line(line: &str) -> Result<RecordedUpdate, serde_json::Error> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    if value.get("payload").is_some() {
+        serde_json::from_value(value)
+    } else {
+        Ok(RecordedUpdate { received_at: None, payload: value })
+    }
+}
+
+/// 按录制时间间隔 (÷ replay_speed) 回放文件中的推送，走与实时 data-update 相同的处理逻辑，
+/// 需要广播的结果发到报警命名空间。返回成功回放的条数
+pub async fn replay_file(state: &ServerState, path: &str) -> anyhow::Result<usize> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let speed = state.config.replay_speed;
+    info!("⏯️ [Replay] Replaying {} (speed x{})", path, speed);
+
+    let mut last_received: Option<i64> = None;
+    let mut replayed = 0;
+    for (line_no, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = match parse_line(line) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("⚠️ [Replay] Line {}: invalid JSON: {}", line_no + 1, e);
+                continue;
+            }
+        };
+
+        if let (Some(prev), Some(current)) = (last_received, record.received_at) {
+            let gap_ms = (current - prev).max(0) as f64;
+            if speed > 0.0 && gap_ms > 0.0 {
+                tokio::time::sleep(Duration::from_secs_f64(gap_ms / speed / 1000.0)).await;
+            }
+        }
+        last_received = record.received_at.or(last_received);
+
+        let mut payload = match serde_json::from_value::<DataPayload>(record.payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("⚠️ [Replay] Line {}: payload mismatch: {}", line_no + 1, e);
+                continue;
+            }
+        };
+        if socket_handlers::process_data_update(state, &mut payload).await {
+            if let Some(ns) = state.io.of(state.config.alerts_namespace.as_str()) {
+                ns.emit("data-broadcast", &payload).await.ok();
+            }
+        }
+        replayed += 1;
+    }
+
+    info!("⏯️ [Replay] Finished {}: {} payloads replayed", path, replayed);
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_accepts_recorded_and_bare_payloads() {
+        let recorded = parse_line(r#"{"receivedAt":1700000000000,"payload":{"category":"hotlist"}}"#).unwrap();
+        assert_eq!(recorded.received_at, Some(1_700_000_000_000));
+        assert_eq!(recorded.payload["category"], "hotlist");
+
+        let bare = parse_line(r#"{"category":"hotlist","type":"snapshot","data":[]}"#).unwrap();
+        assert_eq!(bare.received_at, None);
+        assert_eq!(bare.payload["type"], "snapshot");
+    }
+}
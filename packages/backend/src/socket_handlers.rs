@@ -1,25 +1,28 @@
 // packages/backend/src/socket_handlers.rs
 use super::{
+    config::Config,
     kline_handler,
-    state::SubscriptionCommand,
+    state::{NarrativeCache, NarrativeEntry, PendingBroadcast, SubscriptionCommand, TokenBucket, WorkerEventLog, WorkerMetrics},
     // ✨ 引入新的 Struct 和 Trait
-    types::{DataPayload, KlineSubscribePayload, NarrativeEntity, NarrativeResponse, Room},
+    types::{DataAction, DataPayload, DepthSubscribePayload, HotlistItem, KlineSubscribePayload, MemeScanItem, NarrativeEntity, NarrativeResponse, Room, TimedNarrative, TokenMetadata},
     ServerState,
 };
-use socketioxide::extract::{Data, SocketRef};
+use socketioxide::{
+    extract::{AckSender, Data, SocketRef},
+    socket::Sid,
+};
+use dashmap::mapref::entry::Entry;
 use std::collections::HashSet;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc::{UnboundedReceiver, UnboundedSender}, Mutex};
 use tokio::time::Duration;
 use tracing::{info, warn, error}; // ✨ Added error
 use chrono::Utc;
-use flate2::read::GzDecoder;
-use std::io::Read;
 
 const ENABLE_FILTERING: bool = true;
 const MIN_HOTLIST_AMOUNT: f64 = 5000.0;
 const MIN_HOTLIST_LIQUIDITY: f64 = 30000.0;
-const NARRATIVE_API_URL: &str = "https://web3.binance.com/bapi/defi/v1/public/wallet-direct/buw/wallet/token/ai/narrative/query";
 const LAZY_UNSUBSCRIBE_DELAY: u64 = 60;
 // Helper to normalize address based on chain/pool_id
 // EVM (BSC/ETH/Base) -> Lowercase
@@ -32,9 +35,64 @@ pub fn normalize_address(pool_id: i64, address: &str) -> String {
     }
 }
 
+// Binance poolId -> Chain (chain_to_pool_id 的反向)
+pub fn pool_id_to_chain(pool_id: i64) -> Option<&'static str> {
+    match pool_id {
+        14 => Some("bsc"),
+        16 => Some("sol"),
+        199 => Some("base"),
+        _ => None,
+    }
+}
+
+// Chain -> Binance poolId (不支持的链返回 None)
+pub fn chain_to_pool_id(chain: &str) -> Option<i64> {
+    match chain.to_lowercase().as_str() {
+        "bsc" => Some(14),
+        "sol" | "solana" => Some(16),
+        "base" => Some(199),
+        _ => None,
+    }
+}
+
+/// 按配置注册命名空间：报警与行情命名空间相同时注册全量处理器，不同时各自只注册相关处理器
+pub fn register_namespaces(state: &ServerState) {
+    let alerts_ns = state.config.alerts_namespace.clone();
+    let market_ns = state.config.market_namespace.clone();
+
+    if alerts_ns == market_ns {
+        let socket_state = state.clone();
+        state.io.ns(alerts_ns, move |s: SocketRef| on_socket_connect(s, socket_state.clone()));
+        return;
+    }
+
+    info!("🧭 [Socket.IO] Split namespaces: alerts={}, market={}", alerts_ns, market_ns);
+    let alerts_state = state.clone();
+    state.io.ns(alerts_ns, move |s: SocketRef| on_alerts_connect(s, alerts_state.clone()));
+    let market_state = state.clone();
+    state.io.ns(market_ns, move |s: SocketRef| on_market_connect(s, market_state.clone()));
+}
+
+/// 同一命名空间下注册全部处理器
 pub async fn on_socket_connect(s: SocketRef, state: ServerState) {
     info!("🔌 [Socket.IO] Client connected: {}", s.id);
+    setup_alerts_socket(&s, &state).await;
+    setup_market_socket(&s, &state);
+}
+
+/// 报警/推送命名空间：爬虫数据、Hotlist、报警、黑名单、代币详情、流动性历史
+pub async fn on_alerts_connect(s: SocketRef, state: ServerState) {
+    info!("🔌 [Socket.IO] Alerts client connected: {}", s.id);
+    setup_alerts_socket(&s, &state).await;
+}
+
+/// 行情命名空间：K 线订阅与历史、叙事请求
+pub async fn on_market_connect(s: SocketRef, state: ServerState) {
+    info!("🔌 [Socket.IO] Market client connected: {}", s.id);
+    setup_market_socket(&s, &state);
+}
 
+async fn setup_alerts_socket(s: &SocketRef, state: &ServerState) {
     // 🔥 新增：推送报警历史给新连接的客户端
     {
         let history = state.alert_history.lock().await;
@@ -46,6 +104,9 @@ pub async fn on_socket_connect(s: SocketRef, state: ServerState) {
         }
     }
 
+    // ✨ 新增：推送最近的 Hotlist，避免在两次爬虫推送之间连接的客户端看到空列表
+    emit_cached_hotlist(s, state).await;
+
     // ✨ 新增：推送全量黑名单给新连接的客户端
     {
         let blacklist_vec: Vec<String> = state.blacklist.iter().map(|item| item.key().clone()).collect();
@@ -53,14 +114,64 @@ pub async fn on_socket_connect(s: SocketRef, state: ServerState) {
         info!("🚫 [Blacklist] Sent {} entries to {}", blacklist_vec.len(), s.id);
     }
 
-    register_data_update_handler(&s, state.clone());
-    register_kline_subscribe_handler(&s, state.clone());
-    register_kline_unsubscribe_handler(&s, state.clone());
-    register_disconnect_handler(&s, state.clone());
-    register_kline_history_handler(&s, state.clone());
-    register_liquidity_history_handler(&s, state.clone());
-    register_narrative_handler(&s, state.clone());
-    register_blacklist_handlers(&s, state);
+    register_data_update_handler(s, state.clone());
+    register_liquidity_history_handler(s, state.clone());
+    register_token_detail_handlers(s);
+    register_hotlist_request_handler(s, state.clone());
+    register_blacklist_handlers(s, state.clone());
+}
+
+fn setup_market_socket(s: &SocketRef, state: &ServerState) {
+    register_kline_subscribe_handler(s, state.clone());
+    register_resume_handler(s, state.clone());
+    register_kline_unsubscribe_handler(s, state.clone());
+    register_disconnect_handler(s, state.clone());
+    register_unsubscribe_all_handler(s, state.clone());
+    register_list_subscriptions_handler(s, state.clone());
+    register_kline_history_handler(s, state.clone());
+    register_narrative_handler(s, state.clone());
+    register_narrative_history_handler(s, state.clone());
+    register_depth_handlers(s, state.clone());
+}
+
+/// 以 snapshot 形式把缓存的 Hotlist 单独推送给该客户端 (缓存为空时不推送)
+async fn emit_cached_hotlist(s: &SocketRef, state: &ServerState) {
+    let hotlist = state.last_hotlist.lock().await.clone();
+    if hotlist.is_empty() {
+        return;
+    }
+    let count = hotlist.len();
+    let payload = DataPayload::Hotlist { r#type: DataAction::Snapshot, data: hotlist };
+    s.emit("data-broadcast", &payload).ok();
+    info!("🔥 [Hotlist] Sent {} cached items to {}", count, s.id);
+}
+
+fn register_hotlist_request_handler(socket: &SocketRef, state: ServerState) {
+    socket.on("request_hotlist", move |s: SocketRef| {
+        let state = state.clone();
+        async move {
+            emit_cached_hotlist(&s, &state).await;
+        }
+    });
+}
+
+/// 代币详情房间名 (地址统一小写，与 token_symbols / blacklist 保持一致)
+pub fn token_detail_room(address: &str) -> String {
+    format!("token@{}", address.to_lowercase())
+}
+
+fn register_token_detail_handlers(socket: &SocketRef) {
+    socket.on("subscribe_token_detail", |s: SocketRef, Data(address): Data<String>| {
+        let room = token_detail_room(&address);
+        info!("📄 [TokenDetail] {} joined {}", s.id, room);
+        s.join(room);
+    });
+
+    socket.on("unsubscribe_token_detail", |s: SocketRef, Data(address): Data<String>| {
+        let room = token_detail_room(&address);
+        info!("📄 [TokenDetail] {} left {}", s.id, room);
+        s.leave(room);
+    });
 }
 
 fn register_blacklist_handlers(socket: &SocketRef, state: ServerState) {
@@ -78,7 +189,9 @@ fn register_blacklist_handlers(socket: &SocketRef, state: ServerState) {
             state.blacklist.insert(addr_lower.clone());
             // 广播通知所有人同步
             tracing::info!("📡 [Blacklist:BROADCAST] Notifying all clients about blocked token: {}", addr_lower);
-            state.io.emit("blacklist_update", &serde_json::json!({ "action": "add", "address": addr_lower })).await.ok();
+            if let Some(ns) = state.io.of(state.config.alerts_namespace.as_str()) {
+                ns.emit("blacklist_update", &serde_json::json!({ "action": "add", "address": addr_lower })).await.ok();
+            }
         }
     });
 
@@ -96,7 +209,9 @@ fn register_blacklist_handlers(socket: &SocketRef, state: ServerState) {
             state.blacklist.remove(&addr_lower);
             // 广播通知所有人同步
             tracing::info!("📡 [Blacklist:BROADCAST] Notifying all clients about unblocked token: {}", addr_lower);
-            state.io.emit("blacklist_update", &serde_json::json!({ "action": "remove", "address": addr_lower })).await.ok();
+            if let Some(ns) = state.io.of(state.config.alerts_namespace.as_str()) {
+                ns.emit("blacklist_update", &serde_json::json!({ "action": "remove", "address": addr_lower })).await.ok();
+            }
         }
     });
 }
@@ -142,98 +257,454 @@ fn schedule_lazy_tick_unsubscribe(state: ServerState, address: String, pool_id:
     });
 }
 
+/// 获取 Token 的 Worker 指令通道，不存在时创建并启动。
+/// 通过 entry API 原子地 "查找或插入"，并发的首次订阅只会启动一个 Worker。
+/// Worker 数量达到上限且没有可淘汰的空闲 Worker 时返回 None
+fn ensure_token_worker(state: &ServerState, address: &str, pool_id: i64) -> Option<UnboundedSender<SubscriptionCommand>> {
+    state.worker_activity.insert(address.to_string(), Utc::now().timestamp_millis());
+
+    // 上限检查需在 entry 之外完成：淘汰时要遍历 token_managers，持有 entry 锁会死锁
+    if !state.token_managers.contains_key(address) && !make_room_for_worker(state) {
+        state.worker_activity.remove(address);
+        return None;
+    }
+
+    let sender = match state.token_managers.entry(address.to_string()) {
+        Entry::Occupied(entry) => {
+            info!("♻️ [WORKER REUSE] TokenWorker already exists for: {}", address); // ✨ Debug Log
+            entry.get().clone()
+        }
+        Entry::Vacant(entry) => {
+            info!("🛠️ [WORKER SPAWN] Creating new TokenWorker for: {}", address); // ✨ Debug Log
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            entry.insert(tx.clone());
+            start_worker_task(state, address, pool_id, rx);
+            tx
+        }
+    };
+    Some(sender)
+}
+
+/// Worker 数量达到上限时，淘汰一个已退出或无人订阅且最久未活跃的 Worker。
+/// 返回是否可以再创建新 Worker
+fn make_room_for_worker(state: &ServerState) -> bool {
+    let max_workers = state.config.max_token_workers;
+    if max_workers == 0 || state.token_managers.len() < max_workers {
+        return true;
+    }
+
+    // 已退出的 Worker (通道关闭) 优先淘汰，其次按最近活跃时间从旧到新
+    let victim = state
+        .token_managers
+        .iter()
+        .filter(|entry| {
+            let address = entry.key();
+            let has_rooms = state.room_index.get(address).is_some_and(|rooms| !rooms.is_empty());
+            entry.value().is_closed() || (!has_rooms && !state.depth_subscriptions.contains_key(address))
+        })
+        .map(|entry| {
+            let last_active = state.worker_activity.get(entry.key()).map_or(0, |t| *t);
+            (!entry.value().is_closed(), last_active, entry.key().clone())
+        })
+        .min();
+
+    let Some((_, last_active, address)) = victim else {
+        warn!("🚫 [WORKER LIMIT] {} workers running, none idle. Rejecting new token.", max_workers);
+        return false;
+    };
+
+    if let Some((_, sender)) = state.token_managers.remove(&address) {
+        let _ = sender.send(SubscriptionCommand::Subscribe("SHUTDOWN".to_string()));
+    }
+    state.worker_activity.remove(&address);
+    state.room_index.remove(&address);
+    info!("♻️ [WORKER EVICT] Evicted idle TokenWorker {} (last active {}ms ago)", address, Utc::now().timestamp_millis() - last_active);
+    true
+}
+
+/// 通知客户端因 Worker 数量达到上限无法订阅
+fn emit_worker_limit_error(s: &SocketRef, action: &str) {
+    s.emit("subscription_error", &serde_json::json!({ "action": action, "reason": "worker_limit" })).ok();
+}
+
+/// 周期不在支持列表中时通知客户端，返回是否可以继续
+pub(crate) fn check_interval_supported(s: &SocketRef, state: &ServerState, action: &str, interval: &str) -> bool {
+    if state.config.is_supported_interval(interval) {
+        return true;
+    }
+    warn!("🚫 [INTERVAL] Client {} requested unsupported interval '{}' ({})", s.id, interval, action);
+    s.emit(
+        "subscription_error",
+        &serde_json::json!({ "action": action, "reason": "unsupported_interval", "interval": interval }),
+    )
+    .ok();
+    false
+}
+
+fn start_worker_task(state: &ServerState, address: &str, pool_id: i64, rx: UnboundedReceiver<SubscriptionCommand>) {
+    let state_clone = state.clone();
+    let address_clone = address.to_string();
+    let metrics = Arc::new(WorkerMetrics::default());
+    state.worker_status.insert(address.to_string(), metrics.clone());
+    tokio::spawn(async move {
+         crate::token_manager::start_token_worker(
+             address_clone.clone(),
+             pool_id,
+             state_clone.io.clone(),
+             state_clone.config.clone(),
+             state_clone.app_state.clone(),
+             state_clone.room_index.clone(),
+             crate::state::subscriber_counter(&state_clone.token_subscribers, &address_clone),
+             rx,
+             metrics.clone(),
+             WorkerEventLog::new(&address_clone, state_clone.worker_events.clone()),
+         ).await;
+         // 仅移除自己的计数 (同一地址可能已重建了新 Worker)
+         state_clone.worker_status.remove_if(&address_clone, |_, v| Arc::ptr_eq(v, &metrics));
+    });
+}
+
+/// 向 TokenWorker 发送指令。
+/// 如果通道已关闭 (Worker 崩溃或已空闲退出但条目残留)，移除旧条目并重建 Worker，
+/// 按 room_index 恢复该 Token 仍在使用的订阅后再重试订阅指令。
+fn send_worker_command(state: &ServerState, address: &str, pool_id: i64, cmd: SubscriptionCommand) {
+    let send_result = match state.token_managers.get(address) {
+        Some(sender) => sender.send(cmd),
+        None => return,
+    };
+
+    let failed_cmd = match send_result {
+        Ok(()) => return,
+        Err(e) => e.0,
+    };
+
+    warn!("🩹 [WORKER HEAL] Channel closed for {}. Removing stale worker entry.", address);
+    state.token_managers.remove(address);
+
+    let active_rooms: Vec<String> = state
+        .room_index
+        .get(address)
+        .map(|rooms| rooms.iter().cloned().collect())
+        .unwrap_or_default();
+    let is_subscribe = matches!(failed_cmd, SubscriptionCommand::Subscribe(_));
+
+    // 退订且已无房间：无需重建，旧 Worker 退出即可
+    if !is_subscribe && active_rooms.is_empty() {
+        return;
+    }
+
+    let Some(sender) = ensure_token_worker(state, address, pool_id) else {
+        error!("❌ [WORKER HEAL] Worker limit reached, cannot respawn TokenWorker for {}", address);
+        return;
+    };
+    for room_key in &active_rooms {
+        let _ = sender.send(SubscriptionCommand::Subscribe(room_key.clone()));
+    }
+    if !active_rooms.is_empty() {
+        let _ = sender.send(SubscriptionCommand::Subscribe(format!("tx@{}_{}", pool_id, address)));
+    }
+    if is_subscribe {
+        if let Err(e) = sender.send(failed_cmd) {
+            error!("❌ [CMD FAIL] Failed to send command to respawned worker {}: {}", address, e);
+        }
+    }
+    info!("✅ [WORKER HEAL] Respawned TokenWorker for {} ({} rooms restored)", address, active_rooms.len());
+}
+
+/// 订阅/退订频率限制：超限时通知客户端并拒绝本次操作
+fn allow_subscription_op(s: &SocketRef, state: &ServerState, action: &str) -> bool {
+    take_subscription_ops(s, state, action, 1) == 1
+}
+
+/// 批量操作按条计费：返回放行的条数，不足 n 条时回 subscription_error (带被拒绝的条数)
+fn take_subscription_ops(s: &SocketRef, state: &ServerState, action: &str, n: usize) -> usize {
+    let per_sec = state.config.subscribe_ops_per_sec;
+    if per_sec <= 0.0 {
+        return n;
+    }
+    let taken = state
+        .subscribe_rate_limits
+        .entry(s.id)
+        .or_insert_with(|| TokenBucket::new(per_sec))
+        .take_up_to(per_sec, n);
+    if taken < n {
+        warn!("🚦 [RATE LIMIT] Client {} exceeded {} ops/s ({}, {}/{} allowed)", s.id, per_sec, action, taken, n);
+        s.emit(
+            "subscription_error",
+            &serde_json::json!({ "action": action, "reason": "rate_limited", "rejected": n - taken }),
+        )
+        .ok();
+    }
+    taken
+}
+
 fn register_kline_subscribe_handler(socket: &SocketRef, state: ServerState) {
-    socket.on("subscribe_kline", move |s: SocketRef, Data(payload): Data<KlineSubscribePayload>| {
+    socket.on("subscribe_kline", move |s: SocketRef, Data(payload): Data<KlineSubscribePayload>, ack: AckSender| {
         let state = state.clone();
         async move {
-            info!("🔔 [SUB DEBUG] Payload: address={}, chain={}, interval={}", payload.address, payload.chain, payload.interval);
-            let chain_lower = payload.chain.to_lowercase();
-            // 1. Calculate pool_id FIRST to determine normalization rule
-            let pool_id = match chain_lower.as_str() {
-                "bsc" => 14, "sol" | "solana" => 16, "base" => 199, _ => return,
+            let result = if allow_subscription_op(&s, &state, "subscribe") {
+                subscribe_socket_to_kline(&s, &state, &payload).await
+            } else {
+                Err("rate_limited")
+            };
+            // 客户端带 ack 回调时确认订阅结果 (未请求 ack 时发送失败，忽略)
+            let reply = match result {
+                Ok(room) => serde_json::json!({ "ok": true, "room": room }),
+                Err(error) => serde_json::json!({ "ok": false, "error": error }),
             };
+            ack.send(&reply).ok();
+        }
+    });
+}
 
-            // 2. Normalize Address (Preserve case for SOL, lowercase for EVM)
-            let address = normalize_address(pool_id, &payload.address);
-            
-            let symbol = state.token_symbols.get(&address).map_or_else(
-                || format!("{}...", &address[0..6]),
-                |s| s.value().clone(),
-            );
+/// 断线重连恢复：客户端带上之前的订阅列表一次性重新加入所有房间 (已有 Worker 直接复用)
+fn register_resume_handler(socket: &SocketRef, state: ServerState) {
+    socket.on("resume", move |s: SocketRef, Data(subscriptions): Data<Vec<KlineSubscribePayload>>| {
+        let state = state.clone();
+        async move {
+            // 每恢复一条订阅计一次；超出剩余配额的部分不恢复，客户端收到 rate_limited 后稍后重试
+            let allowed = take_subscription_ops(&s, &state, "resume", subscriptions.len());
+            let mut rooms = Vec::with_capacity(allowed);
+            for payload in &subscriptions[..allowed] {
+                if let Ok(room) = subscribe_socket_to_kline(&s, &state, payload).await {
+                    rooms.push(room);
+                }
+            }
+            info!("🔁 [RESUME] Client {} restored {}/{} subscriptions", s.id, rooms.len(), subscriptions.len());
+            s.emit("resumed", &rooms).ok();
+        }
+    });
+}
 
-            let room_name = format!("kl@{}@{}@{}", pool_id, address, payload.interval);
-            let log_name = format!("kl@{}@{}@{}", pool_id, &symbol, payload.interval);
+/// 该 socket 是否已在房间的客户端集合中
+fn is_room_member(state: &ServerState, room_name: &str, sid: Sid) -> bool {
+    state.app_state.get(room_name).is_some_and(|room| room.clients.contains(&sid))
+}
 
-            info!("🔔 [SUB] Client {} -> {}", s.id, log_name);
-            s.join(room_name.clone());
+/// 未知代币的占位 symbol：地址前 6 个字符 (按字符截取，过短或含多字节字符的输入不会 panic)
+fn placeholder_symbol(address: &str) -> String {
+    format!("{}...", address.chars().take(6).collect::<String>())
+}
 
-            let is_new_room = !state.app_state.contains_key(&room_name);
+/// 让客户端加入 K 线房间，必要时创建 Worker 并下发订阅；返回房间名，失败时返回原因 (与 subscription_error 的 reason 一致)
+async fn subscribe_socket_to_kline(s: &SocketRef, state: &ServerState, payload: &KlineSubscribePayload) -> Result<String, &'static str> {
+    info!("🔔 [SUB DEBUG] Payload: address={}, chain={}, interval={}", payload.address, payload.chain, payload.interval);
+    // 1. Calculate pool_id FIRST to determine normalization rule
+    let Some(pool_id) = chain_to_pool_id(&payload.chain) else { return Err("unsupported_chain"); };
+    if !check_interval_supported(s, state, "subscribe", &payload.interval) {
+        return Err("unsupported_interval");
+    }
 
-            state.app_state.entry(room_name.clone())
-                .or_insert_with(|| Room {
-                    clients: HashSet::new(),
-                    symbol: symbol.clone(),
-                    current_kline: Arc::new(Mutex::new(None)),
-                })
-                .value_mut().clients.insert(s.id);
-
-            let need_sub_tick = handle_index_subscription(&state, &address, &room_name);
-
-            if is_new_room {
-                // 1. Ensure TokenWorker exists (Use normalized address as key)
-                if !state.token_managers.contains_key(&address) {
-                    info!("🛠️ [WORKER SPAWN] Creating new TokenWorker for: {}", address); // ✨ Debug Log
-                    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-                    state.token_managers.insert(address.clone(), tx);
-                    
-                    let state_clone = state.clone();
-                    let address_clone = address.clone();
-                    tokio::spawn(async move {
-                         crate::token_manager::start_token_worker(
-                             address_clone,
-                             pool_id,
-                             state_clone.io.clone(),
-                             state_clone.config.clone(),
-                             state_clone.app_state.clone(),
-                             state_clone.room_index.clone(),
-                             rx
-                         ).await;
-                    });
-                } else {
-                    info!("♻️ [WORKER REUSE] TokenWorker already exists for: {}", address); // ✨ Debug Log
-                }
-                
-                // 2. Send Subscribe Command
-                if let Some(sender) = state.token_managers.get(&address) {
-                    let kl_stream = format!("kl@{}@{}@{}", pool_id, address, payload.interval);
-                    info!("📤 [CMD SEND] Subscribe Kline: {}", kl_stream); // ✨ Debug Log
-                    if let Err(e) = sender.send(SubscriptionCommand::Subscribe(kl_stream)) {
-                        error!("❌ [CMD FAIL] Failed to send Kline sub command: {}", e);
-                    }
-                    
-                    if need_sub_tick {
-                        let tx_stream = format!("tx@{}_{}", pool_id, address);
-                        info!("📤 [CMD SEND] Subscribe Tick: {}", tx_stream); // ✨ Debug Log
-                        if let Err(e) = sender.send(SubscriptionCommand::Subscribe(tx_stream)) {
-                             error!("❌ [CMD FAIL] Failed to send Tick sub command: {}", e);
-                        }
-                    }
-                }
-            } else {
-                info!("✋ [SUB SKIP] Room {} already exists, assuming worker subscribed.", room_name); // ✨ Debug Log
+    // 2. Normalize Address (Preserve case for SOL, lowercase for EVM)
+    let address = normalize_address(pool_id, &payload.address);
+    if address.trim().is_empty() {
+        s.emit("subscription_error", &serde_json::json!({ "action": "subscribe", "reason": "invalid_address" })).ok();
+        return Err("invalid_address");
+    }
+
+    let room_name = format!("kl@{}@{}@{}", pool_id, address, payload.interval);
+    if state.config.dedup_duplicate_subscribes && is_room_member(state, &room_name, s.id) {
+        // 重复订阅：房间与 Worker 订阅均已就绪，只需确认
+        s.emit("already_subscribed", &serde_json::json!({ "room": room_name })).ok();
+        return Ok(room_name);
+    }
+
+    // 先确保 Worker 可用 (也刷新其活跃时间)，达到上限时拒绝订阅
+    if ensure_token_worker(state, &address, pool_id).is_none() {
+        emit_worker_limit_error(s, "subscribe");
+        return Err("worker_limit");
+    }
+    
+    let symbol = lookup_token_symbol(state, &address)
+        .await
+        .unwrap_or_else(|| placeholder_symbol(&address));
+
+    let log_name = format!("kl@{}@{}@{}", pool_id, &symbol, payload.interval);
+
+    info!("🔔 [SUB] Client {} -> {}", s.id, log_name);
+    s.join(room_name.clone());
+    // 逐笔成交按代币推送 (与周期无关)，订阅任一周期即加入
+    s.join(trade_room(&address));
+
+    let is_new_room = !state.app_state.contains_key(&room_name);
+
+    let joined = state.app_state.entry(room_name.clone())
+        .or_insert_with(|| Room {
+            clients: HashSet::new(),
+            symbol: symbol.clone(),
+            current_kline: Arc::new(Mutex::new(None)),
+            last_emitted_kline: Arc::new(Mutex::new(None)),
+        })
+        .value_mut().clients.insert(s.id);
+    if joined {
+        crate::state::subscriber_counter(&state.token_subscribers, &address).fetch_add(1, Ordering::Relaxed);
+    }
+
+    let need_sub_tick = handle_index_subscription(state, &address, &room_name);
+
+    if is_new_room {
+        // Send Subscribe Command (TokenWorker already ensured above)
+        let kl_stream = format!("kl@{}@{}@{}", pool_id, address, payload.interval);
+        info!("📤 [CMD SEND] Subscribe Kline: {}", kl_stream); // ✨ Debug Log
+        send_worker_command(state, &address, pool_id, SubscriptionCommand::Subscribe(kl_stream));
+        
+        if need_sub_tick {
+            let tx_stream = format!("tx@{}_{}", pool_id, address);
+            info!("📤 [CMD SEND] Subscribe Tick: {}", tx_stream); // ✨ Debug Log
+            send_worker_command(state, &address, pool_id, SubscriptionCommand::Subscribe(tx_stream));
+        }
+    } else {
+        info!("✋ [SUB SKIP] Room {} already exists, assuming worker subscribed.", room_name); // ✨ Debug Log
+    }
+
+    Ok(room_name)
+}
+
+/// 预订阅热门代币：成交额达标的代币提前建房间、创建 Worker 并补齐历史，首个用户打开图表时无需等待。
+/// 预订阅房间没有客户端，记录在 presubscriptions 中由 prune_presubscriptions 按 TTL 回收
+fn presubscribe_trending(state: &ServerState, items: &[HotlistItem]) {
+    let Some(min_volume) = state.config.presubscribe_min_volume_usd else { return; };
+    let interval = state.config.presubscribe_interval.as_str();
+    let now = Utc::now().timestamp_millis();
+
+    let mut candidates: Vec<(f64, &HotlistItem)> = items
+        .iter()
+        .map(|item| (item.volume24h.unwrap_or(0.0) * item.price.unwrap_or(0.0), item))
+        .filter(|(volume, _)| *volume >= min_volume)
+        .collect();
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    for (_, item) in candidates.into_iter().take(state.config.presubscribe_max_tokens) {
+        let Some(pool_id) = chain_to_pool_id(&item.chain) else { continue; };
+        if item.contract_address.is_empty() {
+            continue;
+        }
+        let address = normalize_address(pool_id, &item.contract_address);
+        let room_name = format!("kl@{}@{}@{}", pool_id, address, interval);
+        state.presubscriptions.insert(room_name.clone(), now);
+        if state.app_state.contains_key(&room_name) {
+            continue;
+        }
+
+        // 预订阅只使用空闲容量，不淘汰已有 Worker
+        let max_workers = state.config.max_token_workers;
+        if !state.token_managers.contains_key(&address) && max_workers > 0 && state.token_managers.len() >= max_workers {
+            state.presubscriptions.remove(&room_name);
+            break;
+        }
+        if ensure_token_worker(state, &address, pool_id).is_none() {
+            state.presubscriptions.remove(&room_name);
+            continue;
+        }
+
+        info!("🔥 [PRESUB] Pre-subscribing trending {} ({})", item.symbol, room_name);
+        state.app_state.entry(room_name.clone()).or_insert_with(|| Room {
+            clients: HashSet::new(),
+            symbol: item.symbol.clone(),
+            current_kline: Arc::new(Mutex::new(None)),
+            last_emitted_kline: Arc::new(Mutex::new(None)),
+        });
+        let need_sub_tick = handle_index_subscription(state, &address, &room_name);
+        send_worker_command(state, &address, pool_id, SubscriptionCommand::Subscribe(room_name.clone()));
+        if need_sub_tick {
+            send_worker_command(state, &address, pool_id, SubscriptionCommand::Subscribe(format!("tx@{}_{}", pool_id, address)));
+        }
+
+        let payload = KlineSubscribePayload {
+            address: item.contract_address.clone(),
+            chain: item.chain.clone(),
+            interval: interval.to_string(),
+            fill_gaps: true,
+            no_cache: false,
+        };
+        kline_handler::spawn_kline_sync(state.clone(), payload, None);
+    }
+}
+
+/// 回收过期的预订阅：超过 TTL 未再出现在热门中的房间，若仍无真实订阅者则退订 (与客户端离开时的流程相同)；
+/// 已有订阅者的房间交由客户端生命周期管理。返回退订的房间数
+pub fn prune_presubscriptions(state: &ServerState, now: i64) -> usize {
+    let ttl_ms = i64::try_from(state.config.presubscribe_ttl.as_millis()).unwrap_or(i64::MAX);
+    let expired: Vec<String> = state
+        .presubscriptions
+        .iter()
+        .filter(|entry| now - *entry.value() > ttl_ms)
+        .map(|entry| entry.key().clone())
+        .collect();
+
+    let mut released = 0;
+    for room_name in expired {
+        state.presubscriptions.remove(&room_name);
+        if state.app_state.remove_if(&room_name, |_, room| room.clients.is_empty()).is_none() {
+            continue;
+        }
+        let parts: Vec<&str> = room_name.split('@').collect();
+        if parts.len() != 4 {
+            continue;
+        }
+        let pool_id = parts[1].parse::<i64>().unwrap_or(0);
+        let address = parts[2].to_string();
+        send_worker_command(state, &address, pool_id, SubscriptionCommand::Unsubscribe(room_name.clone()));
+        if handle_index_unsubscription(state, &address, &room_name) {
+            schedule_lazy_tick_unsubscribe(state.clone(), address, pool_id);
+        }
+        released += 1;
+    }
+    released
+}
+
+/// 查询代币 symbol：先查内存映射，未命中再查 tokens 表并回填内存
+async fn lookup_token_symbol(state: &ServerState, address: &str) -> Option<String> {
+    if let Some(symbol) = state.token_symbols.get(address) {
+        return Some(symbol.value().clone());
+    }
+    match kline_handler::get_token(&state.read_pool, address).await {
+        Ok(Some(token)) => {
+            if let Some(decimals) = token.decimals {
+                state.token_decimals.insert(token.address.clone(), decimals);
             }
+            state.token_symbols.insert(token.address, token.symbol.clone());
+            Some(token.symbol)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            warn!("⚠️ [TOKENS] Lookup failed for {}: {}", address, e);
+            None
+        }
+    }
+}
+
+/// 后台批量写入代币元数据，不阻塞 data-update 处理
+fn persist_token_metadata(state: &ServerState, tokens: Vec<TokenMetadata>) {
+    if tokens.is_empty() {
+        return;
+    }
+    let pool = state.db_pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = kline_handler::upsert_tokens(&pool, &tokens).await {
+            error!("❌ [TOKENS] Failed to persist {} tokens: {}", tokens.len(), e);
         }
     });
 }
 
+fn meme_token_metadata(item: &MemeScanItem) -> TokenMetadata {
+    TokenMetadata {
+        chain: item.chain.clone(),
+        address: item.contract_address.to_lowercase(),
+        symbol: item.symbol.clone(),
+        decimals: Some(item.decimal),
+        icon: item.icon.clone(),
+        last_seen: Utc::now().timestamp_millis(),
+    }
+}
+
 fn register_kline_unsubscribe_handler(socket: &SocketRef, state: ServerState) {
     socket.on("unsubscribe_kline", move |s: SocketRef, Data(payload): Data<KlineSubscribePayload>| {
         let state = state.clone();
         async move {
+            // 退订只释放资源，不限流 (被丢弃的退订会让房间与 Worker 一直挂着)
             // 1. Calculate pool_id FIRST
-            let pool_id = match payload.chain.to_lowercase().as_str() {
-                "bsc" => 14, "sol" | "solana" => 16, "base" => 199, _ => return,
-            };
+            let Some(pool_id) = chain_to_pool_id(&payload.chain) else { return; };
 
             // 2. Normalize Address
             let address = normalize_address(pool_id, &payload.address);
@@ -246,61 +717,219 @@ fn register_kline_unsubscribe_handler(socket: &SocketRef, state: ServerState) {
             let mut room_empty = false;
             // Remove from app_state
             if let Some(mut room) = state.app_state.get_mut(&room_name) {
-                room.clients.remove(&s.id);
+                if room.clients.remove(&s.id) {
+                    release_token_subscriber(&state, &address);
+                }
                 room_empty = room.clients.is_empty();
             }
 
+            // 该代币已没有订阅的周期：离开成交房间
+            if !is_token_subscriber(&state, &address, s.id) {
+                s.leave(trade_room(&address));
+            }
+
             if room_empty {
-                state.app_state.remove(&room_name);
-                let kl_stream = format!("kl@{}@{}@{}", pool_id, address, payload.interval);
-                
-                if let Some(sender) = state.token_managers.get(&address) {
-                    let _ = sender.send(SubscriptionCommand::Unsubscribe(kl_stream));
-                }
+                schedule_empty_room_removal(&state, room_name);
+            }
+        }
+    });
+}
 
-                if handle_index_unsubscription(&state, &address, &room_name) {
-                    info!("⏳ [LAZY START] No subscribers for {}. Scheduling unsub in {}s...", address, LAZY_UNSUBSCRIBE_DELAY);
-                    schedule_lazy_tick_unsubscribe(state.clone(), address, pool_id);
-                }
+/// 该 socket 是否仍在该代币的任一 K 线房间中
+fn is_token_subscriber(state: &ServerState, address: &str, sid: Sid) -> bool {
+    state
+        .room_index
+        .get(address)
+        .is_some_and(|rooms| rooms.iter().any(|room_name| is_room_member(state, room_name, sid)))
+}
+
+fn depth_room(address: &str) -> String {
+    format!("depth@{}", address)
+}
+
+/// 代币的逐笔成交房间 trades@{address} (地址已规范化)
+pub fn trade_room(address: &str) -> String {
+    format!("trades@{}", address)
+}
+
+// 盘口订阅：房间 depth@{address}，首个订阅者触发 Worker 订阅 depth 流，最后一个离开时退订
+fn register_depth_handlers(socket: &SocketRef, state: ServerState) {
+    let sub_state = state.clone();
+    socket.on("subscribe_depth", move |s: SocketRef, Data(payload): Data<DepthSubscribePayload>| {
+        let state = sub_state.clone();
+        async move {
+            if !allow_subscription_op(&s, &state, "subscribe_depth") {
+                return;
+            }
+            let Some(pool_id) = chain_to_pool_id(&payload.chain) else { return; };
+            let address = normalize_address(pool_id, &payload.address);
+            if ensure_token_worker(&state, &address, pool_id).is_none() {
+                emit_worker_limit_error(&s, "subscribe_depth");
+                return;
+            }
+            s.join(depth_room(&address));
+
+            let is_first = {
+                let mut entry = state.depth_subscriptions.entry(address.clone()).or_insert_with(|| (pool_id, HashSet::new()));
+                let is_first = entry.1.is_empty();
+                entry.1.insert(s.id);
+                is_first
+            };
+            if is_first {
+                info!("📚 [DEPTH SUB] {} -> {}", s.id, depth_room(&address));
+                let depth_stream = format!("depth@{}_{}", pool_id, address);
+                send_worker_command(&state, &address, pool_id, SubscriptionCommand::Subscribe(depth_stream));
             }
         }
     });
+
+    socket.on("unsubscribe_depth", move |s: SocketRef, Data(payload): Data<DepthSubscribePayload>| {
+        let state = state.clone();
+        async move {
+            // 与 unsubscribe_kline 一样不限流
+            let Some(pool_id) = chain_to_pool_id(&payload.chain) else { return; };
+            let address = normalize_address(pool_id, &payload.address);
+            s.leave(depth_room(&address));
+            release_depth_subscription(&state, &address, s.id);
+        }
+    });
+}
+
+/// 移除客户端的盘口订阅，无人订阅时通知 Worker 退订 depth 流
+fn release_depth_subscription(state: &ServerState, address: &str, sid: Sid) {
+    let Some(pool_id) = state
+        .depth_subscriptions
+        .remove_if_mut(address, |_, (_, clients)| clients.remove(&sid) && clients.is_empty())
+        .map(|(_, (pool_id, _))| pool_id)
+    else {
+        return;
+    };
+    info!("📚 [DEPTH UNSUB] No subscribers left for {}", depth_room(address));
+    let depth_stream = format!("depth@{}_{}", pool_id, address);
+    send_worker_command(state, address, pool_id, SubscriptionCommand::Unsubscribe(depth_stream));
 }
 
 fn register_disconnect_handler(socket: &SocketRef, state: ServerState) {
     socket.on_disconnect(move |s: SocketRef| {
         let state = state.clone();
         async move {
-            let mut empty_rooms = Vec::new();
-            for mut entry in state.app_state.iter_mut() {
-                if entry.value_mut().clients.remove(&s.id) && entry.value().clients.is_empty() {
-                    empty_rooms.push(entry.key().clone());
-                }
-            }
+            release_socket_rooms(&state, s.id);
+            state.subscribe_rate_limits.remove(&s.id);
+        }
+    });
+}
 
-            for room_name in empty_rooms {
-                if let Some(_) = state.app_state.remove(&room_name) {
-                    let parts: Vec<&str> = room_name.split('@').collect();
-                    if parts.len() == 4 {
-                        let pool_id = parts[1].parse::<i64>().unwrap_or(0);
-                        let address = parts[2].to_string(); // Already normalized in room key
-                        let interval = parts[3];
+// 页面跳转时前端一次性退订全部房间，避免逐个 unsubscribe_kline 与 Lazy 定时器竞争
+fn register_unsubscribe_all_handler(socket: &SocketRef, state: ServerState) {
+    socket.on("unsubscribe_all", move |s: SocketRef| {
+        let state = state.clone();
+        async move {
+            info!("📤 [UNSUB ALL] Client {} leaving all rooms", s.id);
+            s.leave_all();
+            release_socket_rooms(&state, s.id);
+        }
+    });
+}
 
-                        let kl_stream = format!("kl@{}@{}@{}", pool_id, address, interval);
-                         if let Some(sender) = state.token_managers.get(&address) {
-                            let _ = sender.send(SubscriptionCommand::Unsubscribe(kl_stream));
-                        }
+/// 调试用：返回服务端记录的该 socket 的 K 线订阅，便于与前端订阅状态对账
+fn register_list_subscriptions_handler(socket: &SocketRef, state: ServerState) {
+    socket.on("list_subscriptions", move |s: SocketRef| {
+        let state = state.clone();
+        async move {
+            let mut subscriptions: Vec<serde_json::Value> = state
+                .app_state
+                .iter()
+                .filter(|entry| entry.value().clients.contains(&s.id))
+                .filter_map(|entry| {
+                    let parts: Vec<&str> = entry.key().split('@').collect();
+                    let [_, pool_id, address, interval] = parts[..] else { return None; };
+                    let chain = pool_id.parse().ok().and_then(pool_id_to_chain)?;
+                    Some(serde_json::json!({
+                        "address": address,
+                        "chain": chain,
+                        "interval": interval
+                    }))
+                })
+                .collect();
+            subscriptions.sort_by_key(|v| v.to_string());
+            s.emit("subscriptions", &serde_json::json!({ "subscriptions": subscriptions })).ok();
+        }
+    });
+}
 
-                        if handle_index_unsubscription(&state, &address, &room_name) {
-                            schedule_lazy_tick_unsubscribe(state.clone(), address, pool_id);
-                        }
-                    }
-                }
+/// 将 socket 从所有房间中移除，空房间触发 Worker 退订及 Lazy Tick 退订
+fn release_socket_rooms(state: &ServerState, sid: Sid) {
+    let depth_addresses: Vec<String> = state
+        .depth_subscriptions
+        .iter()
+        .filter(|entry| entry.value().1.contains(&sid))
+        .map(|entry| entry.key().clone())
+        .collect();
+    for address in depth_addresses {
+        release_depth_subscription(state, &address, sid);
+    }
+
+    let mut empty_rooms = Vec::new();
+    let mut left_rooms = Vec::new();
+    for mut entry in state.app_state.iter_mut() {
+        if entry.value_mut().clients.remove(&sid) {
+            left_rooms.push(entry.key().clone());
+            if entry.value().clients.is_empty() {
+                empty_rooms.push(entry.key().clone());
             }
         }
+    }
+    // 遍历结束后再更新计数，避免持有 app_state 分片锁时访问其他 map
+    for room_name in left_rooms {
+        if let Some(address) = room_name.split('@').nth(2) {
+            release_token_subscriber(state, address);
+        }
+    }
+
+    for room_name in empty_rooms {
+        schedule_empty_room_removal(state, room_name);
+    }
+}
+
+/// 客户端离开该 Token 的一个房间：订阅者计数减一
+fn release_token_subscriber(state: &ServerState, address: &str) {
+    if let Some(counter) = state.token_subscribers.get(address) {
+        let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+}
+
+/// 房间变空后延迟 `empty_room_grace` 再移除，宽限期内重新订阅直接复用房间 (Worker 仍在推送，K 线状态不丢失)
+fn schedule_empty_room_removal(state: &ServerState, room_name: String) {
+    let grace = state.config.empty_room_grace;
+    if grace.is_zero() {
+        remove_room_if_empty(state, &room_name);
+        return;
+    }
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        remove_room_if_empty(&state, &room_name);
     });
 }
 
+/// 移除仍然没有客户端的房间 (room_name: kl@poolId@address@interval)：通知 Worker 退订 K 线流，
+/// 该地址已无其他房间时安排 Lazy Tick 退订
+fn remove_room_if_empty(state: &ServerState, room_name: &str) {
+    if state.app_state.remove_if(room_name, |_, room| room.clients.is_empty()).is_none() {
+        return;
+    }
+    let parts: Vec<&str> = room_name.split('@').collect();
+    let [_, pool_id, address, _] = parts[..] else { return; };
+    let pool_id = pool_id.parse::<i64>().unwrap_or(0);
+    // Already normalized in room key
+    send_worker_command(state, address, pool_id, SubscriptionCommand::Unsubscribe(room_name.to_string()));
+
+    if handle_index_unsubscription(state, address, room_name) {
+        info!("⏳ [LAZY START] No subscribers for {}. Scheduling unsub in {}s...", address, LAZY_UNSUBSCRIBE_DELAY);
+        schedule_lazy_tick_unsubscribe(state.clone(), address.to_string(), pool_id);
+    }
+}
+
 fn register_kline_history_handler(socket: &SocketRef, state: ServerState) {
     socket.on("request_historical_kline", move |s: SocketRef, payload: Data<KlineSubscribePayload>| {
         let state = state.clone();
@@ -320,195 +949,320 @@ fn register_narrative_handler(socket: &SocketRef, state: ServerState) {
         let state = state.clone();
         async move {
             let addr = payload.address.to_lowercase();
-            // 1. 检查缓存
-            if let Some(cached) = state.narrative_cache.get(&addr) {
-                if !cached.is_empty() && cached.as_str() != "__PENDING__" {
-                    s.emit("narrative_response", &serde_json::json!({
-                        "address": payload.address,
-                        "narrative": cached.clone()
-                    })).ok();
+            // 1. 有缓存先返回 (即使已过期，刷新结果到达后再推送一次)
+            let cached = state.narrative_cache.get(&addr).and_then(|e| e.latest().map(str::to_owned));
+            if let Some(narrative) = cached {
+                s.emit("narrative_response", &serde_json::json!({
+                    "address": payload.address,
+                    "narrative": narrative
+                })).ok();
+            }
+
+            // 2. 未缓存或已过期时占位抓取，与 enrich_any_data 共用占位，不会重复请求
+            match claim_narrative_fetch(&state.narrative_cache, addr, state.config.narrative_refresh_after) {
+                NarrativeClaim::Hit => {
+                    state.narrative_stats.hits.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                NarrativeClaim::Pending => {
+                    state.narrative_stats.pending_skips.fetch_add(1, Ordering::Relaxed);
                     return;
                 }
+                NarrativeClaim::Claimed => { state.narrative_stats.misses.fetch_add(1, Ordering::Relaxed); }
             }
-
-            // 2. 缓存未击中，发起抓取
             let proxy_pool = state.narrative_proxy_pool.clone();
-            let chain_lower = payload.chain.to_lowercase();
-            let cid_str = match chain_lower.as_str() {
-                "bsc" => Some("56".to_string()),
-                "sol" | "solana" => Some("CT_501".to_string()), // 假设 Solana 的叙事 ID 是这个，或者保留原始逻辑
-                "base" => Some("8453".to_string()),
-                _ => get_chain_id(&chain_lower).map(|id| id.to_string()),
-            };
+            let cid_str = state.config.narrative_chain_id(&payload.chain);
 
             if let Some(cid) = cid_str {
                 let cache = state.narrative_cache.clone();
                 let address_to_fetch = payload.address.clone();
+                let api_url = state.config.narrative_api_url.clone();
+                let stats = state.narrative_stats.clone();
+                let history_size = state.config.narrative_history_size;
                 
                 tokio::spawn(async move {
                     let (_idx, client) = proxy_pool.get_client().await;
-                    match fetch_narrative(&client, &address_to_fetch, &cid).await {
+                    match fetch_narrative(&client, &api_url, &address_to_fetch, &cid).await {
                         Ok(Some(t)) => {
-                            cache.insert(address_to_fetch.to_lowercase(), t.clone());
+                            stats.fetch_ok.fetch_add(1, Ordering::Relaxed);
+                            let now = Utc::now().timestamp_millis();
+                            cache.entry(address_to_fetch.to_lowercase()).or_default().push(t.clone(), now, history_size);
                             s.emit("narrative_response", &serde_json::json!({
                                 "address": address_to_fetch,
                                 "narrative": t
                             })).ok();
                         }
                         Ok(None) => {
-                            cache.insert(address_to_fetch.to_lowercase(), "".into());
+                            stats.fetch_ok.fetch_add(1, Ordering::Relaxed);
+                            let now = Utc::now().timestamp_millis();
+                            cache.entry(address_to_fetch.to_lowercase()).or_default().push(String::new(), now, history_size);
                         }
                         Err(e) => {
+                            stats.fetch_errors.fetch_add(1, Ordering::Relaxed);
                             warn!("❌ [Narrative Request ERR] {}: {}", address_to_fetch, e);
+                            release_narrative_claim(&cache, &address_to_fetch.to_lowercase());
                         }
                     }
                 });
+            } else {
+                // 该链没有叙事接口：记为已检查，过期前不再占位
+                let now = Utc::now().timestamp_millis();
+                state.narrative_cache.entry(payload.address.to_lowercase()).or_default().push(String::new(), now, state.config.narrative_history_size);
             }
         }
     });
 }
 
-// ✨✨✨ 核心更新：匹配新的 DataPayload 枚举 ✨✨✨
-fn register_data_update_handler(socket: &SocketRef, state: ServerState) {
-    socket.on("data-update", move |s: SocketRef, payload: Data<serde_json::Value>| {
+/// 返回某地址缓存中的叙事历史 (旧 -> 新)，不触发抓取
+fn register_narrative_history_handler(socket: &SocketRef, state: ServerState) {
+    socket.on("request_narrative_history", move |s: SocketRef, Data(payload): Data<KlineSubscribePayload>| {
         let state = state.clone();
         async move {
-            match serde_json::from_value::<DataPayload>(payload.0) {
-                Ok(mut parsed_payload) => {
-                    let mut should_broadcast = false;
-                    let log_summary = String::new();
-
-                    match &mut parsed_payload {
-                        // 1. 处理 Hotlist (HotlistItem 结构体)
-                        DataPayload::Hotlist { r#type: _, data } => {
-                            let mut liquidity_items = Vec::new();
-                            // 记录 Symbol 映射以及流动性历史存储 (在过滤之前执行，确保数据连续性)
-                            for item in data.iter() {
-                                state.token_symbols.insert(item.contract_address.to_lowercase(), item.symbol.clone());
-                                
-                                if let Some(liq) = item.liquidity {
-                                    liquidity_items.push((item.contract_address.clone(), liq));
-                                }
-                            }
-
-                            if !liquidity_items.is_empty() {
-                                let db_pool = state.db_pool.clone();
-                                tokio::spawn(async move {
-                                    if let Err(e) = kline_handler::record_liquidity_batch(&db_pool, liquidity_items).await {
-                                        warn!("⚠️ [DB BATCH ERR] Event=Hotlist, err={}", e);
-                                    }
-                                });
-                            }
+            let history: Vec<TimedNarrative> = state
+                .narrative_cache
+                .get(&payload.address.to_lowercase())
+                .map(|e| e.history.iter().cloned().collect())
+                .unwrap_or_default();
+            s.emit("narrative_history", &serde_json::json!({
+                "address": payload.address,
+                "history": history
+            })).ok();
+        }
+    });
+}
 
-                            if ENABLE_FILTERING {
-                                // 过滤逻辑
-                                let now = Utc::now().timestamp_millis();
-                                let thirty_mins_ms = 30 * 60 * 1000;
-                                data.retain(|item| {
-                                    let amount_ok = (item.volume24h.unwrap_or(0.0) * item.price.unwrap_or(0.0)) >= MIN_HOTLIST_AMOUNT;
-                                    let time_ok = match item.create_time {
-                                        Some(ct) => (now - ct) >= thirty_mins_ms,
-                                        None => true, // 如果没传创建时间，默认保留
-                                    };
-                                    let liquidity_ok = item.liquidity.unwrap_or(0.0) > MIN_HOTLIST_LIQUIDITY;
-                                    amount_ok && time_ok && liquidity_ok
-                                });
-                            }
+// ✨✨✨ 核心更新：匹配新的 DataPayload 枚举 ✨✨✨
+/// 处理一条爬虫推送 (symbol/流动性记录、过滤、缓存、报警)，返回是否需要广播。
+/// 实时 data-update 与回放模式 (replay) 共用这段逻辑
+pub async fn process_data_update(state: &ServerState, payload: &mut DataPayload) -> bool {
+    let mut should_broadcast = false;
 
-                            // ✨ Stage 2: 黑名单过滤 (手动过滤)
-                            data.retain(|item| !state.blacklist.contains(&item.contract_address.to_lowercase()));
-
-                            should_broadcast = !data.is_empty();
-                            //log_summary = format!("🔥 [HOTLIST] Act: {:?} | Count: {}", r#type, data.len());
-                            
-                            // 🔥 Hotlist 不需要 Narrative，直接跳过
-                            // enrich_any_data(data, &state).await;
-                            
-                            // 🔥 新增：报警检测
-                            crate::alert_handler::check_and_trigger_alerts(data, &state, &state.io).await;
-                        }
-                        
-                        // 2. 处理 New Meme (MemeScanItem 结构体)
-                        DataPayload::MemeNew { r#type: _, data } => {
-                            let mut liquidity_items = Vec::new();
-                            // 记录 Symbol 映射以及流动性历史存储
-                            for item in data.iter() {
-                                state.token_symbols.insert(item.contract_address.to_lowercase(), item.symbol.clone());
-
-                                if let Some(liq) = item.liquidity {
-                                    liquidity_items.push((item.contract_address.clone(), liq));
-                                }
-                            }
+    match payload {
+        // 1. 处理 Hotlist (HotlistItem 结构体)
+        DataPayload::Hotlist { r#type, data } => {
+            let mut liquidity_items = Vec::new();
+            let now = Utc::now().timestamp_millis();
+            let mut tokens = Vec::with_capacity(data.len());
+            // 记录 Symbol 映射以及流动性历史存储 (在过滤之前执行，确保数据连续性)
+            for item in data.iter() {
+                state.token_symbols.insert(item.contract_address.to_lowercase(), item.symbol.clone());
+                tokens.push(TokenMetadata {
+                    chain: item.chain.clone(),
+                    address: item.contract_address.to_lowercase(),
+                    symbol: item.symbol.clone(),
+                    decimals: None,
+                    icon: item.icon.clone(),
+                    last_seen: now,
+                });
 
-                            if !liquidity_items.is_empty() {
-                                let db_pool = state.db_pool.clone();
-                                tokio::spawn(async move {
-                                    if let Err(e) = kline_handler::record_liquidity_batch(&db_pool, liquidity_items).await {
-                                        warn!("⚠️ [DB BATCH ERR] Event=MemeNew, err={}", e);
-                                    }
-                                });
-                            }
+                if let Some(liq) = item.liquidity {
+                    liquidity_items.push((item.contract_address.clone(), liq));
+                }
+            }
 
-                            // ✨ Stage 2: 黑名单过滤
-                            data.retain(|item| !item.symbol.is_empty() && !state.blacklist.contains(&item.contract_address.to_lowercase()));
-                            
-                            
-                            // 🔥 Debug Logic: 打印收到的 Meme 完整信息
-                            // for item in data.iter() {
-                            //     info!("📦 [MemeNew Received] Detailed Item: {:?}", item);
-                            // }
-
-                            // 🔥 调用泛型 Enrich 函数 (MemeScanItem 实现了 NarrativeEntity)
-                            // enrich_any_data(data, &state).await;
-                            
-                            should_broadcast = !data.is_empty();
-                            //log_summary = format!("🐶 [MEME RUSH] Act: {:?} | Count: {}", r#type, data.len());
-                        }
-                        
-                        // 3. 处理 Migrated Meme (MemeScanItem 结构体)
-                        DataPayload::MemeMigrated { r#type: _, data } => {
-                            let mut liquidity_items = Vec::new();
-                            // 记录 Symbol 映射以及流动性历史存储
-                            for item in data.iter() {
-                                state.token_symbols.insert(item.contract_address.to_lowercase(), item.symbol.clone());
-
-                                if let Some(liq) = item.liquidity {
-                                    liquidity_items.push((item.contract_address.clone(), liq));
-                                }
-                            }
+            if !liquidity_items.is_empty() {
+                // 先进入内存缓冲，由定时任务批量落库 (退出时也会强制刷新)
+                state.pending_liquidity.lock().await.extend(liquidity_items);
+            }
+            persist_token_metadata(state, tokens);
 
-                            if !liquidity_items.is_empty() {
-                                let db_pool = state.db_pool.clone();
-                                tokio::spawn(async move {
-                                    if let Err(e) = kline_handler::record_liquidity_batch(&db_pool, liquidity_items).await {
-                                        warn!("⚠️ [DB BATCH ERR] Event=MemeMigrated, err={}", e);
-                                    }
-                                });
-                            }
+            // 增量更新涉及的地址 (过滤前)：被过滤掉的行也要从快照中移除，否则快照会一直保留它的旧数据
+            let delta_addrs: Option<HashSet<String>> = (*r#type == DataAction::Update)
+                .then(|| data.iter().map(|item| item.contract_address.to_lowercase()).collect());
 
-                            // ✨ Stage 2: 黑名单过滤
-                            data.retain(|item| !item.symbol.is_empty() && !state.blacklist.contains(&item.contract_address.to_lowercase()));
-                            
-                            
-                            // 🔥 Debug Logic: 打印收到的 MemeMigrated 完整信息
-                            // for item in data.iter() {
-                            //     info!("🚀 [MemeMigrated Received] Detailed Item: {:?}", item);
-                            // }
-
-                            // 🔥 调用泛型 Enrich 函数
-                            // enrich_any_data(data, &state).await;
-                            
-                            should_broadcast = !data.is_empty();
-                            //log_summary = format!("🚀 [MEME MIGRATED] Act: {:?} | Count: {}", r#type, data.len());
-                        }
-                        _ => {}
+            if ENABLE_FILTERING {
+                // 过滤逻辑
+                let now = Utc::now().timestamp_millis();
+                let thirty_mins_ms = 30 * 60 * 1000;
+                data.retain(|item| {
+                    // 白名单代币不受阈值过滤
+                    if state.whitelist.contains(&item.contract_address.to_lowercase()) {
+                        return true;
                     }
+                    let amount_ok = (item.volume24h.unwrap_or(0.0) * item.price.unwrap_or(0.0)) >= MIN_HOTLIST_AMOUNT;
+                    let time_ok = match item.create_time {
+                        Some(ct) => (now - ct) >= thirty_mins_ms,
+                        None => true, // 如果没传创建时间，默认保留
+                    };
+                    let liquidity_ok = item.liquidity.unwrap_or(0.0) > MIN_HOTLIST_LIQUIDITY;
+                    amount_ok && time_ok && liquidity_ok
+                });
+            }
+
+            // ✨ Stage 2: 黑名单过滤 (手动过滤)
+            data.retain(|item| !state.blacklist.contains(&item.contract_address.to_lowercase()));
+
+            //log_summary = format!("🔥 [HOTLIST] Act: {:?} | Count: {}", r#type, data.len());
+
+            // 🔥 Hotlist 不需要 Narrative，直接跳过
+            // enrich_any_data(data, state).await;
+
+            // 🔥 新增：报警检测 (增量只检测变化的行)
+            crate::alert_handler::check_and_trigger_alerts(data, state, &state.io).await;
+
+            // 热门代币预订阅 (图表秒开)
+            presubscribe_trending(state, data);
+
+            // 服务端维护完整快照：快照/全量整体替换；增量按地址合并后广播合并后的全量，
+            // 避免客户端用只含变化行的增量覆盖自己的完整列表
+            let mut cached = state.last_hotlist.lock().await;
+            match delta_addrs {
+                Some(delta_addrs) => {
+                    let kept: HashSet<String> = data.iter().map(|item| item.contract_address.to_lowercase()).collect();
+                    cached.retain(|item| {
+                        let addr = item.contract_address.to_lowercase();
+                        !delta_addrs.contains(&addr) || kept.contains(&addr)
+                    });
+                    merge_items_by_address(&mut cached, std::mem::take(data));
+                    *data = cached.clone();
+                    *r#type = DataAction::Full;
+                    should_broadcast = !delta_addrs.is_empty();
+                }
+                None => {
+                    // 过滤后为空的快照同样替换缓存 (否则新连接仍拿到已不在热门中的旧列表)；
+                    // 空快照只在清空了原有列表时广播
+                    should_broadcast = !data.is_empty() || !cached.is_empty();
+                    *cached = data.clone();
+                }
+            }
+        }
+
+        // 2. 处理 New Meme (MemeScanItem 结构体)
+        DataPayload::MemeNew { r#type: _, data } => {
+            let mut liquidity_items = Vec::new();
+            let tokens = data.iter().map(meme_token_metadata).collect();
+            // 记录 Symbol 映射以及流动性历史存储
+            for item in data.iter() {
+                state.token_symbols.insert(item.contract_address.to_lowercase(), item.symbol.clone());
+                state.token_decimals.insert(item.contract_address.to_lowercase(), item.decimal);
+
+                if let Some(liq) = item.liquidity {
+                    liquidity_items.push((item.contract_address.clone(), liq));
+                }
+            }
+
+            if !liquidity_items.is_empty() {
+                // 先进入内存缓冲，由定时任务批量落库 (退出时也会强制刷新)
+                state.pending_liquidity.lock().await.extend(liquidity_items);
+            }
+            persist_token_metadata(state, tokens);
+
+            // ✨ Stage 2: 黑名单过滤
+            data.retain(|item| !item.symbol.is_empty() && !state.blacklist.contains(&item.contract_address.to_lowercase()));
+
+
+            // 🔥 Debug Logic: 打印收到的 Meme 完整信息
+            // for item in data.iter() {
+            //     info!("📦 [MemeNew Received] Detailed Item: {:?}", item);
+            // }
+
+            // 🔥 调用泛型 Enrich 函数 (MemeScanItem 实现了 NarrativeEntity)：已缓存的叙事直接回填，
+            // 未缓存的后台抓取，完成后以 narrative_update 补推 (broadcast_narrative_updates)
+            enrich_any_data(data, state).await;
+
+            // 🔥 新增：买压报警
+            crate::alert_handler::check_buy_pressure_alerts(data, state, &state.io).await;
+            crate::alert_handler::check_meme_rule_alerts(data, state, &state.io).await;
+
+            should_broadcast = !data.is_empty();
+            //log_summary = format!("🐶 [MEME RUSH] Act: {:?} | Count: {}", r#type, data.len());
+        }
+
+        // 3. 处理 Migrated Meme (MemeScanItem 结构体)
+        DataPayload::MemeMigrated { r#type: _, data } => {
+            let mut liquidity_items = Vec::new();
+            let tokens = data.iter().map(meme_token_metadata).collect();
+            // 记录 Symbol 映射以及流动性历史存储
+            for item in data.iter() {
+                state.token_symbols.insert(item.contract_address.to_lowercase(), item.symbol.clone());
+                state.token_decimals.insert(item.contract_address.to_lowercase(), item.decimal);
+
+                if let Some(liq) = item.liquidity {
+                    liquidity_items.push((item.contract_address.clone(), liq));
+                }
+            }
+
+            if !liquidity_items.is_empty() {
+                // 先进入内存缓冲，由定时任务批量落库 (退出时也会强制刷新)
+                state.pending_liquidity.lock().await.extend(liquidity_items);
+            }
+            persist_token_metadata(state, tokens);
+
+            // ✨ Stage 2: 黑名单过滤
+            data.retain(|item| !item.symbol.is_empty() && !state.blacklist.contains(&item.contract_address.to_lowercase()));
+
+
+            // 🔥 Debug Logic: 打印收到的 MemeMigrated 完整信息
+            // for item in data.iter() {
+            //     info!("🚀 [MemeMigrated Received] Detailed Item: {:?}", item);
+            // }
+
+            // 🔥 调用泛型 Enrich 函数
+            enrich_any_data(data, state).await;
 
-                    if should_broadcast {
-                        if !log_summary.is_empty() {
-                            info!("{}", log_summary);
+            // 🔥 新增：迁移报警 (同一地址只报一次)
+            crate::alert_handler::check_migration_alerts(data, state, &state.io).await;
+            crate::alert_handler::check_buy_pressure_alerts(data, state, &state.io).await;
+            crate::alert_handler::check_meme_rule_alerts(data, state, &state.io).await;
+
+            should_broadcast = !data.is_empty();
+            //log_summary = format!("🚀 [MEME MIGRATED] Act: {:?} | Count: {}", r#type, data.len());
+        }
+
+        // 4. 处理单个代币详情刷新：只推送给订阅了该代币的房间，不走全局 data-broadcast
+        DataPayload::TokenDetail { r#type: _, data } => {
+            let addr_lower = data.contract_address.to_lowercase();
+            state.token_symbols.insert(addr_lower.clone(), data.symbol.clone());
+            state.token_decimals.insert(addr_lower.clone(), data.decimal);
+            persist_token_metadata(state, vec![meme_token_metadata(data)]);
+
+            if let Some(liq) = data.liquidity {
+                state.pending_liquidity.lock().await.push((data.contract_address.clone(), liq));
+            }
+
+            if !state.blacklist.contains(&addr_lower) {
+                if let Some(ns) = state.io.of(state.config.alerts_namespace.as_str()) {
+                    ns.to(token_detail_room(&addr_lower)).emit("token_detail", &*data).await.ok();
+                }
+            }
+        }
+
+        // 5. 代币下架：清理 symbol / 叙事 / 热门快照缓存后单独推送 token_removed，不走 data-broadcast
+        DataPayload::Remove { data } => {
+            let removed: HashSet<String> = data.iter().map(|t| t.address.to_lowercase()).collect();
+            for addr in &removed {
+                state.token_symbols.remove(addr);
+                state.token_decimals.remove(addr);
+                state.narrative_cache.remove(addr);
+            }
+            state.last_hotlist.lock().await.retain(|item| !removed.contains(&item.contract_address.to_lowercase()));
+            info!("🗑️ [TOKEN REMOVED] {} tokens delisted", removed.len());
+
+            if let Some(ns) = state.io.of(state.config.alerts_namespace.as_str()) {
+                ns.emit("token_removed", &*data).await.ok();
+            }
+        }
+        _ => {}
+    }
+
+    should_broadcast
+}
+
+fn register_data_update_handler(socket: &SocketRef, state: ServerState) {
+    socket.on("data-update", move |s: SocketRef, payload: Data<serde_json::Value>| {
+        let state = state.clone();
+        async move {
+            if let Some(path) = state.config.data_update_record_path.clone() {
+                crate::replay::record_data_update(path, payload.0.clone());
+            }
+            match serde_json::from_value::<DataPayload>(payload.0) {
+                Ok(mut parsed_payload) => {
+                    if process_data_update(&state, &mut parsed_payload).await {
+                        let category = parsed_payload.category();
+                        match state.config.broadcast_throttle.get(category) {
+                            Some(&window) => queue_throttled_broadcast(&state, &s, category, parsed_payload, window),
+                            None => { s.broadcast().emit("data-broadcast", &parsed_payload).await.ok(); }
                         }
-                        s.broadcast().emit("data-broadcast", &parsed_payload).await.ok();
                     }
                 }
                 Err(e) => warn!("❌ [JSON PARSE ERROR] Payload mismatch: {}", e),
@@ -517,6 +1271,103 @@ fn register_data_update_handler(socket: &SocketRef, state: ServerState) {
     });
 }
 
+/// 节流广播：窗口期内同一分类的 payload 合并 (按地址保留最新)，窗口结束时只广播一次
+fn queue_throttled_broadcast(
+    state: &ServerState,
+    s: &SocketRef,
+    category: &'static str,
+    payload: DataPayload,
+    window: Duration,
+) {
+    let schedule_flush = match state.pending_broadcasts.entry(category) {
+        Entry::Occupied(mut entry) => {
+            let pending = entry.get_mut();
+            merge_payload(&mut pending.payload, payload);
+            pending.sender = s.clone();
+            false
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(PendingBroadcast { payload, sender: s.clone() });
+            true
+        }
+    };
+
+    if schedule_flush {
+        let state = state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            if let Some((_, pending)) = state.pending_broadcasts.remove(category) {
+                pending.sender.broadcast().emit("data-broadcast", &pending.payload).await.ok();
+            }
+        });
+    }
+}
+
+/// 合并同一分类的 payload：增量更新按地址覆盖，快照/全量则整体替换
+fn merge_payload(pending: &mut DataPayload, incoming: DataPayload) {
+    match (pending, incoming) {
+        (DataPayload::Hotlist { data, .. }, DataPayload::Hotlist { r#type: DataAction::Update, data: new_data }) => {
+            merge_items_by_address(data, new_data);
+        }
+        (DataPayload::MemeNew { data, .. }, DataPayload::MemeNew { r#type: DataAction::Update, data: new_data }) => {
+            merge_items_by_address(data, new_data);
+        }
+        (DataPayload::MemeMigrated { data, .. }, DataPayload::MemeMigrated { r#type: DataAction::Update, data: new_data }) => {
+            merge_items_by_address(data, new_data);
+        }
+        (pending, incoming) => *pending = incoming,
+    }
+}
+
+fn merge_items_by_address<T: NarrativeEntity>(existing: &mut Vec<T>, incoming: Vec<T>) {
+    for item in incoming {
+        match existing.iter_mut().find(|e| e.get_address().eq_ignore_ascii_case(item.get_address())) {
+            Some(slot) => *slot = item,
+            None => existing.push(item),
+        }
+    }
+}
+
+/// 叙事缓存查询结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NarrativeClaim {
+    /// 已有抓取结果且未过期
+    Hit,
+    /// 其他调用正在抓取
+    Pending,
+    /// 本次调用写入了占位 (首次抓取或过期刷新)，由它负责抓取
+    Claimed,
+}
+
+/// 原子地检查并占位：并发的多个 payload 包含同一代币时，只有第一个拿到 Claimed。
+/// 已缓存的条目超过 refresh_after 视为过期，同样占位重新抓取 (保留已有历史)
+fn claim_narrative_fetch(cache: &NarrativeCache, addr: String, refresh_after: Option<Duration>) -> NarrativeClaim {
+    let now = Utc::now().timestamp_millis();
+    match cache.entry(addr) {
+        Entry::Occupied(e) if e.get().pending => NarrativeClaim::Pending,
+        Entry::Occupied(mut e) if e.get().is_stale(now, refresh_after) => {
+            e.get_mut().pending = true;
+            NarrativeClaim::Claimed
+        }
+        Entry::Occupied(_) => NarrativeClaim::Hit,
+        Entry::Vacant(v) => {
+            v.insert(NarrativeEntry::pending());
+            NarrativeClaim::Claimed
+        }
+    }
+}
+
+/// 抓取失败时释放占位：已有历史的条目保留 (下次出现时按过期再刷新)，没有历史的删除以便重试
+fn release_narrative_claim(cache: &NarrativeCache, addr: &str) {
+    if let Entry::Occupied(mut e) = cache.entry(addr.to_string()) {
+        if e.get().history.is_empty() {
+            e.remove();
+        } else {
+            e.get_mut().pending = false;
+        }
+    }
+}
+
 // ✨✨✨ 泛型 Enrich 函数 ✨✨✨
 // 使用 trait bound: T 必须实现 NarrativeEntity 且支持并发 (Send + Sync)
 async fn enrich_any_data<T>(items: &mut Vec<T>, state: &ServerState) 
@@ -527,10 +1378,14 @@ where T: NarrativeEntity + Send + Sync
     // 1. 扫描哪些需要抓取
     for (i, item) in items.iter().enumerate() {
         let addr = item.get_address().to_lowercase();
-        // 如果缓存没有这个 key，标记为待抓取
-        if !state.narrative_cache.contains_key(&addr) {
-            state.narrative_cache.insert(addr, "__PENDING__".to_string());
-            to_fetch.push(i);
+        // 缓存没有这个 key 或已过期，占位并标记为待抓取
+        match claim_narrative_fetch(&state.narrative_cache, addr, state.config.narrative_refresh_after) {
+            NarrativeClaim::Pending => { state.narrative_stats.pending_skips.fetch_add(1, Ordering::Relaxed); }
+            NarrativeClaim::Hit => { state.narrative_stats.hits.fetch_add(1, Ordering::Relaxed); }
+            NarrativeClaim::Claimed => {
+                state.narrative_stats.misses.fetch_add(1, Ordering::Relaxed);
+                to_fetch.push(i);
+            }
         }
     }
 
@@ -540,18 +1395,24 @@ where T: NarrativeEntity + Send + Sync
         let chain = items[idx].get_chain().to_string();
         let cache = state.narrative_cache.clone();
         let proxy_pool = state.narrative_proxy_pool.clone();
+        let api_url = state.config.narrative_api_url.clone();
+        let io = state.io.clone();
+        let alerts_ns = state.config.alerts_namespace.clone();
+        let broadcast_update = state.config.broadcast_narrative_updates;
+        let history_size = state.config.narrative_history_size;
+        let stats = state.narrative_stats.clone();
         
         // 错峰延时，避免瞬间打爆 API
-        let delay = std::time::Duration::from_millis(q_idx as u64 * 250);
+        let delay = narrative_fetch_delay(&state.config, q_idx, to_fetch.len());
 
         // 1. 确定 ChainID 
         // 优先使用 narrative_chain_id (如 CT_501)
-        // 如果没有，尝试使用旧的映射 (bsc -> 56)
+        // 如果没有，使用配置中的链映射 (bsc -> 56)
         let specific_cid = items[idx].get_narrative_chain_id();
         let final_cid = if let Some(id) = specific_cid {
             Some(id)
         } else {
-            get_chain_id(&chain).map(|id| id.to_string())
+            state.config.narrative_chain_id(&chain)
         };
 
         if let Some(cid) = final_cid {
@@ -559,41 +1420,65 @@ where T: NarrativeEntity + Send + Sync
                 tokio::time::sleep(delay).await;
                 let (client_idx, client) = proxy_pool.get_client().await;
                 
-                match fetch_narrative(&client, &addr, &cid).await {
+                match fetch_narrative(&client, &api_url, &addr, &cid).await {
                     Ok(Some(t)) => {
+                        stats.fetch_ok.fetch_add(1, Ordering::Relaxed);
                         info!("✅ [Fetch OK] {}: {:.15}...", addr, t);
-                        cache.insert(addr.to_lowercase(), t);
+                        let now = Utc::now().timestamp_millis();
+                        cache.entry(addr.to_lowercase()).or_default().push(t.clone(), now, history_size);
+                        // 首次广播时叙事尚未就绪，抓取完成后单独推送补丁
+                        if broadcast_update {
+                            if let Some(ns) = io.of(alerts_ns.as_str()) {
+                                ns.emit("narrative_update", &serde_json::json!({
+                                    "address": addr,
+                                    "narrative": t
+                                })).await.ok();
+                            }
+                        }
                     }
                     Ok(None) => { 
-                        // 没数据也缓存空字符串，避免重复请求
-                        cache.insert(addr.to_lowercase(), "".into()); 
+                        stats.fetch_ok.fetch_add(1, Ordering::Relaxed);
+                        // 没数据也保留缓存条目 (清除占位并记录抓取时间)，过期前不再重复请求
+                        let now = Utc::now().timestamp_millis();
+                        cache.entry(addr.to_lowercase()).or_default().push(String::new(), now, history_size);
                     }
                     Err(e) => {
+                        stats.fetch_errors.fetch_add(1, Ordering::Relaxed);
                         warn!("❌ [Fetch ERR] Client #{} failed for {}: {}. Recycling...", client_idx, addr, e);
-                        // 只有网络错误才回收连接并删除缓存 key (允许重试)
+                        // 只有网络错误才回收连接并释放占位 (允许重试)
                         proxy_pool.recycle_client(client_idx).await;
-                        cache.remove(&addr.to_lowercase());
+                        release_narrative_claim(&cache, &addr.to_lowercase());
                     }
                 }
             });
         } else {
-            cache.insert(addr.to_lowercase(), "".into());
+            let now = Utc::now().timestamp_millis();
+            cache.entry(addr.to_lowercase()).or_default().push(String::new(), now, history_size);
         }
     }
 
     // 3. 回填数据 (从缓存中读取)
     for item in items.iter_mut() {
         let addr = item.get_address().to_lowercase();
-        if let Some(t) = state.narrative_cache.get(&addr) {
-            if !t.is_empty() && t.as_str() != "__PENDING__" {
-                item.set_narrative(t.clone());
-            }
+        let latest = state.narrative_cache.get(&addr).and_then(|e| e.latest().map(str::to_owned));
+        if let Some(t) = latest {
+            item.set_narrative(t);
         }
     }
 }
 
-async fn fetch_narrative(client: &reqwest::Client, address: &str, chain_id: &str) -> anyhow::Result<Option<String>> {
-    let url = format!("{}?contractAddress={}&chainId={}", NARRATIVE_API_URL, address, chain_id);
+/// 一批 `total` 个叙事抓取中第 `q_idx` 个的延时：按 narrative_fetch_stagger 错峰，
+/// 总跨度超过 narrative_fetch_max_spread 时等比压缩
+fn narrative_fetch_delay(config: &Config, q_idx: usize, total: usize) -> Duration {
+    let mut step = config.narrative_fetch_stagger;
+    if total > 1 {
+        step = step.min(config.narrative_fetch_max_spread / (total - 1) as u32);
+    }
+    step * q_idx as u32
+}
+
+async fn fetch_narrative(client: &reqwest::Client, api_url: &str, address: &str, chain_id: &str) -> anyhow::Result<Option<String>> {
+    let url = format!("{}?contractAddress={}&chainId={}", api_url, address, chain_id);
     info!("🔗 [Narrative Req] URL: {}", url);
     let resp = client.get(&url)
         .header("ClientType", "web")
@@ -609,26 +1494,8 @@ async fn fetch_narrative(client: &reqwest::Client, address: &str, chain_id: &str
         .header("Pragma", "no-cache")
         .send().await?;
 
-    let bytes = resp.bytes().await?;
-    
-    // 自动检测 Gzip Magin Number (1f 8b)
-    let text_body = if bytes.len() >= 2 && bytes[0] == 0x1f && bytes[1] == 0x8b {
-        let mut d = GzDecoder::new(&bytes[..]);
-        let mut s = String::new();
-        match d.read_to_string(&mut s) {
-            Ok(_) => {
-                // info!("✅ [Gzip Decompressed] {} bytes -> {} chars", bytes.len(), s.len());
-                s
-            },
-            Err(e) => {
-                warn!("❌ [Gzip Error] Failed to decompress for {}: {}", address, e);
-                // 降级：如果解压失败，尝试直接当文本读
-                String::from_utf8_lossy(&bytes).to_string()
-            }
-        }
-    } else {
-        String::from_utf8_lossy(&bytes).to_string()
-    };
+    // 压缩 (gzip/deflate/br) 由 reqwest 根据 Content-Encoding 统一自动解码
+    let text_body = resp.text().await?;
 
     let body: NarrativeResponse = match serde_json::from_str(&text_body) {
         Ok(b) => b,
@@ -647,12 +1514,146 @@ async fn fetch_narrative(client: &reqwest::Client, address: &str, chain_id: &str
     Ok(None)
 }
 
-fn get_chain_id(chain: &str) -> Option<u64> {
-    match chain.to_lowercase().as_str() {
-        "bsc" => Some(56), "eth" | "ethereum" => Some(1), "base" => Some(8453),
-        "arb" | "arbitrum" => Some(42161), "matic" | "polygon" => Some(137),
-        "op" | "optimism" => Some(10), "avax" | "avalanche" => Some(43114),
-        _ => None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_manager::parse_tick_stream;
+    use std::collections::HashMap;
+
+    type Index = HashMap<String, HashSet<String>>;
+
+    const SOL_ADDRESS: &str = "7GCihgDB8fe6KNjn2MYtkzZcRjQy3t9GHdC8uHYmW2hr";
+    const EVM_ADDRESS: &str = "0xAbC0000000000000000000000000000000DeF123";
+
+    /// 模拟订阅流程：规范化地址，得到房间名与 Tick 流名，并写入 room_index
+    fn subscribe(chain: &str, address: &str, index: &Index) -> (String, String, Index) {
+        let pool_id = chain_to_pool_id(chain).unwrap();
+        let address = normalize_address(pool_id, address);
+        let room_key = format!("kl@{}@{}@{}", pool_id, address, "1m");
+        let tx_stream = format!("tx@{}_{}", pool_id, address);
+        let mut index = index.clone();
+        index.entry(address).or_default().insert(room_key.clone());
+        (room_key, tx_stream, index)
+    }
+
+    #[test]
+    fn normalize_address_lowercases_evm_only() {
+        assert_eq!(normalize_address(16, SOL_ADDRESS), SOL_ADDRESS);
+        for pool_id in [14, 199] {
+            assert_eq!(normalize_address(pool_id, EVM_ADDRESS), EVM_ADDRESS.to_lowercase());
+        }
+        // 幂等：已规范化的地址再次规范化不变
+        let normalized = normalize_address(14, EVM_ADDRESS);
+        assert_eq!(normalize_address(14, &normalized), normalized);
     }
-}
 
+    #[test]
+    fn chain_aliases_map_to_same_pool() {
+        assert_eq!(chain_to_pool_id("sol"), Some(16));
+        assert_eq!(chain_to_pool_id("Solana"), Some(16));
+        assert_eq!(chain_to_pool_id("BSC"), Some(14));
+        assert_eq!(chain_to_pool_id("base"), Some(199));
+        assert_eq!(chain_to_pool_id("eth"), None);
+    }
+
+    #[test]
+    fn solana_address_keeps_case_from_subscribe_to_tick_routing() {
+        let (room_key, tx_stream, index) = subscribe("sol", SOL_ADDRESS, &Index::new());
+        assert_eq!(room_key, format!("kl@16@{}@1m", SOL_ADDRESS));
+
+        // 同一地址经 "solana" 别名再次订阅得到相同房间
+        let (alias_room_key, _, _) = subscribe("solana", SOL_ADDRESS, &index);
+        assert_eq!(alias_room_key, room_key);
+
+        let (pool_id, tracked_address) = parse_tick_stream(&tx_stream).unwrap();
+        assert_eq!(pool_id, 16);
+        assert!(index.get(&tracked_address).is_some_and(|rooms| rooms.contains(&room_key)));
+        assert!(index.get(&tracked_address.to_lowercase()).is_none());
+    }
+
+    #[test]
+    fn evm_address_is_lowercased_everywhere() {
+        let (room_key, tx_stream, index) = subscribe("bsc", EVM_ADDRESS, &Index::new());
+        let lower = EVM_ADDRESS.to_lowercase();
+        assert_eq!(room_key, format!("kl@14@{}@1m", lower));
+        assert_eq!(tx_stream, format!("tx@14_{}", lower));
+
+        // 客户端用不同大小写订阅，落到同一房间
+        let (upper_room_key, _, _) = subscribe("bsc", &EVM_ADDRESS.to_uppercase().replacen("0X", "0x", 1), &index);
+        assert_eq!(upper_room_key, room_key);
+
+        let (_, tracked_address) = parse_tick_stream(&tx_stream).unwrap();
+        assert!(index.get(&tracked_address).is_some_and(|rooms| rooms.contains(&room_key)));
+
+        // 上游盘口流带原始大小写时也落到 subscribe_depth 的房间
+        let (_, depth_address) = parse_tick_stream(&format!("depth@14_{}", EVM_ADDRESS)).unwrap();
+        assert_eq!(depth_room(&depth_address), depth_room(&normalize_address(14, EVM_ADDRESS)));
+    }
+
+    #[test]
+    fn placeholder_symbol_handles_short_addresses() {
+        assert_eq!(placeholder_symbol(EVM_ADDRESS), "0xAbC0...");
+        assert_eq!(placeholder_symbol("ab"), "ab...");
+        assert_eq!(placeholder_symbol("代币地址"), "代币地址...");
+    }
+
+    #[test]
+    fn token_bucket_grants_partial_batches() {
+        let mut bucket = TokenBucket::new(10.0);
+        assert_eq!(bucket.take_up_to(10.0, 4), 4);
+        // 剩 6 个令牌：12 条的批量只放行 6 条
+        assert_eq!(bucket.take_up_to(10.0, 12), 6);
+        assert!(!bucket.try_take(10.0));
+    }
+
+    #[test]
+    fn narrative_fetch_delay_is_capped_for_large_batches() {
+        let config = Config::new();
+        // 小批量按 250ms 错峰
+        assert_eq!(narrative_fetch_delay(&config, 3, 5), Duration::from_millis(750));
+        // 40 个代币压缩到 3s 以内
+        assert!(narrative_fetch_delay(&config, 39, 40) <= Duration::from_secs(3));
+        assert_eq!(narrative_fetch_delay(&config, 0, 40), Duration::ZERO);
+    }
+
+    #[test]
+    fn narrative_claim_is_exclusive_across_threads() {
+        let cache = crate::state::new_narrative_cache();
+        let claims: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| s.spawn(|| claim_narrative_fetch(&cache, EVM_ADDRESS.to_lowercase(), None)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(claims.iter().filter(|c| **c == NarrativeClaim::Claimed).count(), 1);
+        assert_eq!(claims.iter().filter(|c| **c == NarrativeClaim::Pending).count(), 7);
+
+        // 抓取完成后再来的 payload 直接命中
+        cache.entry(EVM_ADDRESS.to_lowercase()).or_default().push("story".into(), 1, 5);
+        assert_eq!(claim_narrative_fetch(&cache, EVM_ADDRESS.to_lowercase(), None), NarrativeClaim::Hit);
+        // 抓取时间早于刷新间隔：重新占位，历史保留
+        let refresh_after = Some(Duration::from_secs(60));
+        assert_eq!(claim_narrative_fetch(&cache, EVM_ADDRESS.to_lowercase(), refresh_after), NarrativeClaim::Claimed);
+        assert_eq!(claim_narrative_fetch(&cache, EVM_ADDRESS.to_lowercase(), refresh_after), NarrativeClaim::Pending);
+        assert_eq!(cache.get(&EVM_ADDRESS.to_lowercase()).unwrap().latest(), Some("story"));
+    }
+
+    #[test]
+    fn narrative_history_keeps_latest_distinct_versions() {
+        let mut entry = NarrativeEntry::pending();
+        assert!(entry.latest().is_none());
+
+        // 无叙事的抓取结果只清除占位
+        assert!(!entry.push(String::new(), 1, 2));
+        assert!(!entry.pending);
+
+        assert!(entry.push("a".into(), 2, 2));
+        assert!(!entry.push("a".into(), 3, 2));
+        assert!(entry.push("b".into(), 4, 2));
+        assert!(entry.push("c".into(), 5, 2));
+
+        let history: Vec<_> = entry.history.iter().map(|n| (n.text.as_str(), n.fetched_at)).collect();
+        assert_eq!(history, vec![("b", 4), ("c", 5)]);
+        assert_eq!(entry.latest(), Some("c"));
+    }
+}
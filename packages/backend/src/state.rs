@@ -1,12 +1,67 @@
 // packages/backend/src/state.rs
-use super::types::Room;
+use super::types::{DataPayload, Room, TimedNarrative};
+use bytes::Bytes;
 use dashmap::DashMap;
-use std::collections::HashSet;
+use http::{HeaderValue, StatusCode};
+use socketioxide::{extract::SocketRef, socket::Sid};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc::UnboundedSender;
+use std::time::Instant;
+use tokio::sync::{mpsc::UnboundedSender, OnceCell};
 
 pub type AppState = Arc<DashMap<String, Room>>;
-pub type NarrativeCache = Arc<DashMap<String, String>>;
+pub type NarrativeCache = Arc<DashMap<String, NarrativeEntry>>;
+
+/// 叙事缓存条目：最近几条不同的叙事 (旧 -> 新)，抓取进行中时为占位状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NarrativeEntry {
+    /// 抓取进行中的占位标记 (不持久化)
+    #[serde(skip)]
+    pub pending: bool,
+    pub history: VecDeque<TimedNarrative>,
+    /// 最近一次抓取成功的时间 (毫秒，含接口返回无叙事)，用于判断是否需要刷新
+    #[serde(default)]
+    pub refreshed_at: i64,
+}
+
+impl NarrativeEntry {
+    pub fn pending() -> Self {
+        Self { pending: true, ..Self::default() }
+    }
+
+    /// 从单条叙事构造 (兼容旧版只存字符串的缓存文件，空串表示已抓取但无叙事)
+    pub fn from_text(text: String, now: i64) -> Self {
+        let mut entry = Self::default();
+        entry.push(text, now, 1);
+        entry
+    }
+
+    /// 最新的一条叙事
+    pub fn latest(&self) -> Option<&str> {
+        self.history.back().map(|n| n.text.as_str())
+    }
+
+    /// 距上次抓取成功超过 refresh_after 时需要重新抓取 (None 表示抓到后不再刷新)
+    pub fn is_stale(&self, now: i64, refresh_after: Option<std::time::Duration>) -> bool {
+        refresh_after.is_some_and(|ttl| now - self.refreshed_at >= ttl.as_millis() as i64)
+    }
+
+    /// 记录一次抓取结果 (空串表示无叙事) 并清除占位标记；与最新一条不同时追加，超出 `max` 丢弃最旧的。返回是否追加
+    pub fn push(&mut self, text: String, now: i64, max: usize) -> bool {
+        self.pending = false;
+        self.refreshed_at = now;
+        if text.is_empty() || self.latest() == Some(text.as_str()) {
+            return false;
+        }
+        self.history.push_back(TimedNarrative { text, fetched_at: now });
+        while self.history.len() > max.max(1) {
+            self.history.pop_front();
+        }
+        true
+    }
+}
 
 // ✨ 反向索引: Token Address (Lower) -> Set<RoomName>
 pub type RoomIndex = Arc<DashMap<String, HashSet<String>>>;
@@ -22,6 +77,273 @@ pub enum SubscriptionCommand {
 // 这里的 Sender 通常是 mpsc::UnboundedSender<SubscriptionCommand>
 pub type TokenManagerMap = Arc<DashMap<String, UnboundedSender<SubscriptionCommand>>>;
 
+/// 图片抓取结果：成功为 (Content-Type, 图片数据)，失败为最后一次上游状态码
+pub type ImageFetchResult = Result<(HeaderValue, Bytes), StatusCode>;
+
+// ✨ 图片代理 in-flight 去重: URL -> 共享的抓取结果
+// 同一 URL 的并发请求等待同一个 OnceCell，只向上游发起一次抓取
+pub type ImageInflightMap = Arc<DashMap<String, Arc<OnceCell<ImageFetchResult>>>>;
+
+pub fn new_image_inflight_map() -> ImageInflightMap {
+    Arc::new(DashMap::new())
+}
+
+/// 节流中的待广播数据 (同一分类在窗口期内合并)
+pub struct PendingBroadcast {
+    pub payload: DataPayload,
+    /// 最近一次发送该分类数据的 socket (广播时排除发送者自身)
+    pub sender: SocketRef,
+}
+
+// ✨ 广播节流缓冲: 分类 (hotlist / meme_new / ...) -> 待广播数据
+pub type PendingBroadcastMap = Arc<DashMap<&'static str, PendingBroadcast>>;
+
+pub fn new_pending_broadcast_map() -> PendingBroadcastMap {
+    Arc::new(DashMap::new())
+}
+
+/// 令牌桶：容量 = 每秒补充量，允许短暂突发但限制持续频率
+pub struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: f64) -> Self {
+        Self { tokens: capacity, last_refill: Instant::now() }
+    }
+
+    /// 尝试消耗一个令牌，令牌不足返回 false
+    pub fn try_take(&mut self, per_sec: f64) -> bool {
+        self.take_up_to(per_sec, 1) == 1
+    }
+
+    /// 最多消耗 n 个令牌，返回实际消耗的个数 (批量操作按剩余令牌部分放行)
+    pub fn take_up_to(&mut self, per_sec: f64, n: usize) -> usize {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_sec).min(per_sec);
+        self.last_refill = now;
+        let taken = (self.tokens.floor() as usize).min(n);
+        self.tokens -= taken as f64;
+        taken
+    }
+}
+
+// ✨ Worker 最近活跃时间: Token Address -> 毫秒时间戳，用于达到 Worker 上限时的 LRU 淘汰
+pub type WorkerActivity = Arc<DashMap<String, i64>>;
+
+pub fn new_worker_activity() -> WorkerActivity {
+    Arc::new(DashMap::new())
+}
+
+// ✨ 预订阅的 K 线房间: room_key -> 最近一次仍在热门中的毫秒时间戳 (超过 TTL 且无人订阅时退订)
+pub type Presubscriptions = Arc<DashMap<String, i64>>;
+
+pub fn new_presubscriptions() -> Presubscriptions {
+    Arc::new(DashMap::new())
+}
+
+// ✨ 盘口订阅: 规范化地址 -> (poolId, 订阅该盘口的客户端)
+pub type DepthSubscriptions = Arc<DashMap<String, (i64, HashSet<Sid>)>>;
+
+pub fn new_depth_subscriptions() -> DepthSubscriptions {
+    Arc::new(DashMap::new())
+}
+
+// ✨ 订阅频率限制: Sid -> 令牌桶 (断开连接时清理)
+pub type SubscribeRateLimits = Arc<DashMap<Sid, TokenBucket>>;
+
+pub fn new_subscribe_rate_limits() -> SubscribeRateLimits {
+    Arc::new(DashMap::new())
+}
+
+/// 叙事缓存统计 (GET /narrative-stats)。来源：
+/// - socket `request_narrative`：hits / misses / pending_skips，及其抓取结果 fetch_ok / fetch_errors
+/// - 爬虫 meme_new / meme_migrated 的 enrich_any_data：hits / misses / pending_skips，及后台抓取结果
+///   (Hotlist 不做叙事回填，不计入)
+/// 缓存超过 narrative_refresh_after 需要重新抓取时计为 misses
+#[derive(Default)]
+pub struct NarrativeStats {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+    /// 同一代币的抓取已在进行中
+    pub pending_skips: AtomicU64,
+    /// 抓取成功 (含接口返回无叙事)
+    pub fetch_ok: AtomicU64,
+    pub fetch_errors: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NarrativeStatsSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+    pub pending_skips: u64,
+    pub fetch_ok: u64,
+    pub fetch_errors: u64,
+    /// 命中率 = hits / (hits + misses)
+    pub hit_ratio: f64,
+    pub cache_size: usize,
+}
+
+impl NarrativeStats {
+    pub fn snapshot(&self, cache_size: usize) -> NarrativeStatsSnapshot {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+        NarrativeStatsSnapshot {
+            hits,
+            misses,
+            pending_skips: self.pending_skips.load(Ordering::Relaxed),
+            fetch_ok: self.fetch_ok.load(Ordering::Relaxed),
+            fetch_errors: self.fetch_errors.load(Ordering::Relaxed),
+            hit_ratio: if lookups > 0 { hits as f64 / lookups as f64 } else { 0.0 },
+            cache_size,
+        }
+    }
+}
+
+/// 单个 TokenWorker 的帧吞吐计数 (在 handle_payload 中累加)
+pub struct WorkerMetrics {
+    pub frames_received: AtomicU64,
+    pub klines_parsed: AtomicU64,
+    pub ticks_parsed: AtomicU64,
+    pub depth_parsed: AtomicU64,
+    pub broadcasts_emitted: AtomicU64,
+    /// 推送的逐笔成交 (trade_tick，每笔 Tick 最多一次)
+    pub trades_emitted: AtomicU64,
+    /// 按 tp 分类的买入 / 卖出成交笔数 (被跟踪代币视角)
+    pub buy_ticks: AtomicU64,
+    pub sell_ticks: AtomicU64,
+    /// 最近一帧的毫秒时间戳 (0 表示尚未收到)
+    pub last_frame_at: AtomicI64,
+    pub started_at: i64,
+}
+
+impl Default for WorkerMetrics {
+    fn default() -> Self {
+        Self {
+            frames_received: AtomicU64::new(0),
+            klines_parsed: AtomicU64::new(0),
+            ticks_parsed: AtomicU64::new(0),
+            depth_parsed: AtomicU64::new(0),
+            broadcasts_emitted: AtomicU64::new(0),
+            trades_emitted: AtomicU64::new(0),
+            buy_ticks: AtomicU64::new(0),
+            sell_ticks: AtomicU64::new(0),
+            last_frame_at: AtomicI64::new(0),
+            started_at: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerMetricsSnapshot {
+    pub address: String,
+    pub frames_received: u64,
+    pub klines_parsed: u64,
+    pub ticks_parsed: u64,
+    pub depth_parsed: u64,
+    pub broadcasts_emitted: u64,
+    pub trades_emitted: u64,
+    pub buy_ticks: u64,
+    pub sell_ticks: u64,
+    /// 占全部 Worker 广播量的比例 (0~1)
+    pub broadcast_share: f64,
+    /// 启动以来的平均帧速率
+    pub frames_per_sec: f64,
+    pub uptime_secs: i64,
+    pub last_frame_at: Option<i64>,
+}
+
+impl WorkerMetrics {
+    pub fn snapshot(&self, address: &str, now_ms: i64) -> WorkerMetricsSnapshot {
+        let frames_received = self.frames_received.load(Ordering::Relaxed);
+        let uptime_ms = (now_ms - self.started_at).max(1);
+        let last_frame_at = self.last_frame_at.load(Ordering::Relaxed);
+        WorkerMetricsSnapshot {
+            address: address.to_string(),
+            frames_received,
+            klines_parsed: self.klines_parsed.load(Ordering::Relaxed),
+            ticks_parsed: self.ticks_parsed.load(Ordering::Relaxed),
+            depth_parsed: self.depth_parsed.load(Ordering::Relaxed),
+            broadcasts_emitted: self.broadcasts_emitted.load(Ordering::Relaxed),
+            trades_emitted: self.trades_emitted.load(Ordering::Relaxed),
+            buy_ticks: self.buy_ticks.load(Ordering::Relaxed),
+            sell_ticks: self.sell_ticks.load(Ordering::Relaxed),
+            broadcast_share: 0.0,
+            frames_per_sec: frames_received as f64 * 1000.0 / uptime_ms as f64,
+            uptime_secs: uptime_ms / 1000,
+            last_frame_at: (last_frame_at > 0).then_some(last_frame_at),
+        }
+    }
+}
+
+/// Worker 生命周期事件类型 (worker_events 审计表的 event 列)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerEventKind {
+    Spawned,
+    Connected,
+    Disconnected,
+    Shutdown,
+}
+
+impl WorkerEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkerEventKind::Spawned => "spawned",
+            WorkerEventKind::Connected => "connected",
+            WorkerEventKind::Disconnected => "disconnected",
+            WorkerEventKind::Shutdown => "shutdown",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerEvent {
+    pub address: String,
+    pub kind: WorkerEventKind,
+    /// 附加信息 (如断线原因)
+    pub detail: Option<String>,
+    pub at: i64,
+}
+
+// ✨ Worker 生命周期事件通道：由后台任务批量写入 worker_events 表
+pub type WorkerEventSender = UnboundedSender<WorkerEvent>;
+
+/// 单个 Worker 的生命周期事件记录器 (Default 不记录，供测试 / 未启用时使用)
+#[derive(Debug, Clone, Default)]
+pub struct WorkerEventLog {
+    address: String,
+    tx: Option<WorkerEventSender>,
+}
+
+impl WorkerEventLog {
+    pub fn new(address: &str, tx: WorkerEventSender) -> Self {
+        Self { address: address.to_string(), tx: Some(tx) }
+    }
+
+    pub fn record(&self, kind: WorkerEventKind, detail: Option<String>) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(WorkerEvent {
+                address: self.address.clone(),
+                kind,
+                detail,
+                at: chrono::Utc::now().timestamp_millis(),
+            });
+        }
+    }
+}
+
+// ✨ Worker 状态: Token Address -> 帧吞吐计数 (Worker 退出时移除)
+pub type WorkerStatusMap = Arc<DashMap<String, Arc<WorkerMetrics>>>;
+
+pub fn new_worker_status_map() -> WorkerStatusMap {
+    Arc::new(DashMap::new())
+}
+
 pub fn new_token_manager_map() -> TokenManagerMap {
     Arc::new(DashMap::new())
 }
@@ -36,4 +358,17 @@ pub fn new_narrative_cache() -> NarrativeCache {
 
 pub fn new_room_index() -> RoomIndex {
     Arc::new(DashMap::new())
+}
+
+// ✨ 每个 Token 的 K 线房间订阅者总数 (各周期房间 clients 之和): Token Address -> 计数，
+// 客户端加入/离开房间时增减；Worker 持有计数句柄决定 Tick 扇出方式，不必每笔 Tick 遍历房间统计
+pub type TokenSubscribers = Arc<DashMap<String, Arc<AtomicUsize>>>;
+
+pub fn new_token_subscribers() -> TokenSubscribers {
+    Arc::new(DashMap::new())
+}
+
+/// 某个 Token 的订阅者计数句柄 (不存在时创建)
+pub fn subscriber_counter(subscribers: &TokenSubscribers, address: &str) -> Arc<AtomicUsize> {
+    subscribers.entry(address.to_string()).or_default().clone()
 }
\ No newline at end of file
@@ -1,24 +1,25 @@
 use crate::config::Config;
-use crate::state::{AppState, RoomIndex, SubscriptionCommand};
+use crate::socket_handlers::{normalize_address, trade_room};
+use crate::state::{AppState, RoomIndex, SubscriptionCommand, WorkerEventKind, WorkerEventLog, WorkerMetrics};
 use crate::types::{
-    BinanceKlineDataWrapper, BinanceStreamWrapper, BinanceTickDataWrapper, KlineBroadcastData,
-    KlineTick,
+    BinanceDepthDataWrapper, BinanceKlineDataWrapper, BinanceStreamWrapper, BinanceTickDataWrapper,
+    parse_kline, DepthBroadcastData, KlineBroadcastData, KlineTick, Room, StreamStatusEvent, TradeBroadcastData, TradeSide,
 };
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use futures_util::{stream::SplitSink, SinkExt, StreamExt};
 use socketioxide::SocketIo;
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::net::TcpStream;
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{mpsc::UnboundedReceiver, Mutex};
 use tokio::time::{interval, sleep, Duration};
-use tokio_native_tls::TlsStream;
 use tokio_tungstenite::{
     client_async_with_config,
-    tungstenite::{client::IntoClientRequest, Message},
-    WebSocketStream,
+    tungstenite::{client::IntoClientRequest, protocol::WebSocketConfig, Message},
+    MaybeTlsStream, WebSocketStream,
 };
 use tracing::{error, info, warn};
 use url::Url;
@@ -26,7 +27,7 @@ use url::Url;
 // Reuse helper functions from binance_task (will be made public later)
 use crate::binance_task::{establish_http_tunnel, wrap_stream_with_tls};
 
-type WsStream = WebSocketStream<TlsStream<TcpStream>>;
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 type WsWrite = SplitSink<WsStream, Message>;
 
 const LOW_VOLUME_PRICE_DEVIATION_THRESHOLD: f64 = 2.0;
@@ -41,13 +42,20 @@ pub async fn start_token_worker(
     config: Arc<Config>,
     app_state: AppState,
     room_index: RoomIndex,
+    subscribers: Arc<AtomicUsize>,
     mut cmd_rx: UnboundedReceiver<SubscriptionCommand>,
+    metrics: Arc<WorkerMetrics>,
+    events: WorkerEventLog,
 ) {
     let worker_id = format!("WORKER[{}]", token_address);
     info!("🚀 [{}] Starting...", worker_id);
+    events.record(WorkerEventKind::Spawned, None);
 
     let mut active_intervals: HashSet<String> = HashSet::new();
     let mut is_tick_subscribed = false;
+    let mut custom_streams: HashSet<String> = HashSet::new();
+    // 断线时刻 (毫秒)，重连成功后用于计算数据缺口
+    let mut disconnected_at: Option<i64> = None;
 
     loop {
         // Connect loop
@@ -59,23 +67,38 @@ pub async fn start_token_worker(
             &config,
             &app_state,
             &room_index,
+            &subscribers,
             &mut cmd_rx,
             &mut active_intervals,
             &mut is_tick_subscribed,
+            &mut custom_streams,
+            &mut disconnected_at,
+            &metrics,
+            &events,
         )
         .await;
 
-        match result {
+        let reason = match result {
             Ok(should_exit) => {
                 if should_exit {
                     info!("👋 [{}] Shutdown gracefully.", worker_id);
+                    events.record(WorkerEventKind::Shutdown, None);
                     break;
                 }
                 warn!("🔁 [{}] Disconnected. Reconnecting in 3s...", worker_id);
+                "connection closed".to_string()
             }
             Err(e) => {
                 error!("💥 [{}] Crash: {:#?}. Retrying in 5s...", worker_id, e);
+                format!("{:#}", e)
             }
+        };
+        // 首次断开时通知客户端并记录审计事件；重连失败的重试期间不重复推送
+        if disconnected_at.is_none() {
+            events.record(WorkerEventKind::Disconnected, Some(reason));
+            let now = Utc::now().timestamp_millis();
+            disconnected_at = Some(now);
+            emit_stream_status(&io, &config, &room_index, &token_address, "reconnecting", 0).await;
         }
         sleep(Duration::from_secs(3)).await;
     }
@@ -89,28 +112,40 @@ async fn connect_and_serve(
     config: &Config,
     app_state: &AppState,
     room_index: &RoomIndex,
+    subscribers: &AtomicUsize,
     cmd_rx: &mut UnboundedReceiver<SubscriptionCommand>,
     active_intervals: &mut HashSet<String>,
     is_tick_subscribed: &mut bool,
+    custom_streams: &mut HashSet<String>,
+    disconnected_at: &mut Option<i64>,
+    metrics: &WorkerMetrics,
+    events: &WorkerEventLog,
 ) -> Result<bool> {
     // 1. Establish Connection
     let stream = establish_http_tunnel(worker_id, config).await?;
-    let host = Url::parse(&config.binance_wss_url)?
-        .host_str()
-        .unwrap_or_default()
-        .to_string();
-    let tls_stream = wrap_stream_with_tls(stream, &host).await?;
+    let ws_url = Url::parse(&config.binance_wss_url)?;
+    let host = ws_url.host_str().unwrap_or_default().to_string();
+    // 明文 ws:// 仅用于本地调试/测试 (mock 服务器)，生产环境均为 wss://
+    let maybe_tls_stream = if ws_url.scheme() == "ws" {
+        MaybeTlsStream::Plain(stream)
+    } else {
+        MaybeTlsStream::NativeTls(wrap_stream_with_tls(stream, &host).await?)
+    };
 
     let mut request = config.binance_wss_url.as_str().into_client_request()?;
     request
         .headers_mut()
         .insert("User-Agent", "Rust/Backend TokenWorker".parse()?);
 
-    let (ws_stream, _) = client_async_with_config(request, tls_stream, None)
+    let ws_config = WebSocketConfig::default()
+        .max_message_size(Some(config.ws_max_message_size))
+        .max_frame_size(Some(config.ws_max_frame_size));
+    let (ws_stream, _) = client_async_with_config(request, maybe_tls_stream, Some(ws_config))
         .await
         .context("Handshake failed")?;
 
     info!("✅ [{}] Connected!", worker_id);
+    events.record(WorkerEventKind::Connected, None);
 
     let (mut write, mut read) = ws_stream.split();
 
@@ -130,11 +165,19 @@ async fn connect_and_serve(
          streams_to_sub.push(format!("kl@{}@{}@{}", pool_id, token_address, interval));
     }
 
+    streams_to_sub.extend(custom_streams.iter().cloned());
+
     if !streams_to_sub.is_empty() {
         info!("🔄 [{}] Resubscribing {} streams...", worker_id, streams_to_sub.len());
         send_subscribe(&mut write, streams_to_sub).await?;
     }
 
+    if let Some(since) = disconnected_at.take() {
+        let gap_ms = Utc::now().timestamp_millis() - since;
+        info!("🟢 [{}] Live again after {}ms gap", worker_id, gap_ms);
+        emit_stream_status(io, config, room_index, token_address, "live", gap_ms).await;
+    }
+
     let mut heartbeat = interval(config.heartbeat_interval);
     heartbeat.tick().await;
 
@@ -151,45 +194,34 @@ async fn connect_and_serve(
                     Some(command) => {
                         match command {
                             SubscriptionCommand::Subscribe(raw_stream) => {
-                                // raw_stream expected formats:
-                                // kl@poolId_addr_interval or tx@poolId_addr
-                                
-                                if raw_stream.starts_with("tx@") {
-                                    if !*is_tick_subscribed {
-                                        *is_tick_subscribed = true;
-                                        send_subscribe(&mut write, vec![raw_stream]).await?;
-                                    }
-                                } else if raw_stream.starts_with("kl@") {
-                                    // Extract interval
-                                    let parts: Vec<&str> = raw_stream.split('@').collect();
-                                    if let Some(interval) = parts.last() {
-                                        if !active_intervals.contains(*interval) {
-                                            active_intervals.insert(interval.to_string());
-                                            send_subscribe(&mut write, vec![raw_stream]).await?;
-                                        }
+                                // 按流前缀路由: kl@poolId@addr@interval / tx@poolId_addr / depth@poolId_addr
+                                let is_new = match StreamKind::parse(&raw_stream) {
+                                    StreamKind::Shutdown => return Ok(true), // Exit signal
+                                    StreamKind::Tick => !std::mem::replace(is_tick_subscribed, true),
+                                    StreamKind::Kline { interval } => active_intervals.insert(interval.to_string()),
+                                    StreamKind::Custom => custom_streams.insert(raw_stream.clone()),
+                                    StreamKind::Unknown => {
+                                        warn!("⚠️ [{}] Ignored unknown stream: {}", worker_id, raw_stream);
+                                        false
                                     }
-                                } else if raw_stream == "SHUTDOWN" {
-                                    return Ok(true); // Exit signal
+                                };
+                                if is_new {
+                                    send_subscribe(&mut write, vec![raw_stream]).await?;
                                 }
                             },
                             SubscriptionCommand::Unsubscribe(raw_stream) => {
-                                if raw_stream.starts_with("tx@") {
-                                    if *is_tick_subscribed {
-                                        *is_tick_subscribed = false;
-                                        send_unsubscribe(&mut write, vec![raw_stream]).await?;
-                                    }
-                                } else if raw_stream.starts_with("kl@") {
-                                    let parts: Vec<&str> = raw_stream.split('_').collect();
-                                    if let Some(interval) = parts.last() {
-                                        if active_intervals.contains(*interval) {
-                                            active_intervals.remove(*interval);
-                                            send_unsubscribe(&mut write, vec![raw_stream]).await?;
-                                        }
-                                    }
+                                let was_active = match StreamKind::parse(&raw_stream) {
+                                    StreamKind::Tick => std::mem::replace(is_tick_subscribed, false),
+                                    StreamKind::Kline { interval } => active_intervals.remove(interval),
+                                    StreamKind::Custom => custom_streams.remove(&raw_stream),
+                                    StreamKind::Shutdown | StreamKind::Unknown => false,
+                                };
+                                if was_active {
+                                    send_unsubscribe(&mut write, vec![raw_stream]).await?;
                                 }
                                 
                                 // Auto-shutdown Check
-                                if !*is_tick_subscribed && active_intervals.is_empty() {
+                                if !*is_tick_subscribed && active_intervals.is_empty() && custom_streams.is_empty() {
                                     info!("💤 [{}] No active subs. Idle shutdown.", worker_id);
                                     return Ok(true);
                                 }
@@ -205,7 +237,7 @@ async fn connect_and_serve(
                 match msg_result {
                     Some(Ok(msg)) => {
                         match msg {
-                            Message::Text(text) => handle_payload(&worker_id, &text, io, app_state, room_index).await,
+                            Message::Text(text) => handle_payload(&worker_id, &text, io, config, app_state, room_index, subscribers, metrics).await,
                             Message::Ping(p) => { write.send(Message::Pong(p)).await?; }
                             Message::Close(_) => return Ok(false), // Reconnect
                             _ => {}
@@ -219,6 +251,59 @@ async fn connect_and_serve(
     }
 }
 
+/// 解析 Tick 流名 `tx@poolId_address` (盘口流 `depth@poolId_address` 格式相同)，返回 (poolId, address)。
+/// 地址按订阅时的规则规范化 (EVM 小写 / Solana 原样)，可直接用于 room_index / 盘口房间查找；
+/// 不能一律小写，否则 Solana 代币的 Tick 找不到房间 (NO ROOMS)
+pub fn parse_tick_stream(stream: &str) -> Option<(i64, String)> {
+    let (_, params) = stream.split_once('@')?;
+    let (pool_id, address) = params.split_once('_')?;
+    let pool_id = pool_id.parse().ok()?;
+    Some((pool_id, normalize_address(pool_id, address)))
+}
+
+/// 指令中的流类型 (按前缀区分)
+enum StreamKind<'a> {
+    /// kl@poolId@address@interval
+    Kline { interval: &'a str },
+    /// tx@poolId_address
+    Tick,
+    /// 其他按原样转发给上游的流 (目前为 depth@poolId_address 盘口)
+    Custom,
+    Shutdown,
+    Unknown,
+}
+
+impl<'a> StreamKind<'a> {
+    fn parse(raw_stream: &'a str) -> Self {
+        if raw_stream == "SHUTDOWN" {
+            StreamKind::Shutdown
+        } else if raw_stream.starts_with("tx@") {
+            StreamKind::Tick
+        } else if raw_stream.starts_with("kl@") {
+            match raw_stream.rsplit('@').next() {
+                Some(interval) if raw_stream.split('@').count() == 4 => StreamKind::Kline { interval },
+                _ => StreamKind::Unknown,
+            }
+        } else if raw_stream.starts_with("depth@") {
+            StreamKind::Custom
+        } else {
+            StreamKind::Unknown
+        }
+    }
+}
+
+/// 向该 Token 的所有 K 线房间推送 stream_status
+async fn emit_stream_status(io: &SocketIo, config: &Config, room_index: &RoomIndex, address: &str, status: &'static str, gap_ms: i64) {
+    let rooms: Vec<String> = match room_index.get(address) {
+        Some(rooms) if !rooms.is_empty() => rooms.iter().cloned().collect(),
+        _ => return,
+    };
+    let event = StreamStatusEvent { address: address.to_string(), status, gap_ms };
+    if let Some(ns) = io.of(config.market_namespace.as_str()) {
+        ns.to(rooms).emit("stream_status", &event).await.ok();
+    }
+}
+
 async fn send_subscribe(write: &mut WsWrite, params: Vec<String>) -> Result<()> {
     info!("📡 [WS-OUT] Subscribing: {:?}", params);
     let msg = serde_json::json!({
@@ -244,9 +329,14 @@ async fn handle_payload(
     _worker_id: &str,
     text: &str,
     io: &SocketIo,
+    config: &Config,
     app_state: &AppState,
     room_index: &RoomIndex,
+    subscribers: &AtomicUsize,
+    metrics: &WorkerMetrics,
 ) {
+    metrics.frames_received.fetch_add(1, Ordering::Relaxed);
+    metrics.last_frame_at.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
     if text.contains("\"result\":null") { return; }
 
     // Try parsing as Kline first
@@ -260,89 +350,280 @@ async fn handle_payload(
              let interval = parts[3];
              // room_key 格式与内部一致
              let room_key = format!("kl@{}@{}@{}", pool_id, address, interval);
-             let kline = parse_kline(&wrapper.data.kline_data.values);
+             let Some(kline) = parse_kline(&wrapper.data.kline_data.values) else { return; };
+             metrics.klines_parsed.fetch_add(1, Ordering::Relaxed);
              
              // Update & Broadcast
-             if let Some(room) = app_state.get(&room_key) {
-                 *room.current_kline.lock().await = Some(kline.clone());
+             if let Some(handles) = app_state.get(&room_key).map(|room| RoomKlines::of(&room)) {
+                 {
+                     let mut guard = handles.current.lock().await;
+                     // Tick 已滚动到更新的周期时，迟到的旧周期帧不再覆盖 (避免图表闪回)
+                     if guard.as_ref().is_some_and(|current| current.time > kline.time) {
+                         return;
+                     }
+                     *guard = Some(kline.clone());
+                 }
+                 if !should_emit_kline(config, &handles.last_emitted, &kline).await {
+                     return;
+                 }
                  let bca = KlineBroadcastData { room: room_key.clone(), data: kline };
-                 io.to(room_key).emit("kline_update", &bca).await.ok();
+                 if let Some(ns) = io.of(config.market_namespace.as_str()) {
+                     ns.to(room_key).emit("kline_update", &bca).await.ok();
+                     metrics.broadcasts_emitted.fetch_add(1, Ordering::Relaxed);
+                 }
              }
          }
          return;
     }
 
+    // Try parsing as Depth (盘口快照)
+    if let Ok(wrapper) = serde_json::from_str::<BinanceStreamWrapper<BinanceDepthDataWrapper>>(text) {
+        // stream: depth@poolId_address，地址与 subscribe_depth 的房间名同样规范化
+        let Some((_, address)) = parse_tick_stream(&wrapper.stream) else { return; };
+        metrics.depth_parsed.fetch_add(1, Ordering::Relaxed);
+        let room_key = format!("depth@{}", address);
+        let parse_levels = |levels: &[(String, String)]| -> Vec<[f64; 2]> {
+            levels
+                .iter()
+                .filter_map(|(price, qty)| Some([price.parse().ok()?, qty.parse().ok()?]))
+                .collect()
+        };
+        let bca = DepthBroadcastData {
+            room: room_key.clone(),
+            bids: parse_levels(&wrapper.data.depth_data.bids),
+            asks: parse_levels(&wrapper.data.depth_data.asks),
+        };
+        if let Some(ns) = io.of(config.market_namespace.as_str()) {
+            ns.to(room_key).emit("depth_update", &bca).await.ok();
+            metrics.broadcasts_emitted.fetch_add(1, Ordering::Relaxed);
+        }
+        return;
+    }
+
     // Try parsing as Tick
     if let Ok(wrapper) = serde_json::from_str::<BinanceStreamWrapper<BinanceTickDataWrapper>>(text) {
         let tick = &wrapper.data.tick_data;
-        let parts: Vec<&str> = wrapper.stream.split('@').collect();
-        if parts.len() == 2 {
-            // parts[1] example: "16_address"
-            let params: Vec<&str> = parts[1].split('_').collect(); // [poolId, addr]
-            if params.len() >= 2 {
-                let tracked_address = params[1]; 
-                
-                // Debug log for received tick (sampled)
-                if tick.v > 1000.0 { // 只打印大额或随机打印，防止刷屏，但为了调试先全部打印关键信息
-                     info!("🔔 [TICK RECV] Stream: {} | Addr: {} | Price: {}", wrapper.stream, tracked_address, tick.t0pu);
-                }
+        let Some((_, tracked_address)) = parse_tick_stream(&wrapper.stream) else { return; };
+        let tracked_address = tracked_address.as_str();
+        metrics.ticks_parsed.fetch_add(1, Ordering::Relaxed);
+        
+        // Debug log for received tick (按成交额采样，防止刷屏)
+        if config.tick_debug_logging && tick.v > config.tick_debug_recv_min_volume {
+             info!("🔔 [TICK RECV] Stream: {} | Addr: {} | Price: {}", wrapper.stream, tracked_address, tick.t0pu);
+        }
+
+        // Price extraction
+        let tracked_is_t0 = tick.t0a.eq_ignore_ascii_case(tracked_address);
+        let price = if tracked_is_t0 { tick.t0pu } 
+                    else if tick.t1a.eq_ignore_ascii_case(tracked_address) { tick.t1pu } 
+                    else { 
+                        warn!("⚠️ [TICK MISMATCH] Tracked: {} | T0: {} | T1: {}", tracked_address, tick.t0a, tick.t1a);
+                        return; 
+                    };
+        
+        let usd_volume = tick.v;
+        let side = tick.side(tracked_is_t0);
+        match side {
+            Some(TradeSide::Buy) => { metrics.buy_ticks.fetch_add(1, Ordering::Relaxed); }
+            Some(TradeSide::Sell) => { metrics.sell_ticks.fetch_add(1, Ordering::Relaxed); }
+            None => {}
+        }
+
+        // 粉尘成交：不更新 K 线，避免图表噪音
+        if usd_volume < config.min_tick_volume_usd {
+            return;
+        }
+
+        // Broadcast 1: Update all Room Klines for this token
+        // tracked_address 已按订阅规则规范化，与 room_index 的键一致
+        let update = TickUpdate { price, usd_volume, side, time: Utc::now().timestamp_millis() };
+        let subscribers = subscribers.load(Ordering::Relaxed);
+        let Some(broadcast_count) = fan_out_tick(io, config, metrics, app_state, room_index, tracked_address, subscribers, &update).await else {
+            warn!("⚠️ [NO ROOMS] Received tick for {} but no rooms found in index", tracked_address);
+            return;
+        };
+        if config.tick_debug_logging && broadcast_count > 0 && tick.v > config.tick_debug_broadcast_min_volume {
+             info!("📡 [BROADCAST] Sent update to {} rooms for {}", broadcast_count, tracked_address);
+        }
+    }
+}
+
+/// 一笔 Tick 解析出的成交 (扇出到该代币的每个周期房间)
+pub struct TickUpdate {
+    pub price: f64,
+    pub usd_volume: f64,
+    pub side: Option<TradeSide>,
+    /// 服务端收到成交的毫秒时间戳
+    pub time: i64,
+}
 
-                // Price extraction
-                let price = if tick.t0a.eq_ignore_ascii_case(tracked_address) { tick.t0pu } 
-                            else if tick.t1a.eq_ignore_ascii_case(tracked_address) { tick.t1pu } 
-                            else { 
-                                warn!("⚠️ [TICK MISMATCH] Tracked: {} | T0: {} | T1: {}", tracked_address, tick.t0a, tick.t1a);
-                                return; 
-                            };
-                
-                let usd_volume = tick.v;
-
-                // Broadcast 1: Update all Room Klines for this token
-                // Use tracked_address directly (it respects case from subscription)
-                if let Some(room_keys) = room_index.get(tracked_address) {
-                    let mut broadcast_count = 0;
-                    for room_key in room_keys.iter() {
-                         if let Some(entry) = app_state.get(room_key) {
-                             let mut kline_guard = entry.value().current_kline.lock().await;
-                             if let Some(kline) = kline_guard.as_mut() {
-                                 // Price Filter
-                                 if kline.close > 0.0 {
-                                     let ratio = if price > kline.close { price / kline.close } else { kline.close / price };
-                                     if ratio > LOW_VOLUME_PRICE_DEVIATION_THRESHOLD && usd_volume < LOW_VOLUME_THRESHOLD {
-                                         warn!("🛡️ [PRICE FILTER] Ignored anomaly: Price {} vs Last {}, Vol {}", price, kline.close, usd_volume);
-                                         continue;
-                                     }
-                                 }
-                                 kline.high = kline.high.max(price);
-                                 kline.low = kline.low.min(price);
-                                 kline.close = price;
-
-                                 let bca = KlineBroadcastData { room: room_key.clone(), data: kline.clone() };
-                                 io.to(room_key.clone()).emit("kline_update", &bca).await.ok();
-                                 broadcast_count += 1;
-                             }
-                         }
-                    }
-                    if broadcast_count > 0 && tick.v > 5000.0 {
-                         info!("📡 [BROADCAST] Sent update to {} rooms for {}", broadcast_count, tracked_address);
-                    }
-                } else {
-                    warn!("⚠️ [NO ROOMS] Received tick for {} but no rooms found in index", tracked_address);
+/// 把一笔 Tick 扇出到代币的所有周期房间，返回推送了 K 线的房间数；索引中没有该代币时返回 None。
+/// subscribers 为该代币各房间的订阅者总数 (由订阅/退订维护的计数)，达到 tick_fanout_snapshot_min_subscribers 时
+/// 先快照房间再推送，否则直接遍历索引
+pub async fn fan_out_tick(
+    io: &SocketIo,
+    config: &Config,
+    metrics: &WorkerMetrics,
+    app_state: &AppState,
+    room_index: &RoomIndex,
+    address: &str,
+    subscribers: usize,
+    update: &TickUpdate,
+) -> Option<usize> {
+    let room_keys = room_index.get(address)?;
+    let mut broadcast_count = 0;
+    // 至少一个房间接受了这笔 Tick (未被价格异常过滤)
+    let mut accepted = false;
+    if subscribers >= config.tick_fanout_snapshot_min_subscribers {
+        // 热门代币：先快照该代币的房间 (每个周期一个房间) 并取出 K 线句柄，推送期间不持有 room_index / app_state 的分片读锁，
+        // 高频广播时不会阻塞同分片上的订阅与退订
+        let rooms: Vec<(String, RoomKlines)> = room_keys
+            .iter()
+            .filter_map(|room_key| Some((room_key.clone(), RoomKlines::of(&app_state.get(room_key)?))))
+            .collect();
+        drop(room_keys);
+        for (room_key, handles) in &rooms {
+            let outcome = apply_tick_to_room(io, config, metrics, room_key, handles, update).await;
+            accepted |= outcome != TickOutcome::Filtered;
+            broadcast_count += usize::from(outcome == TickOutcome::Emitted);
+        }
+    } else {
+        // 订阅者少：直接遍历索引，省去每笔 Tick 的快照分配
+        for room_key in room_keys.iter() {
+            let Some(handles) = app_state.get(room_key).map(|room| RoomKlines::of(&room)) else { continue; };
+            let outcome = apply_tick_to_room(io, config, metrics, room_key, &handles, update).await;
+            accepted |= outcome != TickOutcome::Filtered;
+            broadcast_count += usize::from(outcome == TickOutcome::Emitted);
+        }
+    }
+
+    // 逐笔成交明细：每笔 Tick 只推送一次到代币的成交房间，且在价格异常过滤之后 (方向未知的成交不推送)
+    if let (true, true, Some(side)) = (accepted, config.broadcast_trade_ticks, update.side) {
+        if let Some(ns) = io.of(config.market_namespace.as_str()) {
+            let room = trade_room(address);
+            let trade = TradeBroadcastData { room: room.clone(), side, price: update.price, volume_usd: update.usd_volume, time: update.time };
+            ns.to(room).emit("trade_tick", &trade).await.ok();
+            metrics.trades_emitted.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    Some(broadcast_count)
+}
+
+/// 一笔 Tick 应用到单个房间的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TickOutcome {
+    /// 小额成交价格偏离过大，按异常丢弃
+    Filtered,
+    /// 已更新 K 线但未推送 (与上次推送相同，或无法确定周期)
+    Applied,
+    /// 已更新并推送 kline_update
+    Emitted,
+}
+
+/// 把一笔 Tick 应用到单个周期房间并推送 kline_update
+async fn apply_tick_to_room(
+    io: &SocketIo,
+    config: &Config,
+    metrics: &WorkerMetrics,
+    room_key: &str,
+    handles: &RoomKlines,
+    tick: &TickUpdate,
+) -> TickOutcome {
+    let (price, usd_volume) = (tick.price, tick.usd_volume);
+
+    let mut kline_guard = handles.current.lock().await;
+    let kline = match kline_guard.as_mut() {
+        Some(kline) => {
+            // Price Filter
+            if kline.close > 0.0 {
+                let ratio = if price > kline.close { price / kline.close } else { kline.close / price };
+                if ratio > LOW_VOLUME_PRICE_DEVIATION_THRESHOLD && usd_volume < LOW_VOLUME_THRESHOLD {
+                    warn!("🛡️ [PRICE FILTER] Ignored anomaly: Price {} vs Last {}, Vol {}", price, kline.close, usd_volume);
+                    return TickOutcome::Filtered;
                 }
             }
+            // 每个周期房间独立维护自己的 K 线：Tick 落入新周期时先滚动出新 K 线，
+            // 旧周期的 high/low 不会被之后的价格污染
+            if is_new_bucket(room_key, kline) {
+                let Some(next) = provisional_kline(room_key, price, usd_volume) else { return TickOutcome::Applied; };
+                *kline = next;
+            } else {
+                kline.high = kline.high.max(price);
+                kline.low = kline.low.min(price);
+                kline.close = price;
+                // 权威帧之间累加 Tick 成交额，让实时成交量柱随成交增长；下一根权威帧到达时整根替换
+                kline.volume += usd_volume;
+            }
+            kline
+        }
+        // 历史尚未加载：用首个 Tick 建立临时 K 线，让图表立即显示实时价格 (历史到达后再对齐)
+        None => {
+            let Some(provisional) = provisional_kline(room_key, price, usd_volume) else { return TickOutcome::Applied; };
+            info!("🌱 [KLINE PROVISIONAL] {} initialized from first tick @ {}", room_key, price);
+            kline_guard.insert(provisional)
         }
+    };
+
+    if !should_emit_kline(config, &handles.last_emitted, kline).await {
+        return TickOutcome::Applied;
+    }
+    let bca = KlineBroadcastData { room: room_key.to_string(), data: kline.clone() };
+    if let Some(ns) = io.of(config.market_namespace.as_str()) {
+        ns.to(room_key.to_string()).emit("kline_update", &bca).await.ok();
+        metrics.broadcasts_emitted.fetch_add(1, Ordering::Relaxed);
+    }
+    TickOutcome::Emitted
+}
+
+/// 房间 K 线状态的共享句柄 (从 app_state 中取出后即可释放 DashMap 引用)
+struct RoomKlines {
+    current: Arc<Mutex<Option<KlineTick>>>,
+    last_emitted: Arc<Mutex<Option<KlineTick>>>,
+}
+
+impl RoomKlines {
+    fn of(room: &Room) -> Self {
+        Self { current: room.current_kline.clone(), last_emitted: room.last_emitted_kline.clone() }
     }
 }
 
-fn parse_kline(values: &(String, String, String, String, String, String)) -> KlineTick {
-    KlineTick {
-        time: DateTime::from_timestamp(values.5.parse::<i64>().unwrap_or_default() / 1000, 0)
-            .unwrap_or_default()
-            .with_timezone(&Utc),
-        open: values.0.parse().unwrap_or_default(),
-        high: values.1.parse().unwrap_or_default(),
-        low: values.2.parse().unwrap_or_default(),
-        close: values.3.parse().unwrap_or_default(),
-        volume: values.4.parse().unwrap_or_default(),
+/// 与该房间上次推送的 K 线比较，完全相同时返回 false (跳过推送)；否则记录本次推送
+async fn should_emit_kline(config: &Config, last_emitted: &Mutex<Option<KlineTick>>, kline: &KlineTick) -> bool {
+    if !config.skip_unchanged_kline_updates {
+        return true;
     }
+    let mut last = last_emitted.lock().await;
+    if last.as_ref() == Some(kline) {
+        return false;
+    }
+    *last = Some(kline.clone());
+    true
+}
+
+/// 当前时间所在周期桶的起始时间 (room_key: kl@poolId@address@interval)
+fn current_bucket_start(room_key: &str) -> Option<DateTime<Utc>> {
+    let interval_ms = crate::kline_handler::interval_to_ms(room_key.rsplit('@').next()?);
+    if interval_ms <= 0 {
+        return None;
+    }
+    let now_ms = Utc::now().timestamp_millis();
+    DateTime::from_timestamp_millis(now_ms - now_ms % interval_ms)
+}
+
+/// 当前 K 线是否已落后于该房间周期的当前桶
+fn is_new_bucket(room_key: &str, kline: &KlineTick) -> bool {
+    current_bucket_start(room_key).is_some_and(|bucket| bucket > kline.time)
+}
+
+/// 以 Tick 价格与成交额构造当前周期桶的临时 K 线
+fn provisional_kline(room_key: &str, price: f64, volume: f64) -> Option<KlineTick> {
+    let kline = KlineTick {
+        time: current_bucket_start(room_key)?,
+        open: price,
+        high: price,
+        low: price,
+        close: price,
+        volume,
+    };
+    kline.validate().ok().map(|_| kline)
 }
@@ -8,6 +8,7 @@ use sqlx::{sqlite::SqliteRow, Row};
 use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tracing::warn;
 
 // ==============================================================================
 // 1. 定义 Trait：通用行为 (NarrativeEntity)
@@ -38,6 +39,28 @@ pub enum AlertType {
     PriceChange1m,
     #[serde(rename = "priceChange5m")]
     PriceChange5m,
+    #[serde(rename = "migration")]
+    Migration,
+    #[serde(rename = "buyPressure")]
+    BuyPressure,
+    /// 配置文件中的自定义规则 (规则名见 AlertLogEntry.rule)
+    #[serde(rename = "custom")]
+    Custom,
+}
+
+impl AlertType {
+    /// 与 serde rename 一致的类型字符串 (用于冷却键与配置)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertType::Volume1m => "volume1m",
+            AlertType::Volume5m => "volume5m",
+            AlertType::PriceChange1m => "priceChange1m",
+            AlertType::PriceChange5m => "priceChange5m",
+            AlertType::Migration => "migration",
+            AlertType::BuyPressure => "buyPressure",
+            AlertType::Custom => "custom",
+        }
+    }
 }
 
 /// 报警日志条目
@@ -53,6 +76,8 @@ pub struct AlertLogEntry {
     #[ts(type = "number")]
     pub timestamp: i64,            // 毫秒时间戳
     pub alert_type: AlertType,
+    /// 触发的规则名 (内置规则与 alert_type 相同)
+    pub rule: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, TS)]
@@ -97,6 +122,17 @@ impl NarrativeEntity for HotlistItem {
     fn get_narrative_chain_id(&self) -> Option<String> { None }
 }
 
+/// 带抓取时间的叙事版本 (`request_narrative_history` 返回的历史条目)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+#[ts(export, export_to = "../../shared-types/src/bindings/TimedNarrative.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct TimedNarrative {
+    pub text: String,
+    /// 毫秒级 Unix 时间戳（首次抓取到该版本的时间）
+    #[ts(type = "number")]
+    pub fetched_at: i64,
+}
+
 /// 流动性历史数据点
 #[derive(Debug, Serialize, Deserialize, Clone, Default, TS)]
 #[ts(export, export_to = "../../shared-types/src/bindings/LiquidityPoint.ts")]
@@ -231,6 +267,12 @@ impl NarrativeEntity for MemeScanItem {
 // 4. Payload 定义 (交通枢纽)
 // ==============================================================================
 
+/// 爬虫推送的数据语义：
+/// - snapshot / full：当前完整列表，整体替换
+/// - update：增量，只包含发生变化 (或新出现) 的行，按 contractAddress 覆盖到已有列表；
+///   不会通过增量删除行，行的删除只能由下一次 snapshot / full 体现。
+///
+/// Hotlist 的增量由服务端合并进快照后，以 full 广播给客户端 (爬虫启动后应先推送一次完整快照)
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, TS)]
 #[ts(export, export_to = "../../shared-types/src/bindings/DataAction.ts")]
 pub enum DataAction {
@@ -266,10 +308,43 @@ pub enum DataPayload {
         r#type: DataAction,
         data: Vec<MemeScanItem>,
     },
+    // 4. 单个代币详情刷新 (持有人、流动性等随时间变化) -> 只推送给 token@{addr} 房间
+    #[serde(rename = "token_detail")]
+    TokenDetail {
+        r#type: DataAction,
+        data: MemeScanItem,
+    },
+    // 5. 代币下架 / 被 rug：广播 token_removed 让前端移除条目，并清理服务端缓存
+    #[serde(rename = "remove")]
+    Remove {
+        data: Vec<RemovedToken>,
+    },
     #[serde(other)]
     Unknown,
 }
 
+/// 被移除 (下架 / rug) 的代币
+#[derive(Debug, Deserialize, Serialize, Clone, TS)]
+#[ts(export, export_to = "../../shared-types/src/bindings/RemovedToken.ts")]
+pub struct RemovedToken {
+    pub chain: String,
+    pub address: String,
+}
+
+impl DataPayload {
+    /// 与 serde tag 一致的分类名
+    pub fn category(&self) -> &'static str {
+        match self {
+            DataPayload::Hotlist { .. } => "hotlist",
+            DataPayload::MemeNew { .. } => "meme_new",
+            DataPayload::MemeMigrated { .. } => "meme_migrated",
+            DataPayload::TokenDetail { .. } => "token_detail",
+            DataPayload::Remove { .. } => "remove",
+            DataPayload::Unknown => "unknown",
+        }
+    }
+}
+
 // ... (以下保留之前的辅助结构不变) ...
 #[derive(Debug, Deserialize)]
 pub struct NarrativeResponse {
@@ -287,10 +362,20 @@ pub struct NarrativeText {
     pub cn: Option<String>,
 }
 #[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct KlineSubscribePayload {
     pub address: String,
     pub chain: String,
     pub interval: String,
+    /// 是否用平直的合成 K 线补齐缺口 (默认 true)；false 时只返回真实 K 线，由前端自行处理缺口
+    #[serde(default = "default_true", alias = "fill_gaps")]
+    pub fill_gaps: bool,
+    /// 跳过 DB 缓存直接从上游拉取历史 (默认 false)，拉取结果仍写入 DB
+    #[serde(default, alias = "no_cache")]
+    pub no_cache: bool,
+}
+fn default_true() -> bool {
+    true
 }
 #[derive(Debug, Deserialize)]
 pub struct BinanceStreamWrapper<T> {
@@ -323,19 +408,90 @@ pub struct BinanceTickDetail {
     pub v: f64,
     pub a0: f64,
     pub a1: f64,
+    /// 成交方向 (以 t0 为标的)：上游实际取值为 "buy" / "sell"，其他取值视为未知
     pub tp: String,
 }
+
+/// 逐笔成交方向
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+impl BinanceTickDetail {
+    /// 被跟踪代币视角下的成交方向：tp 以 t0 为标的，跟踪的是 t1 时方向相反
+    pub fn side(&self, tracked_is_t0: bool) -> Option<TradeSide> {
+        let side = if self.tp.eq_ignore_ascii_case("buy") {
+            TradeSide::Buy
+        } else if self.tp.eq_ignore_ascii_case("sell") {
+            TradeSide::Sell
+        } else {
+            return None;
+        };
+        Some(match (side, tracked_is_t0) {
+            (side, true) => side,
+            (TradeSide::Buy, false) => TradeSide::Sell,
+            (TradeSide::Sell, false) => TradeSide::Buy,
+        })
+    }
+}
 #[derive(Debug, Serialize, Clone)]
 pub struct KlineBroadcastData {
     pub room: String,
     pub data: KlineTick,
 }
-#[derive(Debug, Serialize, Clone, Default, PartialEq, TS)]
-#[ts(export, export_to = "../../shared-types/src/bindings/KlineTick.ts")]
+/// 盘口深度帧: d.b / d.a 为 [价格, 数量] 档位 (字符串，与 Kline 帧一致)
+#[derive(Debug, Deserialize)]
+pub struct BinanceDepthDataWrapper {
+    #[serde(rename = "d")]
+    pub depth_data: BinanceDepthDetail,
+}
+#[derive(Debug, Deserialize)]
+pub struct BinanceDepthDetail {
+    #[serde(rename = "b")]
+    pub bids: Vec<(String, String)>,
+    #[serde(rename = "a")]
+    pub asks: Vec<(String, String)>,
+}
+/// 推送给 depth@{address} 房间的盘口快照，档位为 [价格, 数量]
+#[derive(Debug, Serialize, Clone)]
+pub struct DepthBroadcastData {
+    pub room: String,
+    pub bids: Vec<[f64; 2]>,
+    pub asks: Vec<[f64; 2]>,
+}
+/// 推送给代币成交房间 (trades@{address}) 的逐笔成交 (trade_tick)，每笔 Tick 一次，前端成交明细按 side 着色
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
+pub struct TradeBroadcastData {
+    /// trades@{address}：订阅该代币任一 K 线周期的客户端都在此房间
+    pub room: String,
+    pub side: TradeSide,
+    pub price: f64,
+    pub volume_usd: f64,
+    /// 服务端收到成交的毫秒时间戳
+    pub time: i64,
+}
+/// 推送给 Token 所有 K 线房间的实时流状态：断线时 reconnecting，恢复后 live (gap_ms 为中断时长，
+/// 前端可据此提示并重新拉取历史补齐缺口)
+#[derive(Debug, Serialize, Clone)]
+pub struct StreamStatusEvent {
+    pub address: String,
+    pub status: &'static str,
+    pub gap_ms: i64,
+}
+/// 盘口订阅参数
+#[derive(Debug, Deserialize, Clone)]
+pub struct DepthSubscribePayload {
+    pub address: String,
+    pub chain: String,
+}
+/// 序列化时经 KlineTickWire 输出：time 为秒 (图表库使用)，timeMs 为毫秒 (与其余 *_ms 字段一致)
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+#[serde(into = "KlineTickWire")]
 pub struct KlineTick {
-    #[serde(with = "chrono::serde::ts_seconds")]
-#[ts(type = "string")] // DateTime<Utc> 映射为 string
     pub time: DateTime<Utc>,
     pub open: f64,
     pub high: f64,
@@ -343,6 +499,79 @@ pub struct KlineTick {
     pub close: f64,
     pub volume: f64,
 }
+/// KlineTick 的线上格式 (TS 类型名仍为 KlineTick)
+#[derive(Debug, Serialize, TS)]
+#[ts(export, rename = "KlineTick", export_to = "../../shared-types/src/bindings/KlineTick.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct KlineTickWire {
+    /// 开盘时间 (秒)
+    #[ts(type = "number")]
+    pub time: i64,
+    /// 开盘时间 (毫秒)
+    #[ts(type = "number")]
+    pub time_ms: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+impl From<KlineTick> for KlineTickWire {
+    fn from(kline: KlineTick) -> Self {
+        Self {
+            time: kline.time.timestamp(),
+            time_ms: kline.time.timestamp_millis(),
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+        }
+    }
+}
+/// 允许的时钟偏差：K 线时间晚于当前时间超过该值视为非法
+const KLINE_MAX_FUTURE_SKEW_SECS: i64 = 60;
+
+impl KlineTick {
+    /// 入库/广播前的结构校验，返回非法原因
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.time.timestamp() <= 0 {
+            return Err("zero timestamp");
+        }
+        if self.time.timestamp() > Utc::now().timestamp() + KLINE_MAX_FUTURE_SKEW_SECS {
+            return Err("future timestamp");
+        }
+        if self.open <= 0.0 || self.high <= 0.0 || self.low <= 0.0 || self.close <= 0.0 {
+            return Err("non-positive price");
+        }
+        if self.high < self.low {
+            return Err("high < low");
+        }
+        Ok(())
+    }
+}
+
+/// 解析上游 Kline 帧 (open, high, low, close, volume, 毫秒时间戳)，非法数据 (零价格/零时间戳/high<low 等) 记录日志后返回 None
+pub fn parse_kline(values: &(String, String, String, String, String, String)) -> Option<KlineTick> {
+    let kline = KlineTick {
+        time: DateTime::from_timestamp(values.5.parse::<i64>().unwrap_or_default() / 1000, 0)
+            .unwrap_or_default()
+            .with_timezone(&Utc),
+        open: values.0.parse().unwrap_or_default(),
+        high: values.1.parse().unwrap_or_default(),
+        low: values.2.parse().unwrap_or_default(),
+        close: values.3.parse().unwrap_or_default(),
+        volume: values.4.parse().unwrap_or_default(),
+    };
+    match kline.validate() {
+        Ok(()) => Some(kline),
+        Err(reason) => {
+            warn!("⚠️ [KLINE INVALID] Dropped frame ({}): {:?}", reason, values);
+            None
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Clone, TS)]
 #[ts(export, export_to = "../../shared-types/src/bindings/KlineHistoryResponse.ts")]
 #[serde(rename_all = "camelCase")]
@@ -350,19 +579,269 @@ pub struct KlineHistoryResponse {
     pub address: String,
     pub chain: String,
     pub interval: String,
+    #[ts(as = "Vec<KlineTickWire>")]
     pub data: Vec<KlineTick>,
     /// 流动性历史（可选）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub liquidity_history: Option<Vec<LiquidityPoint>>,
+    /// 代币精度 (来自 Meme 数据，已知时返回，供前端格式化价格轴)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub decimals: Option<i64>,
+    /// 代币 symbol 与图标 (已知时返回，图表标题无需再从 data-update 中拼凑)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub icon: Option<String>,
+    /// 缓存中最新 K 线收盘距今的毫秒数 (仅首屏响应、且超过 kline_stale_threshold 时返回)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub stale_ms: Option<i64>,
 }
 pub struct Room {
     pub clients: HashSet<Sid>,
     pub symbol: String,
     pub current_kline: Arc<Mutex<Option<KlineTick>>>,
+    /// 上一次推送给该房间的 K 线 (skip_unchanged_kline_updates 开启时用于去重)
+    pub last_emitted_kline: Arc<Mutex<Option<KlineTick>>>,
+}
+/// 构建信息 (GET /version)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    /// 构建时间 (Unix 秒)
+    pub build_timestamp: &'static str,
+}
+/// 声明式报警规则的比较方式
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AlertComparator {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = "<")]
+    Lt,
+    /// 绝对值大于 (涨跌幅双向触发)
+    #[serde(rename = "abs>")]
+    AbsGt,
+}
+/// 规则作用的数据源
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertRuleSource {
+    #[default]
+    Hotlist,
+    Meme,
+}
+/// 声明式报警规则：对数据项的某个字段做比较，可选最小成交额门槛。
+/// 字段名为 serde 字段名 (如 priceChange1h)；volumeXxUsd 表示 volumeXx × price。
+/// message 模板支持 {symbol} {value} {abs} {direction}，数值保留 decimals 位小数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRule {
+    /// 规则名，用作冷却键与按类型冷却配置的键 (不能包含 ':')
+    pub name: String,
+    #[serde(default)]
+    pub source: AlertRuleSource,
+    pub field: String,
+    pub comparator: AlertComparator,
+    pub threshold: f64,
+    /// 门槛字段 (同样支持 volumeXxUsd)，其值需大于 min_volume 才触发
+    #[serde(default)]
+    pub min_volume_field: Option<String>,
+    #[serde(default)]
+    pub min_volume: f64,
+    pub message: String,
+    #[serde(default)]
+    pub decimals: usize,
+}
+/// 报警阈值 (可通过 /admin/alert-config 运行时调整，立即对后续报警检测生效)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AlertThresholds {
+    pub volume_1m_usd: f64,
+    pub volume_5m_usd: f64,
+    pub price_change_1m_percent: f64,
+    pub price_change_5m_percent: f64,
+    /// 价格异动需满足的最小成交额
+    pub price_change_1m_min_volume_usd: f64,
+    pub price_change_5m_min_volume_usd: f64,
+    pub buy_pressure_min_ratio: f64,
+    pub buy_pressure_min_buys: i64,
+    pub buy_pressure_max_top10_percent: f64,
+}
+/// 管理接口：指定单个 Token
+#[derive(Debug, Deserialize)]
+pub struct AdminTokenRequest {
+    pub chain: String,
+    pub address: String,
+}
+/// 管理接口：按地址增删名单
+#[derive(Debug, Deserialize)]
+pub struct AdminAddressRequest {
+    pub address: String,
+}
+/// 管理接口：手动触发清理 (POST /admin/prune)
+#[derive(Debug, Deserialize)]
+pub struct AdminPruneRequest {
+    pub target: PruneTarget,
+}
+/// 管理接口：运行时修改日志级别 (POST /admin/log-level)
+#[derive(Debug, Deserialize)]
+pub struct AdminLogLevelRequest {
+    /// EnvFilter 指令，如 "backend=info,backend::token_manager=debug"
+    pub filter: String,
+}
+/// 可手动清理的数据
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PruneTarget {
+    /// 24 小时前的流动性历史
+    Liquidity,
+    /// 过期的黑名单记录
+    Blacklist,
+    /// 超过保留期不再更新的代币 K 线
+    Klines,
+    /// 过期的报警冷却记录 (内存)
+    Alerts,
+}
+/// HTTP 获取 K 线参数 (GET /klines)
+#[derive(Debug, Deserialize)]
+pub struct KlineHttpQuery {
+    pub address: String,
+    pub chain: String,
+    pub interval: String,
+    /// 只返回最近的 N 根 (默认全部缓存，最多 500)
+    pub limit: Option<usize>,
+    /// 是否在后台触发一次上游补齐 (默认 false)
+    pub sync: Option<bool>,
+    /// 是否补齐缺口 (默认 true)
+    pub fill_gaps: Option<bool>,
+}
+/// K 线批量导出参数 (GET /klines/export)
+#[derive(Debug, Deserialize)]
+pub struct KlineExportQuery {
+    pub address: String,
+    pub chain: String,
+    pub interval: String,
+}
+/// 当前价格查询参数 (GET /price)
+#[derive(Debug, Deserialize)]
+pub struct PriceQuery {
+    pub address: String,
+    pub chain: String,
+    /// 查找实时房间与缓存 K 线使用的周期 (默认 1m)
+    pub interval: Option<String>,
+}
+/// 当前价格：source 为 live (实时房间) / db (最新缓存 K 线) / upstream (实时拉取)
+#[derive(Debug, Serialize)]
+pub struct PriceResponse {
+    pub price: f64,
+    /// 对应 K 线的开盘时间 (毫秒)
+    pub time: i64,
+    pub source: &'static str,
+}
+/// 流动性历史导出参数 (GET /liquidity)
+#[derive(Debug, Deserialize)]
+pub struct LiquidityExportQuery {
+    pub address: String,
+    /// 聚合周期 (1m / 5m / 15m / 1h)，默认 1m
+    pub interval: Option<String>,
+    /// 输出格式 (json / csv)，默认 json
+    pub format: Option<String>,
 }
 #[derive(Debug, Deserialize)]
 pub struct ImageProxyQuery {
     pub url: String,
+    /// 缓存类型 ("icon" / "banner")，缺省为 icon
+    pub kind: Option<String>,
+}
+/// 取得结构体的 serde 字段名 (已应用 rename_all)：serde 派生的 Deserialize 会把字段表传给
+/// `deserialize_struct`，这里用一个只记录字段表的 Deserializer 截获它
+pub fn serde_field_names<T: serde::de::DeserializeOwned>() -> &'static [&'static str] {
+    use serde::de::{self, Visitor};
+
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("field name introspection only"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("field name introspection only"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// 爬虫可抓取的字段名 (MemeScanItem 与 HotlistItem 的并集)，用于校验 desired_fields
+pub fn known_item_fields() -> HashSet<&'static str> {
+    serde_field_names::<MemeScanItem>()
+        .iter()
+        .chain(serde_field_names::<HotlistItem>())
+        .copied()
+        .collect()
+}
+
+/// gzip 压缩后的 socket 负载：客户端按 encoding 解压 data 得到原始 JSON
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressedPayload {
+    pub encoding: &'static str,
+    pub original_size: usize,
+    pub data: bytes::Bytes,
+}
+/// Worker 生命周期事件 (GET /worker-events)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerEventRecord {
+    pub address: String,
+    /// spawned / connected / disconnected / shutdown
+    pub event: String,
+    pub detail: Option<String>,
+    /// 毫秒时间戳
+    pub created_at: i64,
+}
+/// Worker 生命周期事件查询参数 (GET /worker-events)
+#[derive(Debug, Deserialize)]
+pub struct WorkerEventsQuery {
+    /// 代币地址，缺省为全部
+    pub address: Option<String>,
+    /// 起止时间 (毫秒)，缺省为不限
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// 最多返回条数 (默认 200，上限 1000)
+    pub limit: Option<i64>,
+}
+/// 代币元数据 (持久化于 tokens 表，重启后恢复 symbol / decimals 映射)
+#[derive(Debug, Clone)]
+pub struct TokenMetadata {
+    pub chain: String,
+    pub address: String,
+    pub symbol: String,
+    pub decimals: Option<i64>,
+    pub icon: Option<String>,
+    pub last_seen: i64,
 }
 #[derive(Serialize, Deserialize)]
 pub struct CacheMeta {
@@ -386,10 +865,82 @@ mod tests {
         DataAction::export().expect("Failed to export DataAction");
         DataPayload::export().expect("Failed to export DataPayload");
         DataPayload::export().expect("Failed to export DataPayload");
-        KlineTick::export().expect("Failed to export KlineTick");
+        KlineTickWire::export().expect("Failed to export KlineTick");
         LiquidityPoint::export().expect("Failed to export LiquidityPoint");
         KlineHistoryResponse::export().expect("Failed to export KlineHistoryResponse");
         AlertType::export().expect("Failed to export AlertType");
         AlertLogEntry::export().expect("Failed to export AlertLogEntry");
+        TimedNarrative::export().expect("Failed to export TimedNarrative");
+        RemovedToken::export().expect("Failed to export RemovedToken");
+    }
+
+    #[test]
+    fn tick_side_follows_tracked_token() {
+        let tick = |tp: &str| BinanceTickDetail {
+            t0a: "a".into(),
+            t1a: "b".into(),
+            t0pu: 1.0,
+            t1pu: 1.0,
+            v: 1.0,
+            a0: 1.0,
+            a1: 1.0,
+            tp: tp.into(),
+        };
+        assert_eq!(tick("buy").side(true), Some(TradeSide::Buy));
+        assert_eq!(tick("SELL").side(true), Some(TradeSide::Sell));
+        assert_eq!(tick("buy").side(false), Some(TradeSide::Sell));
+        assert_eq!(tick("swap").side(true), None);
+    }
+
+    #[test]
+    fn parse_kline_validates_frame_values() {
+        let values = |open: &str, ts: &str| (open.to_string(), "1.2".to_string(), "0.9".to_string(), "1.1".to_string(), "250".to_string(), ts.to_string());
+        let kline = parse_kline(&values("1.0", "1700000040000")).unwrap();
+        assert_eq!(kline.time.timestamp(), 1_700_000_040);
+        assert_eq!((kline.open, kline.close, kline.volume), (1.0, 1.1, 250.0));
+        assert!(parse_kline(&values("0", "1700000040000")).is_none());
+        assert!(parse_kline(&values("1.0", "garbage")).is_none());
+
+        // high < low
+        let inverted = ("1.0".to_string(), "0.8".to_string(), "0.9".to_string(), "0.85".to_string(), "250".to_string(), "1700000040000".to_string());
+        assert!(parse_kline(&inverted).is_none());
+
+        // 超出允许时钟偏差的未来时间戳；偏差以内的仍保留
+        let now_ms = Utc::now().timestamp_millis();
+        let future_ms = now_ms + (KLINE_MAX_FUTURE_SKEW_SECS + 60) * 1000;
+        assert!(parse_kline(&values("1.0", &future_ms.to_string())).is_none());
+        assert!(parse_kline(&values("1.0", &(now_ms + 5_000).to_string())).is_some());
+    }
+
+    #[test]
+    fn kline_tick_serializes_seconds_and_millis() {
+        let kline = KlineTick {
+            time: DateTime::from_timestamp(1_700_000_040, 0).unwrap(),
+            open: 1.0,
+            high: 1.2,
+            low: 0.9,
+            close: 1.1,
+            volume: 250.0,
+        };
+        assert_eq!(
+            serde_json::to_value(&kline).unwrap(),
+            serde_json::json!({
+                "time": 1_700_000_040i64,
+                "timeMs": 1_700_000_040_000i64,
+                "open": 1.0,
+                "high": 1.2,
+                "low": 0.9,
+                "close": 1.1,
+                "volume": 250.0
+            })
+        );
+    }
+
+    #[test]
+    fn known_item_fields_use_serde_names() {
+        let fields = known_item_fields();
+        assert!(fields.contains("contractAddress"));
+        assert!(fields.contains("priceChange24h"));
+        assert!(!fields.contains("contract_address"));
     }
 }
\ No newline at end of file
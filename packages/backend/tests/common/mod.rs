@@ -0,0 +1,124 @@
+// packages/backend/tests/common/mod.rs
+// 集成测试共用桩：完整 socket.io 服务、mock 上游 (HTTP 接口 / Binance WebSocket)、手写 Engine.IO v4 客户端
+// 各测试文件只用到其中一部分
+#![allow(dead_code)]
+
+use backend::{config::Config, setup_shared_state, socket_handlers, ServerState};
+use futures_util::{SinkExt, StreamExt};
+use socketioxide::SocketIo;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+pub type SocketClient = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// 每个测试独立的临时 SQLite 文件
+pub fn temp_db_path(prefix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}.db", prefix, uuid::Uuid::new_v4()))
+}
+
+/// 测试基础配置：直连 (不走代理)、独立数据库、连接池不错峰
+pub fn test_config(db_path: &Path) -> Config {
+    let mut config = Config::new();
+    config.proxy_addr = "none".to_string();
+    config.database_url = format!("sqlite:{}", db_path.display());
+    config.pool_init_stagger = Duration::ZERO;
+    config
+}
+
+/// 在随机端口上启动 axum 应用 (mock HTTP 接口)
+pub async fn spawn_http(app: axum::Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    addr
+}
+
+/// 启动完整的 socket.io 服务 (与 bin 相同的 setup_shared_state + register_namespaces)
+pub async fn spawn_server(config: Config) -> (ServerState, String) {
+    let (layer, io) = SocketIo::new_layer();
+    let state = setup_shared_state(Arc::new(config), io).await;
+    socket_handlers::register_namespaces(&state);
+
+    let addr = spawn_http(axum::Router::new().layer(layer)).await;
+    (state, format!("ws://{}/socket.io/?EIO=4&transport=websocket", addr))
+}
+
+/// mock Binance 上游：收到包含 stream 的 SUBSCRIBE 后 (stream 为空时任意 SUBSCRIBE) 依次推送 frames
+pub async fn spawn_mock_binance(stream: &str, frames: Vec<String>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let stream = stream.to_string();
+
+    tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Text(text) = msg {
+                if text.contains("SUBSCRIBE") && text.contains(stream.as_str()) {
+                    break;
+                }
+            }
+        }
+
+        for frame in frames {
+            ws.send(Message::Text(frame.into())).await.unwrap();
+        }
+
+        // 保持连接，避免 worker 进入重连
+        while ws.next().await.is_some() {}
+    });
+
+    format!("ws://{}/w3w/stream", addr)
+}
+
+/// 连接 socket.io 服务并完成默认命名空间握手
+pub async fn connect_socket(socket_url: &str) -> SocketClient {
+    let (mut client, _) = tokio_tungstenite::connect_async(socket_url).await.unwrap();
+    next_frame(&mut client, "namespace connect", |text| text.starts_with("40").then_some(())).await;
+    client
+}
+
+/// 发送事件；ack_id 非空时请求服务端 ack (回复帧为 "43{ack_id}[...]")
+pub async fn emit(client: &mut SocketClient, event: serde_json::Value, ack_id: Option<u32>) {
+    let ack_id = ack_id.map(|id| id.to_string()).unwrap_or_default();
+    client.send(Message::Text(format!("42{}{}", ack_id, event).into())).await.unwrap();
+}
+
+/// 读取帧直到 on_frame 返回 Some (10s 超时)；自动完成 Engine.IO open 与心跳
+pub async fn next_frame<T>(client: &mut SocketClient, what: &str, mut on_frame: impl FnMut(&str) -> Option<T>) -> T {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while let Some(Ok(msg)) = client.next().await {
+            let Message::Text(text) = msg else { continue; };
+            let text = text.as_str();
+            if text.starts_with('0') {
+                // Engine.IO open -> 连接默认命名空间
+                client.send(Message::Text("40".into())).await.unwrap();
+            } else if text == "2" {
+                client.send(Message::Text("3".into())).await.unwrap();
+            } else if let Some(value) = on_frame(text) {
+                return value;
+            }
+        }
+        panic!("socket closed before {}", what);
+    })
+    .await
+    .unwrap_or_else(|_| panic!("no {} received", what))
+}
+
+/// 解析 "42[name, data]" 事件帧
+pub fn parse_event(text: &str) -> Option<(String, serde_json::Value)> {
+    let event: serde_json::Value = serde_json::from_str(text.strip_prefix("42")?).ok()?;
+    Some((event[0].as_str()?.to_string(), event[1].clone()))
+}
+
+/// 等待指定名称的事件，返回其数据
+pub async fn wait_for_event(client: &mut SocketClient, name: &str) -> serde_json::Value {
+    next_frame(client, name, |text| parse_event(text).filter(|(event, _)| event == name).map(|(_, data)| data)).await
+}
@@ -0,0 +1,37 @@
+// packages/backend/tests/hotlist_flow.rs
+// 服务端 Hotlist 快照：全量快照经过滤后为空时同样替换缓存，新连接不会再拿到旧列表
+mod common;
+
+use backend::{socket_handlers, types::DataPayload};
+use common::{spawn_server, temp_db_path, test_config};
+
+fn hotlist_payload(items: Vec<serde_json::Value>) -> DataPayload {
+    serde_json::from_value(serde_json::json!({ "category": "hotlist", "type": "full", "data": items })).unwrap()
+}
+
+#[tokio::test]
+async fn filtered_empty_snapshot_replaces_cached_hotlist() {
+    let db_path = temp_db_path("backend-hotlist");
+    let (state, _socket_url) = spawn_server(test_config(&db_path)).await;
+
+    let address = "0x5555555555555555555555555555555555555555";
+    let hot = serde_json::json!({
+        "chain": "bsc", "contractAddress": address, "symbol": "HOT",
+        "price": 1.0, "volume24h": 100000.0, "liquidity": 50000.0
+    });
+    assert!(socket_handlers::process_data_update(&state, &mut hotlist_payload(vec![hot])).await);
+    assert_eq!(state.last_hotlist.lock().await.len(), 1);
+
+    // 同一代币流动性跌破阈值：快照过滤后为空，仍替换缓存并广播清空
+    let drained = serde_json::json!({
+        "chain": "bsc", "contractAddress": address, "symbol": "HOT",
+        "price": 1.0, "volume24h": 100000.0, "liquidity": 10.0
+    });
+    assert!(socket_handlers::process_data_update(&state, &mut hotlist_payload(vec![drained.clone()])).await);
+    assert!(state.last_hotlist.lock().await.is_empty());
+
+    // 缓存已为空时，空快照不再重复广播
+    assert!(!socket_handlers::process_data_update(&state, &mut hotlist_payload(vec![drained])).await);
+
+    let _ = std::fs::remove_file(db_path);
+}
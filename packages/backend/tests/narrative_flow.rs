@@ -0,0 +1,215 @@
+// packages/backend/tests/narrative_flow.rs
+// 端到端：爬虫经 socket 推送 meme_new -> enrich_any_data 后台抓取叙事 (mock 叙事接口)
+// -> 抓取完成后客户端收到 narrative_update；并发 payload 对同一代币只抓取一次；过期后重新抓取并追加历史
+mod common;
+
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use backend::{config::Config, socket_handlers, types::DataPayload};
+use common::{connect_socket, emit, next_frame, parse_event, spawn_http, spawn_server, temp_db_path};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// mock 叙事接口收到的请求 (contractAddress, 到达时间)
+type NarrativeRequests = Arc<Mutex<Vec<(String, Instant)>>>;
+
+async fn narrative_handler(
+    State(requests): State<NarrativeRequests>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<serde_json::Value> {
+    let address = query.get("contractAddress").cloned().unwrap_or_default();
+    // 同一地址再次请求时返回新版本 ("story of X v2" ...)，模拟叙事更新
+    let version = {
+        let mut requests = requests.lock().unwrap();
+        requests.push((address.clone(), Instant::now()));
+        requests.iter().filter(|(a, _)| *a == address).count()
+    };
+    let text = if version == 1 { format!("story of {}", address) } else { format!("story of {} v{}", address, version) };
+    Json(serde_json::json!({
+        "code": "000000",
+        "data": { "text": { "cn": text } },
+        "success": true
+    }))
+}
+
+async fn spawn_mock_narrative_api() -> (String, NarrativeRequests) {
+    let requests = NarrativeRequests::default();
+    let app = Router::new().route("/narrative", get(narrative_handler)).with_state(requests.clone());
+    let addr = spawn_http(app).await;
+    (format!("http://{}/narrative", addr), requests)
+}
+
+fn test_config(api_url: String, db_path: &std::path::Path) -> Config {
+    let mut config = common::test_config(db_path);
+    config.narrative_api_url = api_url;
+    config.narrative_pool_size = 2;
+    config
+}
+
+fn meme_item(address: &str) -> serde_json::Value {
+    serde_json::json!({
+        "chain": "bsc", "contractAddress": address, "symbol": "MEME", "name": "Meme",
+        "decimal": 18, "createTime": 0, "migrateTime": 0, "displayTime": 0,
+        "holders": 10, "count": 1, "countBuy": 1, "countSell": 0, "devMigrateCount": 0,
+        "progress": 0.5, "buySellRatio": 1.0,
+        "holdersTop10Percent": 50.0, "holdersDevPercent": 0.0, "holdersSniperPercent": 0.0,
+        "holdersInsiderPercent": 0.0, "devSellPercent": 0.0,
+        "sensitiveToken": false, "exclusive": false, "paidOnDexScreener": false
+    })
+}
+
+/// 以爬虫身份连接，连上后推送 payloads 中的每个 data-update，收集 narrative_update 直到收满 expected 条
+async fn push_and_collect_updates(socket_url: String, payloads: Vec<serde_json::Value>, expected: usize) -> Vec<serde_json::Value> {
+    let mut client = connect_socket(&socket_url).await;
+    for payload in payloads {
+        emit(&mut client, serde_json::json!(["data-update", payload]), None).await;
+    }
+    let mut updates = Vec::new();
+    next_frame(&mut client, "narrative_update", |text| {
+        let (name, data) = parse_event(text)?;
+        if name == "narrative_update" {
+            updates.push(data);
+        }
+        (updates.len() == expected).then_some(())
+    })
+    .await;
+    updates
+}
+
+#[tokio::test]
+async fn late_narrative_is_pushed_as_update() {
+    let db_path = temp_db_path("backend-narrative");
+    let (api_url, requests) = spawn_mock_narrative_api().await;
+    let (state, socket_url) = spawn_server(test_config(api_url, &db_path)).await;
+
+    let address = "0x1111111111111111111111111111111111111111";
+    let payload = serde_json::json!({ "category": "meme_new", "type": "full", "data": [meme_item(address)] });
+    let updates = push_and_collect_updates(socket_url, vec![payload], 1).await;
+
+    assert_eq!(updates[0]["address"], address);
+    assert_eq!(updates[0]["narrative"], format!("story of {}", address));
+    assert_eq!(requests.lock().unwrap().len(), 1);
+    let cached = state.narrative_cache.get(address).expect("narrative cached");
+    assert_eq!(cached.latest(), Some(format!("story of {}", address).as_str()));
+
+    let _ = std::fs::remove_file(db_path);
+}
+
+/// 一批叙事抓取到达 mock 接口的时间跨度 (最早 -> 最晚)
+async fn fetch_spread(stagger: Duration, max_spread: Duration, tokens: usize) -> Duration {
+    let db_path = temp_db_path("backend-narrative");
+    let (api_url, requests) = spawn_mock_narrative_api().await;
+    let mut config = test_config(api_url, &db_path);
+    config.narrative_fetch_stagger = stagger;
+    config.narrative_fetch_max_spread = max_spread;
+    let (_state, socket_url) = spawn_server(config).await;
+
+    let items: Vec<_> = (0..tokens).map(|i| meme_item(&format!("0x{:040x}", i + 1))).collect();
+    let payload = serde_json::json!({ "category": "meme_new", "type": "full", "data": items });
+    push_and_collect_updates(socket_url, vec![payload], tokens).await;
+
+    let times: Vec<Instant> = requests.lock().unwrap().iter().map(|(_, at)| *at).collect();
+    assert_eq!(times.len(), tokens);
+    let _ = std::fs::remove_file(db_path);
+    *times.iter().max().unwrap() - *times.iter().min().unwrap()
+}
+
+#[tokio::test]
+async fn narrative_fetches_are_staggered() {
+    // 3 个代币按 300ms 错峰：首尾间隔约 600ms
+    let spread = fetch_spread(Duration::from_millis(300), Duration::from_secs(10), 3).await;
+    assert!(spread >= Duration::from_millis(550), "spread {:?}", spread);
+}
+
+#[tokio::test]
+async fn narrative_fetch_spread_is_capped() {
+    // 错峰 1s 但总跨度上限 400ms：3 个代币压缩为每步 200ms
+    let spread = fetch_spread(Duration::from_secs(1), Duration::from_millis(400), 3).await;
+    assert!(spread >= Duration::from_millis(350), "spread {:?}", spread);
+    assert!(spread < Duration::from_millis(900), "spread {:?}", spread);
+}
+
+#[tokio::test]
+async fn concurrent_payloads_fetch_each_narrative_once() {
+    let db_path = temp_db_path("backend-narrative");
+    let (api_url, requests) = spawn_mock_narrative_api().await;
+    let (state, _socket_url) = spawn_server(test_config(api_url, &db_path)).await;
+
+    // 两个同时到达、代币重叠的 payload 走真实的 process_data_update -> enrich_any_data
+    let addresses = ["0x2222222222222222222222222222222222222222", "0x3333333333333333333333333333333333333333"];
+    let payload = || -> DataPayload {
+        let items: Vec<_> = addresses.iter().map(|a| meme_item(a)).collect();
+        serde_json::from_value(serde_json::json!({ "category": "meme_new", "type": "full", "data": items })).unwrap()
+    };
+    let (mut first, mut second) = (payload(), payload());
+    tokio::join!(
+        socket_handlers::process_data_update(&state, &mut first),
+        socket_handlers::process_data_update(&state, &mut second),
+    );
+
+    for _ in 0..100 {
+        if addresses.iter().all(|a| state.narrative_cache.get(*a).is_some_and(|e| !e.pending)) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let mut fetched: Vec<String> = requests.lock().unwrap().iter().map(|(addr, _)| addr.clone()).collect();
+    fetched.sort();
+    assert_eq!(fetched, addresses);
+
+    // 一个 payload 占位抓取 (misses)，另一个看到抓取中 (pending_skips)
+    let stats = state.narrative_stats.snapshot(state.narrative_cache.len());
+    assert_eq!(stats.misses, 2);
+    assert_eq!(stats.pending_skips, 2);
+    assert_eq!(stats.fetch_ok, 2);
+
+    let _ = std::fs::remove_file(db_path);
+}
+
+#[tokio::test]
+async fn stale_narrative_is_refetched_and_history_grows() {
+    let db_path = temp_db_path("backend-narrative");
+    let (api_url, requests) = spawn_mock_narrative_api().await;
+    let mut config = test_config(api_url, &db_path);
+    config.narrative_refresh_after = Some(Duration::from_millis(300));
+    let (state, _socket_url) = spawn_server(config).await;
+
+    let address = "0x4444444444444444444444444444444444444444";
+    let payload = || -> DataPayload {
+        serde_json::from_value(serde_json::json!({ "category": "meme_new", "type": "full", "data": [meme_item(address)] })).unwrap()
+    };
+    let wait_fetched = || async {
+        for _ in 0..100 {
+            if state.narrative_cache.get(address).is_some_and(|e| !e.pending) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("narrative fetch did not finish");
+    };
+
+    socket_handlers::process_data_update(&state, &mut payload()).await;
+    wait_fetched().await;
+    // 未过期：命中缓存，不再请求
+    socket_handlers::process_data_update(&state, &mut payload()).await;
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    // 过期后再次出现：重新抓取，新版本追加到历史
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    socket_handlers::process_data_update(&state, &mut payload()).await;
+    wait_fetched().await;
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    let entry = state.narrative_cache.get(address).unwrap();
+    let history: Vec<&str> = entry.history.iter().map(|n| n.text.as_str()).collect();
+    assert_eq!(history, [format!("story of {}", address), format!("story of {} v2", address)]);
+    drop(entry);
+
+    let stats = state.narrative_stats.snapshot(state.narrative_cache.len());
+    assert_eq!((stats.misses, stats.hits), (2, 1));
+
+    let _ = std::fs::remove_file(db_path);
+}
@@ -0,0 +1,212 @@
+// packages/backend/tests/subscribe_flow.rs
+// 端到端：真实 socket.io 客户端 (手写 Engine.IO v4 over WebSocket) 订阅 K 线 -> 创建 Worker -> mock 上游推送 Tick
+// -> 房间 current_kline 更新并收到 kline_update，覆盖房间名 / 地址规范化这条核心链路，以及重复订阅的短路确认；
+// 另覆盖历史补齐按 Solana 原始大小写找到房间并校准临时 K 线，以及 noCache 请求同样推送 completed 并初始化房间 K 线
+mod common;
+
+use backend::{
+    kline_handler::spawn_kline_sync,
+    types::{KlineSubscribePayload, KlineTick, Room},
+};
+use axum::{routing::get, Json};
+use chrono::{DateTime, Utc};
+use common::{connect_socket, emit, next_frame, parse_event, spawn_http, spawn_mock_binance, spawn_server, temp_db_path, test_config, wait_for_event};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+// 混合大小写的 EVM 地址：订阅、房间名与 Tick 路由都应统一为小写
+const ADDRESS: &str = "0xAbC0000000000000000000000000000000DeF123";
+const INTERVAL: &str = "1d";
+const PRICE: f64 = 2.5;
+
+fn tick_frame() -> String {
+    serde_json::json!({
+        "stream": format!("tx@14_{}", ADDRESS.to_lowercase()),
+        "data": { "d": {
+            "t0a": ADDRESS,
+            "t1a": "0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c",
+            "t0pu": PRICE,
+            "t1pu": 600.0,
+            "v": 500.0,
+            "a0": 10.0,
+            "a1": 0.1,
+            "tp": "buy"
+        } }
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn subscribe_then_tick_updates_room_and_broadcasts() {
+    let db_path = temp_db_path("backend-e2e");
+    let mut config = test_config(&db_path);
+    // mock 上游：收到 Tick 流的 SUBSCRIBE 后推送一条 Tick
+    config.binance_wss_url = spawn_mock_binance("tx@", vec![tick_frame()]).await;
+    let (state, socket_url) = spawn_server(config).await;
+
+    let mut client = connect_socket(&socket_url).await;
+    let room_key = format!("kl@14@{}@{}", ADDRESS.to_lowercase(), INTERVAL);
+    let subscribe = serde_json::json!(["subscribe_kline", { "address": ADDRESS, "chain": "BSC", "interval": INTERVAL }]);
+
+    // 带 ack id 1 的订阅
+    emit(&mut client, subscribe.clone(), Some(1)).await;
+    let mut ack = None;
+    let update = next_frame(&mut client, "kline_update", |text| {
+        if let Some(reply) = text.strip_prefix("431") {
+            let reply: serde_json::Value = serde_json::from_str(reply).unwrap();
+            ack = Some(reply[0].clone());
+            return None;
+        }
+        parse_event(text).filter(|(name, _)| name == "kline_update").map(|(_, data)| data)
+    })
+    .await;
+
+    // 订阅确认先于首个 Tick 到达
+    let ack = ack.expect("no subscribe ack received");
+    assert_eq!(ack["ok"], true);
+    assert_eq!(ack["room"], room_key.as_str());
+    assert_eq!(update["room"], room_key.as_str());
+    assert_eq!(update["data"]["close"], PRICE);
+
+    // 服务端状态：Worker 以小写地址登记，房间 K 线由首个 Tick 建立
+    assert!(state.token_managers.contains_key(&ADDRESS.to_lowercase()));
+    let current = state.app_state.get(&room_key).expect("room exists").current_kline.clone();
+    let kline = current.lock().await.clone().expect("kline initialized");
+    assert_eq!(kline.close, PRICE);
+    assert_eq!(kline.volume, 500.0);
+
+    // 同一 socket 重复订阅：直接确认，客户端集合不变
+    emit(&mut client, subscribe, None).await;
+    let ack = wait_for_event(&mut client, "already_subscribed").await;
+    assert_eq!(ack["room"], room_key.as_str());
+    assert_eq!(state.app_state.get(&room_key).unwrap().clients.len(), 1);
+
+    let _ = std::fs::remove_file(db_path);
+}
+
+fn kline(time: DateTime<Utc>, open: f64, high: f64, low: f64, close: f64, volume: f64) -> KlineTick {
+    KlineTick { time, open, high, low, close, volume }
+}
+
+fn room_with(current: Option<KlineTick>) -> Room {
+    Room {
+        clients: HashSet::new(),
+        symbol: "SOLMEME".to_string(),
+        current_kline: Arc::new(Mutex::new(current)),
+        last_emitted_kline: Arc::new(Mutex::new(None)),
+    }
+}
+
+#[tokio::test]
+async fn history_sync_reconciles_mixed_case_solana_room() {
+    // Solana 地址区分大小写：房间名保留原样，历史补齐必须用同样的 key 才能找到房间
+    const SOL_ADDRESS: &str = "So1anaMixedCaseMint1111111111111111111111pump";
+    let db_path = temp_db_path("backend-e2e");
+    let (state, _socket_url) = spawn_server(test_config(&db_path)).await;
+
+    let now = Utc::now().timestamp();
+    let minute = DateTime::from_timestamp(now - now % 60, 0).unwrap();
+    let five_minutes = DateTime::from_timestamp(now - now % 300, 0).unwrap();
+
+    // 1m 房间已由首个 Tick 建立临时 K 线；5m 房间尚无 K 线
+    let provisional = kline(minute, 2.0, 2.2, 1.9, 2.1, 5.0);
+    state.app_state.insert(format!("kl@16@{}@1m", SOL_ADDRESS), room_with(Some(provisional)));
+    state.app_state.insert(format!("kl@16@{}@5m", SOL_ADDRESS), room_with(None));
+
+    // DB 中已有历史，并标记为刚同步过，跳过上游拉取
+    let history_1m = kline(minute, 1.8, 2.0, 1.7, 1.9, 50.0);
+    let history_5m = kline(five_minutes, 1.5, 2.0, 1.4, 1.9, 300.0);
+    for (interval, history) in [("1m", &history_1m), ("5m", &history_5m)] {
+        let primary_key = format!("{}@sol@{}", SOL_ADDRESS, interval);
+        state.kline_store.save(&primary_key, std::slice::from_ref(history), 500).await.unwrap();
+        state.kline_last_sync.insert(primary_key, Utc::now().timestamp_millis());
+        let payload = KlineSubscribePayload {
+            address: SOL_ADDRESS.to_string(),
+            chain: "sol".to_string(),
+            interval: interval.to_string(),
+            fill_gaps: false,
+            no_cache: false,
+        };
+        spawn_kline_sync(state.clone(), payload, None);
+    }
+
+    let current_of = |interval: &str| state.app_state.get(&format!("kl@16@{}@{}", SOL_ADDRESS, interval)).unwrap().current_kline.clone();
+    let (current_1m, current_5m) = (current_of("1m"), current_of("5m"));
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let reconciled = current_1m.lock().await.as_ref().is_some_and(|k| k.open == history_1m.open);
+            if reconciled && current_5m.lock().await.is_some() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("history never reached the solana rooms");
+
+    // 临时 K 线：open/volume 以历史为准，close 保留实时值，极值取两者并集
+    let reconciled = current_1m.lock().await.clone().unwrap();
+    assert_eq!(reconciled.time, minute);
+    assert_eq!(reconciled.open, 1.8);
+    assert_eq!(reconciled.close, 2.1);
+    assert_eq!(reconciled.high, 2.2);
+    assert_eq!(reconciled.low, 1.7);
+    assert_eq!(reconciled.volume, 50.0);
+
+    // 空房间：直接以历史最新一根初始化
+    let initialized = current_5m.lock().await.clone().unwrap();
+    assert_eq!(initialized.time, history_5m.time);
+    assert_eq!(initialized.open, history_5m.open);
+    assert_eq!(initialized.volume, history_5m.volume);
+
+    let _ = std::fs::remove_file(db_path);
+}
+
+/// mock K 线接口：返回当天一根日线 (open 1.0 / close PRICE)
+async fn spawn_mock_kline_api(day_start_ms: i64) -> String {
+    let app = axum::Router::new().route(
+        "/candles",
+        get(move || async move { Json(serde_json::json!({ "data": [[1.0, 3.0, 0.5, PRICE, 42.0, day_start_ms]] })) }),
+    );
+    let addr = spawn_http(app).await;
+    format!("http://{}/candles?address={{address}}&interval={{interval}}&limit={{limit}}&platform={{platform}}", addr)
+}
+
+#[tokio::test]
+async fn no_cache_history_completes_and_seeds_room_kline() {
+    let db_path = temp_db_path("backend-e2e");
+    let now_ms = Utc::now().timestamp_millis();
+    let day_start_ms = now_ms - now_ms % 86_400_000;
+    let mut config = test_config(&db_path);
+    config.kline_api_url_template = spawn_mock_kline_api(day_start_ms).await;
+    let (state, socket_url) = spawn_server(config).await;
+
+    // 房间已存在但尚无 K 线 (如 Worker 刚建立、首个 Tick 未到)
+    let room_key = format!("kl@14@{}@{}", ADDRESS.to_lowercase(), INTERVAL);
+    state.app_state.insert(room_key.clone(), room_with(None));
+
+    let mut client = connect_socket(&socket_url).await;
+    let request = serde_json::json!(["request_historical_kline", {
+        "address": ADDRESS, "chain": "BSC", "interval": INTERVAL, "noCache": true, "fillGaps": false
+    }]);
+    emit(&mut client, request, None).await;
+    let mut events = Vec::new();
+    let completed = next_frame(&mut client, "historical_kline_completed", |text| {
+        let (name, data) = parse_event(text)?;
+        events.push(name.clone());
+        (name == "historical_kline_completed").then_some(data)
+    })
+    .await;
+    assert_eq!(completed["data"][0]["close"], PRICE);
+
+    assert_eq!(events, ["historical_kline_initial", "historical_kline_completed"]);
+    let current = state.app_state.get(&room_key).unwrap().current_kline.clone();
+    let seeded = current.lock().await.clone().expect("room kline seeded from upstream history");
+    assert_eq!(seeded.time.timestamp_millis(), day_start_ms);
+    assert_eq!(seeded.open, 1.0);
+    assert_eq!(seeded.close, PRICE);
+
+    let _ = std::fs::remove_file(db_path);
+}
@@ -0,0 +1,254 @@
+// packages/backend/tests/token_worker.rs
+// 使用本地 mock WebSocket 服务器驱动 TokenWorker，验证 Kline / Tick 帧的解析与房间更新
+mod common;
+
+use backend::{
+    config::Config,
+    state::{self, AppState, SubscriptionCommand, WorkerEventLog, WorkerMetrics},
+    token_manager,
+    types::{KlineTick, Room},
+};
+use common::spawn_mock_binance;
+use socketioxide::{extract::SocketRef, SocketIo};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+const POOL_ID: i64 = 16;
+const ADDRESS: &str = "MockSoLToken1111111111111111111111111111pump";
+// 用日线，避免测试期间跨越周期边界触发 K 线滚动
+const INTERVAL: &str = "1d";
+const DAY_MS: i64 = 86_400_000;
+
+/// 当前日线周期的起始时间 (毫秒)
+fn current_bucket_ms() -> i64 {
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    now_ms - now_ms % DAY_MS
+}
+
+fn kline_frame(open_time_ms: i64) -> String {
+    serde_json::json!({
+        "stream": format!("kl@{}@{}@{}", POOL_ID, ADDRESS, INTERVAL),
+        "data": { "d": { "u": ["1.0", "1.2", "0.9", "1.1", "250", open_time_ms.to_string()] } }
+    })
+    .to_string()
+}
+
+fn tick_frame(price: f64) -> String {
+    tick_frame_with_volume(price, 500.0)
+}
+
+fn tick_frame_with_volume(price: f64, volume_usd: f64) -> String {
+    serde_json::json!({
+        "stream": format!("tx@{}_{}", POOL_ID, ADDRESS),
+        "data": { "d": {
+            "t0a": ADDRESS,
+            "t1a": "So11111111111111111111111111111111111111112",
+            "t0pu": price,
+            "t1pu": 150.0,
+            "v": volume_usd,
+            "a0": 10.0,
+            "a1": 0.1,
+            "tp": "buy"
+        } }
+    })
+    .to_string()
+}
+
+async fn wait_for_kline<F>(app_state: &AppState, room_key: &str, pred: F) -> KlineTick
+where
+    F: Fn(&KlineTick) -> bool,
+{
+    let current = app_state.get(room_key).unwrap().current_kline.clone();
+    for _ in 0..100 {
+        if let Some(kline) = current.lock().await.as_ref() {
+            if pred(kline) {
+                return kline.clone();
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("room {} was not updated in time: {:?}", room_key, current.lock().await);
+}
+
+struct WorkerHarness {
+    app_state: AppState,
+    room_key: String,
+    worker: tokio::task::JoinHandle<()>,
+    metrics: Arc<WorkerMetrics>,
+    // 持有发送端，关闭通道会让 worker 退出
+    _cmd_tx: tokio::sync::mpsc::UnboundedSender<SubscriptionCommand>,
+}
+
+/// 建好房间与索引后启动 worker
+async fn start_worker(frames: Vec<String>) -> WorkerHarness {
+    start_worker_with_config(frames, Config::new(), &[]).await
+}
+
+/// extra_intervals：额外建立的周期房间 (同一代币的 Tick 会扇出到所有周期房间)
+async fn start_worker_with_config(frames: Vec<String>, mut config: Config, extra_intervals: &[&str]) -> WorkerHarness {
+    // mock 上游：等待客户端发来 SUBSCRIBE 后依次推送给定的帧
+    config.binance_wss_url = spawn_mock_binance("", frames).await;
+    config.proxy_addr = "none".to_string();
+
+    let (_layer, io) = SocketIo::new_layer();
+    // 广播需要默认命名空间存在
+    io.ns("/", |_s: SocketRef| async {});
+    let app_state = state::new_app_state();
+    let room_index = state::new_room_index();
+
+    let room_key = format!("kl@{}@{}@{}", POOL_ID, ADDRESS, INTERVAL);
+    let extra_rooms = extra_intervals.iter().map(|interval| format!("kl@{}@{}@{}", POOL_ID, ADDRESS, interval));
+    for key in std::iter::once(room_key.clone()).chain(extra_rooms) {
+        app_state.insert(
+            key.clone(),
+            Room {
+                clients: HashSet::new(),
+                symbol: "MOCK".to_string(),
+                current_kline: Arc::new(Mutex::new(None)),
+                last_emitted_kline: Arc::new(Mutex::new(None)),
+            },
+        );
+        room_index.entry(ADDRESS.to_string()).or_default().insert(key);
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    tx.send(SubscriptionCommand::Subscribe(room_key.clone())).unwrap();
+    let metrics = Arc::new(WorkerMetrics::default());
+
+    let worker = tokio::spawn(token_manager::start_token_worker(
+        ADDRESS.to_string(),
+        POOL_ID,
+        io,
+        Arc::new(config),
+        app_state.clone(),
+        room_index,
+        Arc::new(AtomicUsize::new(0)),
+        rx,
+        metrics.clone(),
+        WorkerEventLog::default(),
+    ));
+
+    WorkerHarness { app_state, room_key, worker, metrics, _cmd_tx: tx }
+}
+
+#[tokio::test]
+async fn worker_applies_kline_and_tick_frames_to_room() {
+    let bucket_ms = current_bucket_ms();
+    let WorkerHarness { app_state, room_key, worker, metrics, _cmd_tx } =
+        start_worker(vec![kline_frame(bucket_ms), tick_frame(1.5)]).await;
+
+    // Kline 帧：权威数据，整根替换
+    let kline = wait_for_kline(&app_state, &room_key, |k| k.volume == 250.0).await;
+    assert_eq!(kline.open, 1.0);
+    assert_eq!(kline.low, 0.9);
+    assert_eq!(kline.time.timestamp_millis(), bucket_ms);
+
+    // Tick 帧：更新 close / high / low，并累加成交额
+    let kline = wait_for_kline(&app_state, &room_key, |k| k.close == 1.5).await;
+    assert_eq!(kline.high, 1.5);
+    assert_eq!(kline.low, 0.9);
+    assert_eq!(kline.volume, 750.0);
+
+    // 帧吞吐计数
+    assert_eq!(metrics.klines_parsed.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.ticks_parsed.load(Ordering::Relaxed), 1);
+    // tick_frame 的 tp 为 "buy"，跟踪的是 t0
+    assert_eq!(metrics.buy_ticks.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.sell_ticks.load(Ordering::Relaxed), 0);
+    assert!(metrics.frames_received.load(Ordering::Relaxed) >= 2);
+
+    worker.abort();
+}
+
+#[tokio::test]
+async fn solana_tick_routes_to_case_preserved_room() {
+    // 回归：Tick 路由曾把流名中的地址一律小写，Solana 房间 (保留大小写) 查不到，出现 NO ROOMS 且图表不动
+    assert_ne!(ADDRESS, ADDRESS.to_lowercase());
+    let WorkerHarness { app_state, room_key, worker, metrics, _cmd_tx } = start_worker(vec![tick_frame(1.5)]).await;
+
+    // 没有 Kline 帧：只有经 room_index 找到房间，首个 Tick 才会建立临时 K 线
+    let kline = wait_for_kline(&app_state, &room_key, |k| k.close == 1.5).await;
+    assert_eq!(kline.volume, 500.0);
+    assert_eq!(metrics.broadcasts_emitted.load(Ordering::Relaxed), 1);
+
+    worker.abort();
+}
+
+#[tokio::test]
+async fn tick_after_period_end_rolls_into_new_kline() {
+    let bucket_ms = current_bucket_ms();
+    let frames = vec![kline_frame(bucket_ms - DAY_MS), tick_frame(1.5)];
+    let WorkerHarness { app_state, room_key, worker, _cmd_tx, .. } = start_worker(frames).await;
+
+    // 上一周期的极值不会带入新 K 线
+    let kline = wait_for_kline(&app_state, &room_key, |k| k.close == 1.5).await;
+    assert_eq!(kline.time.timestamp_millis(), bucket_ms);
+    assert_eq!(kline.open, 1.5);
+    assert_eq!(kline.high, 1.5);
+    assert_eq!(kline.low, 1.5);
+    assert_eq!(kline.volume, 500.0);
+
+    worker.abort();
+}
+
+#[tokio::test]
+async fn unchanged_kline_is_not_broadcast_again() {
+    let bucket_ms = current_bucket_ms();
+    let mut config = Config::new();
+    config.skip_unchanged_kline_updates = true;
+    let frames = vec![kline_frame(bucket_ms), kline_frame(bucket_ms), tick_frame(1.5)];
+    let WorkerHarness { app_state, room_key, worker, metrics, _cmd_tx } =
+        start_worker_with_config(frames, config, &[]).await;
+
+    wait_for_kline(&app_state, &room_key, |k| k.close == 1.5).await;
+    assert_eq!(metrics.klines_parsed.load(Ordering::Relaxed), 2);
+    // 第二个完全相同的 Kline 帧被跳过，Tick 改变了 K 线仍正常推送
+    assert_eq!(metrics.broadcasts_emitted.load(Ordering::Relaxed), 2);
+
+    worker.abort();
+}
+
+#[tokio::test]
+async fn tick_fans_out_to_every_interval_room_in_both_modes() {
+    // 0：所有代币走快照扇出；usize::MAX：始终直接遍历索引。两种方式结果一致
+    for snapshot_min_subscribers in [0, usize::MAX] {
+        let mut config = Config::new();
+        config.tick_fanout_snapshot_min_subscribers = snapshot_min_subscribers;
+        let WorkerHarness { app_state, room_key, worker, metrics, _cmd_tx } =
+            start_worker_with_config(vec![tick_frame(1.5)], config, &["1m", "1h"]).await;
+
+        let other_rooms = ["1m", "1h"].map(|interval| format!("kl@{}@{}@{}", POOL_ID, ADDRESS, interval));
+        for key in std::iter::once(&room_key).chain(&other_rooms) {
+            let kline = wait_for_kline(&app_state, key, |k| k.close == 1.5).await;
+            assert_eq!(kline.volume, 500.0);
+        }
+        assert_eq!(metrics.broadcasts_emitted.load(Ordering::Relaxed), 3);
+
+        worker.abort();
+    }
+}
+
+#[tokio::test]
+async fn trade_tick_is_emitted_once_per_tick_after_price_filter() {
+    // 第二笔为小额且价格偏离 10 倍的异常成交，被过滤；两笔正常成交各推送一次 trade_tick (不随周期房间数重复)
+    let frames = vec![tick_frame(1.5), tick_frame_with_volume(15.0, 1.0), tick_frame(1.6)];
+    let WorkerHarness { app_state, room_key, worker, metrics, _cmd_tx } =
+        start_worker_with_config(frames, Config::new(), &["1m", "1h"]).await;
+
+    let kline = wait_for_kline(&app_state, &room_key, |k| k.close == 1.6).await;
+    assert_eq!(kline.high, 1.6);
+    // trade_tick 在所有周期房间处理完后推送，稍等计数落定
+    for _ in 0..100 {
+        if metrics.trades_emitted.load(Ordering::Relaxed) >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(metrics.ticks_parsed.load(Ordering::Relaxed), 3);
+    assert_eq!(metrics.trades_emitted.load(Ordering::Relaxed), 2);
+
+    worker.abort();
+}
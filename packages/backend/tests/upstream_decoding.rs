@@ -0,0 +1,59 @@
+// packages/backend/tests/upstream_decoding.rs
+// 验证连接池中的客户端会根据 Content-Encoding 自动解压上游响应 (gzip / deflate)
+mod common;
+
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use backend::client_pool::ClientPool;
+use common::spawn_http;
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use std::io::Write;
+
+const BODY: &str = r#"{"code":"000000","data":{"text":{"cn":"测试叙事","en":"test narrative"}},"success":true}"#;
+
+async fn gzip_handler() -> impl IntoResponse {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(BODY.as_bytes()).unwrap();
+    (
+        [(header::CONTENT_ENCODING, "gzip"), (header::CONTENT_TYPE, "application/json")],
+        encoder.finish().unwrap(),
+    )
+}
+
+async fn deflate_handler() -> impl IntoResponse {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(BODY.as_bytes()).unwrap();
+    (
+        [(header::CONTENT_ENCODING, "deflate"), (header::CONTENT_TYPE, "application/json")],
+        encoder.finish().unwrap(),
+    )
+}
+
+async fn spawn_upstream() -> String {
+    let app = Router::new()
+        .route("/gzip", get(gzip_handler))
+        .route("/deflate", get(deflate_handler))
+        .route("/plain", get(|| async { BODY }));
+    format!("http://{}", spawn_http(app).await)
+}
+
+#[tokio::test]
+async fn pool_clients_decode_compressed_bodies() {
+    let base = spawn_upstream().await;
+    let pool = ClientPool::new(1, None, "TEST".to_string()).await;
+    let (_idx, client) = pool.get_client().await;
+
+    for path in ["/gzip", "/deflate", "/plain"] {
+        let text = client
+            .get(format!("{}{}", base, path))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(text, BODY, "body mismatch for {}", path);
+    }
+}
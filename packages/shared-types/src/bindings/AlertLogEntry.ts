@@ -12,4 +12,8 @@ export type AlertLogEntry = {
   message: string;
   timestamp: number;
   alertType: AlertType;
+  /**
+   * 触发的规则名 (内置规则与 alert_type 相同)
+   */
+  rule: string;
 };
@@ -7,4 +7,7 @@ export type AlertType =
   | "volume1m"
   | "volume5m"
   | "priceChange1m"
-  | "priceChange5m";
+  | "priceChange5m"
+  | "migration"
+  | "buyPressure"
+  | "custom";
@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
+/**
+ * 爬虫推送的数据语义：
+ * - snapshot / full：当前完整列表，整体替换
+ * - update：增量，只包含发生变化 (或新出现) 的行，按 contractAddress 覆盖到已有列表；
+ * 不会通过增量删除行，行的删除只能由下一次 snapshot / full 体现。
+ *
+ * Hotlist 的增量由服务端合并进快照后，以 full 广播给客户端 (爬虫启动后应先推送一次完整快照)
+ */
 export type DataAction = "snapshot" | "update" | "full" | "Unknown";
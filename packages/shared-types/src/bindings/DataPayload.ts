@@ -2,9 +2,12 @@
 import type { DataAction } from "./DataAction";
 import type { HotlistItem } from "./HotlistItem";
 import type { MemeScanItem } from "./MemeScanItem";
+import type { RemovedToken } from "./RemovedToken";
 
 export type DataPayload =
   | { "category": "hotlist"; type: DataAction; data: Array<HotlistItem> }
   | { "category": "meme_new"; type: DataAction; data: Array<MemeScanItem> }
   | { "category": "meme_migrated"; type: DataAction; data: Array<MemeScanItem> }
+  | { "category": "token_detail"; type: DataAction; data: MemeScanItem }
+  | { "category": "remove"; data: Array<RemovedToken> }
   | { "category": "Unknown" };
@@ -11,4 +11,17 @@ export type KlineHistoryResponse = {
    * 流动性历史（可选）
    */
   liquidityHistory: Array<LiquidityPoint> | null;
+  /**
+   * 代币精度 (来自 Meme 数据，已知时返回，供前端格式化价格轴)
+   */
+  decimals?: number;
+  /**
+   * 代币 symbol 与图标 (已知时返回，图表标题无需再从 data-update 中拼凑)
+   */
+  symbol?: string;
+  icon?: string;
+  /**
+   * 缓存中最新 K 线收盘距今的毫秒数 (仅首屏响应、且超过 kline_stale_threshold 时返回)
+   */
+  staleMs?: number;
 };
@@ -1,7 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
+/**
+ * KlineTick 的线上格式 (TS 类型名仍为 KlineTick)
+ */
 export type KlineTick = {
-  time: string;
+  /**
+   * 开盘时间 (秒)
+   */
+  time: number;
+  /**
+   * 开盘时间 (毫秒)
+   */
+  timeMs: number;
   open: number;
   high: number;
   low: number;
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 被移除 (下架 / rug) 的代币
+ */
+export type RemovedToken = { chain: string; address: string };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 带抓取时间的叙事版本 (`request_narrative_history` 返回的历史条目)
+ */
+export type TimedNarrative = {
+  text: string;
+  /**
+   * 毫秒级 Unix 时间戳（首次抓取到该版本的时间）
+   */
+  fetchedAt: number;
+};
@@ -10,6 +10,8 @@ export * from './bindings/AlertLogEntry';
 export * from './bindings/AlertType';
 export * from './bindings/LiquidityPoint';       // [NEW]
 export * from './bindings/KlineHistoryResponse'; // [NEW]
+export * from './bindings/TimedNarrative';
+export * from './bindings/RemovedToken';
 
 // ----------------------------------------------------------------------------
 // 2. 核心常量定义 (用于爬虫 Dynamic Extraction)